@@ -0,0 +1,129 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use markdown::{mdast::Node, to_mdast};
+use supamdx::{
+    ast::{find_all_matches, find_deepest_match, get_ancestor_chain, walk},
+    parser::get_parser_options,
+};
+use tower_lsp::lsp_types::Position;
+
+const DOCUMENT_SIZES: [usize; 3] = [100, 1_000, 10_000];
+const CHAIN_DEPTHS: [usize; 3] = [3, 10, 50];
+
+/// Builds a document of roughly `node_count` nodes. Every block contributes two
+/// nodes (the block and its text), and every fifth block is a heading, so
+/// headings make up 10% of the tree.
+fn build_document(node_count: usize) -> (Node, String, Position) {
+    let blocks = node_count / 2;
+    let text = (0..blocks)
+        .map(|i| {
+            if i % 5 == 0 {
+                format!("## Heading {}", i)
+            } else {
+                format!("Paragraph {}", i)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    // Point at the last block so the lookup has to scan past every sibling.
+    let position = Position {
+        line: ((blocks - 1) * 2) as u32,
+        character: 3,
+    };
+
+    (
+        to_mdast(&text, &get_parser_options()).unwrap(),
+        text,
+        position,
+    )
+}
+
+/// Builds a document whose ancestor chain at the returned position is `depth`
+/// nodes long: the root, nested block quotes, a paragraph and its text.
+fn build_nested_document(depth: usize) -> (Node, String, Position) {
+    let quotes = depth - 3;
+    let text = format!("{}Nested text", "> ".repeat(quotes));
+    let position = Position {
+        line: 0,
+        character: (quotes * 2 + 3) as u32,
+    };
+
+    (
+        to_mdast(&text, &get_parser_options()).unwrap(),
+        text,
+        position,
+    )
+}
+
+fn count_recursive(node: &Node) -> usize {
+    1 + node
+        .children()
+        .map(|children| children.iter().map(count_recursive).sum())
+        .unwrap_or(0)
+}
+
+fn count_walk(node: &Node) -> usize {
+    let mut count = 0;
+    walk(node, |_| count += 1);
+    count
+}
+
+fn bench_get_ancestor_chain(c: &mut Criterion) {
+    let mut group = c.benchmark_group("get_ancestor_chain");
+    for size in DOCUMENT_SIZES {
+        let (ast, text, position) = build_document(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &ast, |b, ast| {
+            b.iter(|| get_ancestor_chain(black_box(ast), black_box(&text), black_box(&position)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_traversal(c: &mut Criterion) {
+    let mut group = c.benchmark_group("traversal");
+    for size in DOCUMENT_SIZES {
+        let (ast, _, _) = build_document(size);
+        group.bench_with_input(BenchmarkId::new("walk", size), &ast, |b, ast| {
+            b.iter(|| count_walk(black_box(ast)))
+        });
+        group.bench_with_input(BenchmarkId::new("recursive", size), &ast, |b, ast| {
+            b.iter(|| count_recursive(black_box(ast)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_find_all_matches(c: &mut Criterion) {
+    let mut group = c.benchmark_group("find_all_matches");
+    for size in DOCUMENT_SIZES {
+        let (ast, _, _) = build_document(size);
+        group.bench_with_input(BenchmarkId::from_parameter(size), &ast, |b, ast| {
+            b.iter(|| find_all_matches(black_box(ast), |node| matches!(node, Node::Heading(_))))
+        });
+    }
+    group.finish();
+}
+
+fn bench_find_deepest_match(c: &mut Criterion) {
+    let mut group = c.benchmark_group("find_deepest_match");
+    for depth in CHAIN_DEPTHS {
+        let (ast, text, position) = build_nested_document(depth);
+        let chain = get_ancestor_chain(&ast, &text, &position);
+        assert_eq!(chain.len(), depth);
+
+        // Only the root matches, so the whole chain is searched.
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &chain, |b, chain| {
+            b.iter(|| find_deepest_match(black_box(chain), |node| matches!(node, Node::Root(_))))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_get_ancestor_chain,
+    bench_traversal,
+    bench_find_all_matches,
+    bench_find_deepest_match
+);
+criterion_main!(benches);
@@ -1,13 +1,15 @@
 use markdown::mdast::Node;
 use tower_lsp::lsp_types::Position;
 
-use crate::nodes::NodeExt;
+use crate::{encoding::to_byte_position, nodes::NodeExt};
 
-pub fn get_ancestor_chain<'a>(ast: &'a Node, position: &Position) -> Vec<&'a Node> {
+/// Returns the nodes spanning `position`, an LSP position in `text`, from the
+/// root down to the deepest one.
+pub fn get_ancestor_chain<'a>(ast: &'a Node, text: &str, position: &Position) -> Vec<&'a Node> {
     let mut ancestor_chain = Vec::new();
     let mut current_node = Some(ast);
 
-    if !ast.contains_position(&position) {
+    if !ast.contains_position(text, position) {
         return ancestor_chain;
     }
 
@@ -17,7 +19,7 @@ pub fn get_ancestor_chain<'a>(ast: &'a Node, position: &Position) -> Vec<&'a Nod
         let next = node.children().and_then(|children| {
             children
                 .iter()
-                .find(|child| child.contains_position(position))
+                .find(|child| child.contains_position(text, position))
         });
         current_node = next;
     }
@@ -38,6 +40,127 @@ where
     None
 }
 
+/// Visits every node of the tree in document order (pre-order, depth-first)
+/// without recursing, so deeply nested documents can't overflow the stack.
+pub fn walk<'a, F>(ast: &'a Node, mut visit: F)
+where
+    F: FnMut(&'a Node),
+{
+    let mut stack = vec![ast];
+
+    while let Some(node) = stack.pop() {
+        visit(node);
+        if let Some(children) = node.children() {
+            stack.extend(children.iter().rev());
+        }
+    }
+}
+
+pub fn find_all_matches<F>(ast: &Node, test: F) -> Vec<&Node>
+where
+    F: Fn(&Node) -> bool,
+{
+    let mut matches = Vec::new();
+    walk(ast, |node| {
+        if test(node) {
+            matches.push(node);
+        }
+    });
+
+    matches
+}
+
+/// Returns the names exported by the document's ESM blocks with `export
+/// const`, `let`, `var`, `function` or `class`, in order.
+pub fn extract_exports(ast: &Node) -> Vec<String> {
+    let mut exports = Vec::new();
+
+    for esm in find_all_matches(ast, |node| matches!(node, Node::MdxjsEsm(_))) {
+        for line in esm.to_string().lines() {
+            let Some(declaration) = line.strip_prefix("export ") else {
+                continue;
+            };
+            let declaration = declaration.strip_prefix("async ").unwrap_or(declaration);
+            let Some(rest) = ["const ", "let ", "var ", "function ", "class "]
+                .iter()
+                .find_map(|keyword| declaration.strip_prefix(keyword))
+            else {
+                continue;
+            };
+
+            let name: String = rest
+                .trim_start()
+                .chars()
+                .take_while(|ch| ch.is_alphanumeric() || matches!(ch, '_' | '$'))
+                .collect();
+            if !name.is_empty() && !exports.contains(&name) {
+                exports.push(name);
+            }
+        }
+    }
+
+    exports
+}
+
+/// Counts the nodes of an ancestor chain that pass `test`, e.g. how deeply a
+/// list is nested.
+pub fn node_depth<F>(ancestor_chain: &[&Node], test: F) -> usize
+where
+    F: Fn(&Node) -> bool,
+{
+    ancestor_chain.iter().filter(|node| test(node)).count()
+}
+
+/// Returns the headings whose sections contain `position`, outermost first.
+/// A section runs from its heading to the next heading of the same or a
+/// higher level. Only top-level headings are considered. `position` is an LSP
+/// position in `text`.
+pub fn find_heading_chain<'a>(ast: &'a Node, text: &str, position: &Position) -> Vec<&'a Node> {
+    let position = to_byte_position(text, position);
+    heading_chain_at(
+        ast,
+        (
+            (position.line + 1) as usize,
+            (position.character + 1) as usize,
+        ),
+    )
+}
+
+/// Returns the headings whose sections contain the start of `node`, like
+/// `find_heading_chain`. A top-level heading ends its own chain.
+pub fn find_node_heading_chain<'a>(ast: &'a Node, node: &Node) -> Vec<&'a Node> {
+    match node.position() {
+        Some(position) => heading_chain_at(ast, (position.start.line, position.start.column)),
+        None => Vec::new(),
+    }
+}
+
+/// The heading chain at a 1-based line and byte column.
+fn heading_chain_at(ast: &Node, target: (usize, usize)) -> Vec<&Node> {
+    let mut chain: Vec<&Node> = Vec::new();
+
+    for node in ast.children().into_iter().flatten() {
+        let Some(pos) = node.position() else {
+            continue;
+        };
+        if (pos.start.line, pos.start.column) > target {
+            break;
+        }
+        let Node::Heading(heading) = node else {
+            continue;
+        };
+
+        while chain.last().is_some_and(
+            |last| matches!(last, Node::Heading(parent) if parent.depth >= heading.depth),
+        ) {
+            chain.pop();
+        }
+        chain.push(node);
+    }
+
+    chain
+}
+
 #[cfg(test)]
 mod tests {
     use log::debug;
@@ -48,17 +171,14 @@ mod tests {
 
     #[test]
     fn test_get_ancestor_chain() {
-        let ast = to_mdast(
-            r#"
+        let source = r#"
 # Hello World
 
 - Item 1
 - Item 2
 "#
-            .trim(),
-            &get_parser_options(),
-        )
-        .unwrap();
+        .trim();
+        let ast = to_mdast(source, &get_parser_options()).unwrap();
         debug!("{:#?}", ast);
 
         let position = Position {
@@ -66,7 +186,7 @@ mod tests {
             character: 5,
         };
 
-        let ancestor_chain = get_ancestor_chain(&ast, &position);
+        let ancestor_chain = get_ancestor_chain(&ast, source, &position);
         debug!("ancestor chain: {:#?}", ancestor_chain);
 
         let list = ast.children().unwrap().get(1).unwrap();
@@ -78,20 +198,36 @@ mod tests {
         assert_eq!(ancestor_chain, expected_ancestor_chain);
     }
 
+    #[test]
+    fn test_get_ancestor_chain_non_ascii() {
+        let text = "😀 漢字 *emphasis* and `code`";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        // In UTF-16 code units, the emphasis spans columns 6 to 16 and the
+        // inline code columns 21 to 27; in bytes, they start 5 and 10 later.
+        let deepest = |character| {
+            get_ancestor_chain(&ast, text, &Position { line: 0, character })
+                .last()
+                .map(|node| node.to_string())
+        };
+        assert_eq!(deepest(8), Some("emphasis".to_string()));
+        assert_eq!(deepest(17), Some(" and ".to_string()));
+        assert_eq!(deepest(22), Some("code".to_string()));
+        assert_eq!(deepest(3), Some("😀 漢字 ".to_string()));
+    }
+
     #[test]
     fn test_find_deepest_match() {
-        let ast = to_mdast(
-            r#"
+        let text = r#"
 # Hello World
 
 - Item 1
 - Item 2
   - Nested Item 1
 "#
-            .trim(),
-            &get_parser_options(),
-        )
-        .unwrap();
+        .trim();
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
         debug!("{:#?}", ast);
 
         let position = Position {
@@ -99,7 +235,7 @@ mod tests {
             character: 5,
         };
 
-        let ancestor_chain = get_ancestor_chain(&ast, &position);
+        let ancestor_chain = get_ancestor_chain(&ast, text, &position);
         debug!("ancestor chain: {:#?}", ancestor_chain);
 
         let deepest_match = find_deepest_match(&ancestor_chain, |node| match node {
@@ -115,4 +251,128 @@ mod tests {
 
         assert_eq!(deepest_match, Some(nested_list_item));
     }
+
+    #[test]
+    fn test_node_depth() {
+        let text = "- a\n  - b\n    - c";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let chain = get_ancestor_chain(
+            &ast,
+            text,
+            &Position {
+                line: 2,
+                character: 6,
+            },
+        );
+        assert_eq!(node_depth(&chain, |node| matches!(node, Node::List(_))), 3);
+        assert_eq!(
+            node_depth(&chain, |node| matches!(node, Node::Heading(_))),
+            0
+        );
+    }
+
+    #[test]
+    fn test_find_heading_chain() {
+        let text = r#"
+# Guide
+
+## Setup
+
+### Details
+
+Some text.
+
+## Usage
+
+Use it.
+"#
+        .trim();
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let chain_at = |line| {
+            find_heading_chain(&ast, text, &Position { line, character: 1 })
+                .iter()
+                .map(|node| node.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(chain_at(6), vec!["Guide", "Setup", "Details"]);
+        assert_eq!(chain_at(10), vec!["Guide", "Usage"]);
+        assert_eq!(chain_at(0), vec!["Guide"]);
+    }
+
+    #[test]
+    fn test_walk() {
+        let ast = to_mdast("# Hello\n\nSome *text*.", &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let mut visited = Vec::new();
+        walk(&ast, |node| visited.push(node.to_string()));
+
+        assert_eq!(
+            visited,
+            vec![
+                "HelloSome text.",
+                "Hello",
+                "Hello",
+                "Some text.",
+                "Some ",
+                "text",
+                "text",
+                "."
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_all_matches() {
+        let ast = to_mdast(
+            r#"
+# Hello World
+
+- Item 1
+- Item 2
+  - Nested Item 1
+"#
+            .trim(),
+            &get_parser_options(),
+        )
+        .unwrap();
+        debug!("{:#?}", ast);
+
+        let matches = find_all_matches(&ast, |node| matches!(node, Node::ListItem(_)));
+        let texts: Vec<_> = matches.iter().map(|node| node.to_string()).collect();
+
+        assert_eq!(
+            texts,
+            vec!["Item 1", "Item 2Nested Item 1", "Nested Item 1"]
+        );
+    }
+
+    #[test]
+    fn test_extract_exports() {
+        let ast = to_mdast(
+            r#"
+import { Card } from "./card"
+export const meta = { title: "Guide" }
+export async function getStaticProps() {}
+
+# Guide
+
+export function Note() {}
+const internal = 1
+"#
+            .trim(),
+            &get_parser_options(),
+        )
+        .unwrap();
+        debug!("{:#?}", ast);
+
+        assert_eq!(
+            extract_exports(&ast),
+            vec!["meta", "getStaticProps", "Note"]
+        );
+    }
 }
@@ -0,0 +1,219 @@
+use std::path::Path;
+
+use dashmap::DashMap;
+use markdown::mdast::Node;
+use tower_lsp::lsp_types::{
+    CallHierarchyIncomingCall, CallHierarchyItem, CallHierarchyOutgoingCall, Position, Range,
+    SymbolKind, Url,
+};
+
+use crate::{
+    ast::{find_all_matches, find_deepest_match, get_ancestor_chain},
+    config::ConfigValues,
+    encoding::get_node_range,
+    nodes::{is_configured_partial, partials::find_matching_partial, NodeExt},
+    references::get_partial_file_references,
+};
+
+/// Returns the partial file included by the `$Partial` under the cursor, in
+/// the element's name or its `src` value alike.
+pub fn prepare_call_hierarchy(
+    ast: &Node,
+    text: &str,
+    position: &Position,
+    config: &ConfigValues,
+) -> Option<Vec<CallHierarchyItem>> {
+    let ancestor_chain = get_ancestor_chain(ast, text, position);
+    let partial = find_deepest_match(&ancestor_chain, |node| is_configured_partial(node, config))?;
+    let path = find_matching_partial(partial.get_attribute("src")?, config)?;
+
+    Some(vec![get_file_item(&path, config)?])
+}
+
+/// Lists the documents in `ast_map`, whose text is in `text_map`, that include
+/// the partial file of `item`, with the ranges of their `$Partial` elements.
+pub fn get_incoming_calls(
+    item: &CallHierarchyItem,
+    ast_map: &DashMap<String, Node>,
+    text_map: &DashMap<String, String>,
+    config: &ConfigValues,
+) -> Vec<CallHierarchyIncomingCall> {
+    let mut calls: Vec<CallHierarchyIncomingCall> = Vec::new();
+
+    for location in
+        get_partial_file_references(&item.uri, ast_map, text_map, config).unwrap_or_default()
+    {
+        if let Some(call) = calls.iter_mut().find(|call| call.from.uri == location.uri) {
+            call.from_ranges.push(location.range);
+            continue;
+        }
+        let Some(from) = location
+            .uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| get_file_item(&path, config))
+        else {
+            continue;
+        };
+        calls.push(CallHierarchyIncomingCall {
+            from,
+            from_ranges: vec![location.range],
+        });
+    }
+
+    calls
+}
+
+/// Lists the partial files that `ast`, the document of a call hierarchy item,
+/// includes, with the ranges of the `$Partial` elements including each one.
+/// Partials that don't resolve are left out.
+pub fn get_outgoing_calls(
+    ast: &Node,
+    text: &str,
+    config: &ConfigValues,
+) -> Vec<CallHierarchyOutgoingCall> {
+    let mut calls: Vec<CallHierarchyOutgoingCall> = Vec::new();
+
+    for partial in find_all_matches(ast, |node| is_configured_partial(node, config)) {
+        let (Some(path), Some(range)) = (
+            partial
+                .get_attribute("src")
+                .and_then(|src| find_matching_partial(src, config)),
+            get_node_range(text, partial),
+        ) else {
+            continue;
+        };
+        let Some(to) = get_file_item(&path, config) else {
+            continue;
+        };
+
+        match calls.iter_mut().find(|call| call.to.uri == to.uri) {
+            Some(call) => call.from_ranges.push(range),
+            None => calls.push(CallHierarchyOutgoingCall {
+                to,
+                from_ranges: vec![range],
+            }),
+        }
+    }
+
+    calls
+}
+
+/// A call hierarchy item for a whole file, named after it, with its path in
+/// the workspace as the detail.
+fn get_file_item(path: &Path, config: &ConfigValues) -> Option<CallHierarchyItem> {
+    let name = path.file_name()?.to_string_lossy().to_string();
+    let detail = path
+        .strip_prefix(&config.workspace_root)
+        .unwrap_or(path)
+        .display()
+        .to_string();
+
+    Some(CallHierarchyItem {
+        name,
+        kind: SymbolKind::FILE,
+        tags: None,
+        detail: Some(detail),
+        uri: Url::from_file_path(path).ok()?,
+        range: Range::default(),
+        selection_range: Range::default(),
+        data: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use log::debug;
+    use markdown::to_mdast;
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    fn create_config() -> (TempDir, ConfigValues) {
+        let partials_dir = tempfile::tempdir().unwrap();
+        fs::write(partials_dir.path().join("hero.mdx"), "# Hero").unwrap();
+        fs::write(partials_dir.path().join("footer.mdx"), "Footer").unwrap();
+        let config = ConfigValues {
+            partials_dirs: vec![partials_dir.path().to_path_buf()],
+            ..Default::default()
+        };
+        (partials_dir, config)
+    }
+
+    #[test]
+    fn test_prepare_call_hierarchy() {
+        let (partials_dir, config) = create_config();
+        let text = "# Guide\n\n<$Partial src=\"hero.mdx\" />\n\n<$Partial src=\"missing.mdx\" />";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let items = prepare_call_hierarchy(&ast, text, &Position::new(2, 18), &config).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "hero.mdx");
+        assert_eq!(
+            items[0].uri,
+            Url::from_file_path(partials_dir.path().join("hero.mdx")).unwrap()
+        );
+
+        assert_eq!(
+            prepare_call_hierarchy(&ast, text, &Position::new(4, 3), &config),
+            None
+        );
+        assert_eq!(
+            prepare_call_hierarchy(&ast, text, &Position::new(0, 3), &config),
+            None
+        );
+    }
+
+    #[test]
+    fn test_incoming_calls() {
+        let (partials_dir, config) = create_config();
+        let ast_map = DashMap::new();
+        let text_map = DashMap::new();
+        for (path, text) in [
+            (
+                "/docs/index.mdx",
+                "<$Partial src=\"hero.mdx\" />\n\n<$Partial src=\"hero\" />",
+            ),
+            ("/docs/guide.mdx", "<$Partial src=\"footer.mdx\" />"),
+        ] {
+            let ast = to_mdast(text, &get_parser_options()).unwrap();
+            let uri = Url::from_file_path(path).unwrap().to_string();
+            ast_map.insert(uri.clone(), ast);
+            text_map.insert(uri, text.to_string());
+        }
+
+        let item = get_file_item(&partials_dir.path().join("hero.mdx"), &config).unwrap();
+        let calls = get_incoming_calls(&item, &ast_map, &text_map, &config);
+
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].from.name, "index.mdx");
+        assert_eq!(
+            calls[0].from_ranges,
+            vec![
+                Range::new(Position::new(0, 0), Position::new(0, 27)),
+                Range::new(Position::new(2, 0), Position::new(2, 23)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_outgoing_calls() {
+        let (_partials_dir, config) = create_config();
+        let text = "<$Partial src=\"hero.mdx\" />\n\n<$Partial src=\"footer\" />\n\n<$Partial src=\"hero\" />\n\n<$Partial src=\"missing.mdx\" />";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let calls: Vec<_> = get_outgoing_calls(&ast, text, &config)
+            .into_iter()
+            .map(|call| (call.to.name, call.from_ranges.len()))
+            .collect();
+        assert_eq!(
+            calls,
+            vec![("hero.mdx".to_string(), 2), ("footer.mdx".to_string(), 1)]
+        );
+    }
+}
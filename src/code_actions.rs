@@ -0,0 +1,1194 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use markdown::mdast::Node;
+use serde_json::Value;
+use tower_lsp::lsp_types::{
+    CodeAction, CodeActionKind, CodeActionOrCommand, Command, CreateFile, CreateFileOptions,
+    Diagnostic, DiagnosticSeverity, DocumentChangeOperation, DocumentChanges, NumberOrString,
+    OneOf, OptionalVersionedTextDocumentIdentifier, Position, Range, ResourceOp, TextDocumentEdit,
+    TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::{
+    ast::{find_deepest_match, get_ancestor_chain},
+    commands,
+    config::ConfigValues,
+    diagnostics::{
+        get_unimported_components, get_unlabeled_elements, DIAGNOSTIC_SOURCE, MISSING_ARIA_LABEL,
+        MISSING_PARTIAL_SRC,
+    },
+    encoding::{get_node_range, offset_to_position, position_to_offset},
+    formatting::get_table_edit,
+    nodes::{
+        headings::slug_from_heading,
+        is_configured_partial,
+        partials::{find_matching_partial, get_partial_src, read_inlined_partial},
+        NodeExt,
+    },
+};
+
+/// Returns the code actions available at `range`. When `only` is given and
+/// not empty, only actions of those kinds (or of their sub-kinds, e.g.
+/// `refactor.extract` for `refactor`) are returned.
+pub fn get_code_actions(
+    ast: &Node,
+    text: &str,
+    uri: &Url,
+    range: &Range,
+    only: Option<&[CodeActionKind]>,
+    config: &ConfigValues,
+) -> Vec<CodeActionOrCommand> {
+    [
+        get_extract_selection_action(text, uri, range, config),
+        get_split_file_action(ast, text, uri, &range.start, config),
+        get_extract_code_block_action(ast, text, uri, &range.start, config),
+        get_import_component_action(ast, text, uri, &range.start, config),
+        get_convert_to_callout_action(ast, text, uri, &range.start, config),
+        get_add_aria_label_action(ast, text, uri, &range.start, config),
+        get_inline_partial_action(ast, text, uri, &range.start, config),
+        get_format_table_action(ast, text, uri, &range.start),
+    ]
+    .into_iter()
+    .flatten()
+    .chain(get_missing_src_actions(
+        ast,
+        text,
+        uri,
+        &range.start,
+        config,
+    ))
+    .filter(|action| is_kind_requested(action, only))
+    .map(CodeActionOrCommand::CodeAction)
+    .collect()
+}
+
+fn is_kind_requested(action: &CodeAction, only: Option<&[CodeActionKind]>) -> bool {
+    let Some(only) = only.filter(|only| !only.is_empty()) else {
+        return true;
+    };
+    let Some(kind) = &action.kind else {
+        return false;
+    };
+
+    only.iter().any(|requested| {
+        let requested = requested.as_str();
+        kind.as_str() == requested
+            || kind
+                .as_str()
+                .strip_prefix(requested)
+                .is_some_and(|rest| rest.starts_with('.'))
+    })
+}
+
+/// Moves the selected text into a new partial in the first partials
+/// directory, named after a hash of its content, and includes it in its place.
+fn get_extract_selection_action(
+    text: &str,
+    uri: &Url,
+    range: &Range,
+    config: &ConfigValues,
+) -> Option<CodeAction> {
+    let start = position_to_offset(text, &range.start);
+    let end = position_to_offset(text, &range.end);
+    let selection = text.get(start..end)?;
+    if selection.trim().is_empty() {
+        return None;
+    }
+    let partials_dir = config.partials_dirs.first()?;
+
+    let mut hasher = DefaultHasher::new();
+    selection.hash(&mut hasher);
+    let file_name = format!("partial-{:08x}.mdx", hasher.finish() as u32);
+    let new_path = partials_dir.join(&file_name);
+    let new_uri = Url::from_file_path(&new_path).ok()?;
+
+    let mut content = selection.trim_end().to_string();
+    content.push('\n');
+    let operations = vec![
+        create_file(&new_uri),
+        text_document_edit(
+            &new_uri,
+            TextEdit {
+                range: Range::default(),
+                new_text: content,
+            },
+        ),
+        text_document_edit(
+            uri,
+            TextEdit {
+                range: *range,
+                new_text: format!(
+                    "<{} src=\"{}\" />",
+                    config.get_partial_element_name(),
+                    get_partial_src(&new_path, config)
+                ),
+            },
+        ),
+    ];
+
+    Some(CodeAction {
+        title: "Extract to partial".to_string(),
+        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+        edit: Some(WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(operations)),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Moves a long fenced code block into a new partial in the first partials
+/// directory, named after a hash of its content.
+fn get_extract_code_block_action(
+    ast: &Node,
+    text: &str,
+    uri: &Url,
+    position: &Position,
+    config: &ConfigValues,
+) -> Option<CodeAction> {
+    let ancestor_chain = get_ancestor_chain(ast, text, position);
+    let node = find_deepest_match(&ancestor_chain, |node| matches!(node, Node::Code(_)))?;
+    let Node::Code(code) = node else {
+        return None;
+    };
+    if code.value.lines().count() <= config.extract_code_block_min_lines as usize {
+        return None;
+    }
+
+    let pos = node.position()?;
+    let source = text.get(pos.start.offset..pos.end.offset)?;
+    let partials_dir = config.partials_dirs.first()?;
+
+    let mut hasher = DefaultHasher::new();
+    code.value.hash(&mut hasher);
+    let file_name = format!("code-{:08x}.mdx", hasher.finish() as u32);
+    let new_path = partials_dir.join(&file_name);
+    let new_uri = Url::from_file_path(&new_path).ok()?;
+
+    let operations = vec![
+        create_file(&new_uri),
+        text_document_edit(
+            &new_uri,
+            TextEdit {
+                range: Range::default(),
+                new_text: format!("{}\n", source),
+            },
+        ),
+        text_document_edit(
+            uri,
+            TextEdit {
+                range: get_node_range(text, node)?,
+                new_text: format!(
+                    "<{} src=\"{}\" />",
+                    config.get_partial_element_name(),
+                    get_partial_src(&new_path, config)
+                ),
+            },
+        ),
+    ];
+
+    Some(CodeAction {
+        title: "Extract code block to partial".to_string(),
+        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+        edit: Some(WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(operations)),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Replaces the block quote under the cursor with a callout component holding
+/// the same content, without the `>` markers.
+fn get_convert_to_callout_action(
+    ast: &Node,
+    text: &str,
+    uri: &Url,
+    position: &Position,
+    config: &ConfigValues,
+) -> Option<CodeAction> {
+    let name = match &config.callout_component_name {
+        Some(name) => name.as_str(),
+        None if config.component_map.contains_key("Callout") => "Callout",
+        None => return None,
+    };
+
+    let ancestor_chain = get_ancestor_chain(ast, text, position);
+    let node = find_deepest_match(&ancestor_chain, |node| matches!(node, Node::Blockquote(_)))?;
+    let pos = node.position()?;
+    let source = text.get(pos.start.offset..pos.end.offset)?;
+
+    let content: Vec<&str> = source
+        .lines()
+        .map(|line| {
+            let line = line.trim_start();
+            let line = line.strip_prefix('>').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line)
+        })
+        .collect();
+
+    Some(CodeAction {
+        title: "Convert to callout component".to_string(),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(vec![text_document_edit(
+                uri,
+                TextEdit {
+                    range: get_node_range(text, node)?,
+                    new_text: format!("<{}>\n{}\n</{}>", name, content.join("\n"), name),
+                },
+            )])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Aligns the columns of the table under the cursor, when they aren't
+/// already.
+fn get_format_table_action(
+    ast: &Node,
+    text: &str,
+    uri: &Url,
+    position: &Position,
+) -> Option<CodeAction> {
+    let ancestor_chain = get_ancestor_chain(ast, text, position);
+    let table = find_deepest_match(&ancestor_chain, |node| matches!(node, Node::Table(_)))?;
+
+    Some(CodeAction {
+        title: "Format table".to_string(),
+        kind: Some(CodeActionKind::REFACTOR_REWRITE),
+        edit: Some(WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(vec![text_document_edit(
+                uri,
+                get_table_edit(table, text)?,
+            )])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Replaces the partial under the cursor with the content of its file. When
+/// partials nested deeper than `inline_partial_depth` are left as references,
+/// the action carries a warning saying so.
+fn get_inline_partial_action(
+    ast: &Node,
+    text: &str,
+    uri: &Url,
+    position: &Position,
+    config: &ConfigValues,
+) -> Option<CodeAction> {
+    let ancestor_chain = get_ancestor_chain(ast, text, position);
+    let partial = find_deepest_match(&ancestor_chain, |node| is_configured_partial(node, config))?;
+    let path = find_matching_partial(partial.get_attribute("src")?, config)?;
+    let (content, limit_hit) = read_inlined_partial(&path, config.inline_partial_depth, config)?;
+    let range = get_node_range(text, partial)?;
+
+    let diagnostics = limit_hit.then(|| {
+        vec![Diagnostic {
+            range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            source: Some(DIAGNOSTIC_SOURCE.to_string()),
+            message: format!(
+                "Partials nested more than {} level(s) deep are left as references",
+                config.inline_partial_depth
+            ),
+            ..Default::default()
+        }]
+    });
+
+    Some(CodeAction {
+        title: "Inline partial".to_string(),
+        kind: Some(CodeActionKind::REFACTOR_INLINE),
+        diagnostics,
+        edit: Some(WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(vec![text_document_edit(
+                uri,
+                TextEdit {
+                    range,
+                    new_text: content,
+                },
+            )])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Fixes a `missing-aria-label` hint by adding an empty `aria-label` right
+/// after the element name.
+fn get_add_aria_label_action(
+    ast: &Node,
+    text: &str,
+    uri: &Url,
+    position: &Position,
+    config: &ConfigValues,
+) -> Option<CodeAction> {
+    if !config.lint_accessibility {
+        return None;
+    }
+
+    let element = get_unlabeled_elements(ast, config)
+        .into_iter()
+        .rev()
+        .find(|element| element.contains_position(text, position))?;
+    let start = element.position()?.start.offset;
+    let name_end = offset_to_position(text, start + 1 + element.get_name()?.len());
+
+    Some(CodeAction {
+        title: "Add aria-label attribute".to_string(),
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![Diagnostic {
+            range: get_node_range(text, element)?,
+            severity: Some(DiagnosticSeverity::HINT),
+            code: Some(NumberOrString::String(MISSING_ARIA_LABEL.to_string())),
+            ..Default::default()
+        }]),
+        edit: Some(WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(vec![text_document_edit(
+                uri,
+                TextEdit {
+                    range: Range::new(name_end, name_end),
+                    new_text: " aria-label=\"\"".to_string(),
+                },
+            )])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Fixes a `missing-partial-src` error by adding an empty `src` before the
+/// end of the opening tag, or by removing the partial altogether.
+fn get_missing_src_actions(
+    ast: &Node,
+    text: &str,
+    uri: &Url,
+    position: &Position,
+    config: &ConfigValues,
+) -> Vec<CodeAction> {
+    let ancestor_chain = get_ancestor_chain(ast, text, position);
+    let Some(partial) = find_deepest_match(&ancestor_chain, |node| {
+        is_configured_partial(node, config) && !node.get_all_attributes().contains(&"src")
+    }) else {
+        return Vec::new();
+    };
+    let (Some(range), Some(insertion)) = (
+        get_node_range(text, partial),
+        get_src_insertion(partial, text),
+    ) else {
+        return Vec::new();
+    };
+    let diagnostic = Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String(MISSING_PARTIAL_SRC.to_string())),
+        source: Some(DIAGNOSTIC_SOURCE.to_string()),
+        ..Default::default()
+    };
+
+    let action = |title: &str, edit: TextEdit| CodeAction {
+        title: title.to_string(),
+        kind: Some(CodeActionKind::QUICKFIX),
+        diagnostics: Some(vec![diagnostic.clone()]),
+        edit: Some(WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(vec![text_document_edit(
+                uri, edit,
+            )])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    vec![
+        action(
+            "Add src attribute",
+            TextEdit {
+                range: Range::new(insertion, insertion),
+                new_text: " src=\"\"".to_string(),
+            },
+        ),
+        action(
+            "Convert to prose",
+            TextEdit {
+                range,
+                new_text: String::new(),
+            },
+        ),
+    ]
+}
+
+/// Where a new attribute goes in a partial: before the `/>` of a
+/// self-closing tag, and after the element name otherwise.
+fn get_src_insertion(partial: &Node, text: &str) -> Option<Position> {
+    let position = partial.position()?;
+    let source = text.get(position.start.offset..position.end.offset)?;
+
+    let offset = match source.strip_suffix("/>") {
+        Some(before) => before.trim_end().len(),
+        None => 1 + partial.get_name()?.len(),
+    };
+    Some(offset_to_position(text, position.start.offset + offset))
+}
+
+/// Fixes a `component-not-imported` warning by importing the component under
+/// the cursor from the path given in `component_map`.
+fn get_import_component_action(
+    ast: &Node,
+    text: &str,
+    uri: &Url,
+    position: &Position,
+    config: &ConfigValues,
+) -> Option<CodeAction> {
+    let element = get_unimported_components(ast, config)
+        .into_iter()
+        .rev()
+        .find(|element| element.contains_position(text, position))?;
+    let name = element.get_name()?;
+    let path = config.component_map.get(name)?;
+
+    let edit = insert_import(ast, text, &format!("import {} from \"{}\"", name, path))?;
+
+    Some(CodeAction {
+        title: format!("Import {} from component map", name),
+        kind: Some(CodeActionKind::QUICKFIX),
+        is_preferred: Some(true),
+        edit: Some(WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(vec![text_document_edit(
+                uri, edit,
+            )])),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+/// Adds an import statement at the end of the document's first ESM block, or
+/// in a new block at the top of the document (below any frontmatter).
+fn insert_import(ast: &Node, text: &str, statement: &str) -> Option<TextEdit> {
+    let children = ast.children()?;
+
+    if let Some(esm) = children
+        .iter()
+        .find(|node| matches!(node, Node::MdxjsEsm(_)))
+    {
+        let end = get_node_range(text, esm)?.end;
+        return Some(TextEdit {
+            range: Range::new(end, end),
+            new_text: format!("\n{}", statement),
+        });
+    }
+
+    match children.first() {
+        Some(frontmatter @ (Node::Yaml(_) | Node::Toml(_))) => {
+            let end = get_node_range(text, frontmatter)?.end;
+            Some(TextEdit {
+                range: Range::new(end, end),
+                new_text: format!("\n\n{}", statement),
+            })
+        }
+        _ => Some(TextEdit {
+            range: Range::default(),
+            new_text: format!("{}\n\n", statement),
+        }),
+    }
+}
+
+/// Moves the section starting at the heading under the cursor into a new file
+/// named after the heading, and includes it back as a partial. The section
+/// ends at the next heading of the same or a higher level.
+fn get_split_file_action(
+    ast: &Node,
+    text: &str,
+    uri: &Url,
+    position: &Position,
+    config: &ConfigValues,
+) -> Option<CodeAction> {
+    let children = ast.children()?;
+    let index = children.iter().position(|node| {
+        matches!(node, Node::Heading(_)) && node.contains_position(text, position)
+    })?;
+    let Node::Heading(heading) = &children[index] else {
+        return None;
+    };
+
+    // Splitting at the first heading would move the whole document.
+    if !children[..index]
+        .iter()
+        .any(|node| matches!(node, Node::Heading(_)))
+    {
+        return None;
+    }
+
+    let section_end = children[index + 1..].iter().find(|node| match node {
+        Node::Heading(next) => next.depth <= heading.depth,
+        _ => false,
+    });
+
+    let start = children[index].position()?;
+    let end_offset = section_end
+        .and_then(|node| node.position())
+        .map_or(text.len(), |pos| pos.start.offset);
+    let section = text.get(start.start.offset..end_offset)?.trim_end();
+
+    let slug = slug_from_heading(&children[index].to_string());
+    if slug.is_empty() {
+        return None;
+    }
+    let document_path = uri.to_file_path().ok()?;
+    let new_path = document_path.parent()?.join(format!("{}.mdx", slug));
+    let new_uri = Url::from_file_path(&new_path).ok()?;
+
+    let replacement_end = match section_end.and_then(|node| get_node_range(text, node)) {
+        Some(range) => range.start,
+        None => get_end_position(text),
+    };
+    let partial = format!(
+        "<{} src=\"{}\" />\n",
+        config.get_partial_element_name(),
+        get_partial_src(&new_path, config)
+    );
+    let partial = if section_end.is_some() {
+        format!("{}\n", partial)
+    } else {
+        partial
+    };
+
+    let operations = vec![
+        create_file(&new_uri),
+        text_document_edit(
+            &new_uri,
+            TextEdit {
+                range: Range::default(),
+                new_text: format!("{}\n", section),
+            },
+        ),
+        text_document_edit(
+            uri,
+            TextEdit {
+                range: Range {
+                    start: Position {
+                        line: (start.start.line - 1) as u32,
+                        character: 0,
+                    },
+                    end: replacement_end,
+                },
+                new_text: partial,
+            },
+        ),
+    ];
+
+    Some(CodeAction {
+        title: "Split file at this heading".to_string(),
+        kind: Some(CodeActionKind::REFACTOR_EXTRACT),
+        edit: Some(WorkspaceEdit {
+            document_changes: Some(DocumentChanges::Operations(operations)),
+            ..Default::default()
+        }),
+        command: Some(Command {
+            title: "Open new file".to_string(),
+            command: commands::SHOW_DOCUMENT.to_string(),
+            arguments: Some(vec![Value::String(new_uri.to_string())]),
+        }),
+        ..Default::default()
+    })
+}
+
+/// Creates a new file, failing if it already exists.
+fn create_file(uri: &Url) -> DocumentChangeOperation {
+    DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+        uri: uri.clone(),
+        options: Some(CreateFileOptions {
+            overwrite: Some(false),
+            ignore_if_exists: Some(false),
+        }),
+        annotation_id: None,
+    }))
+}
+
+fn text_document_edit(uri: &Url, edit: TextEdit) -> DocumentChangeOperation {
+    DocumentChangeOperation::Edit(TextDocumentEdit {
+        text_document: OptionalVersionedTextDocumentIdentifier {
+            uri: uri.clone(),
+            version: None,
+        },
+        edits: vec![OneOf::Left(edit)],
+    })
+}
+
+fn get_end_position(text: &str) -> Position {
+    let line = text.split('\n').count() - 1;
+    let last_line = text.rsplit('\n').next().unwrap_or_default();
+
+    Position {
+        line: line as u32,
+        character: last_line.encode_utf16().count() as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use log::debug;
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    fn get_actions(text: &str, line: u32) -> Vec<CodeActionOrCommand> {
+        get_actions_with_config(text, line, &ConfigValues::default())
+    }
+
+    fn get_actions_with_config(
+        text: &str,
+        line: u32,
+        config: &ConfigValues,
+    ) -> Vec<CodeActionOrCommand> {
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let position = Position { line, character: 3 };
+        get_code_actions(
+            &ast,
+            text,
+            &Url::from_file_path("/docs/guide.mdx").unwrap(),
+            &Range {
+                start: position,
+                end: position,
+            },
+            None,
+            config,
+        )
+    }
+
+    fn get_selection_actions(
+        text: &str,
+        range: Range,
+        only: Option<&[CodeActionKind]>,
+    ) -> Vec<CodeAction> {
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+        let config = ConfigValues {
+            partials_dirs: vec![PathBuf::from("/docs/partials")],
+            ..Default::default()
+        };
+
+        get_code_actions(
+            &ast,
+            text,
+            &Url::from_file_path("/docs/guide.mdx").unwrap(),
+            &range,
+            only,
+            &config,
+        )
+        .into_iter()
+        .filter_map(|action| match action {
+            CodeActionOrCommand::CodeAction(action) => Some(action),
+            CodeActionOrCommand::Command(_) => None,
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_extract_selection() {
+        let text = "# Guide\n\nFirst paragraph.\n\nSecond *paragraph*.\n";
+        let range = Range::new(Position::new(2, 0), Position::new(4, 19));
+        let actions = get_selection_actions(text, range, None);
+
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].title, "Extract to partial");
+        let Some(DocumentChanges::Operations(operations)) = actions[0]
+            .edit
+            .as_ref()
+            .and_then(|edit| edit.document_changes.clone())
+        else {
+            panic!("expected document change operations");
+        };
+        let DocumentChangeOperation::Op(ResourceOp::Create(create)) = &operations[0] else {
+            panic!("expected a file to be created");
+        };
+        let file_name = create.uri.path().rsplit('/').next().unwrap().to_string();
+        assert!(file_name.starts_with("partial-"));
+        assert_eq!(
+            operations[1..],
+            [
+                text_document_edit(
+                    &create.uri,
+                    TextEdit {
+                        range: Range::default(),
+                        new_text: "First paragraph.\n\nSecond *paragraph*.\n".to_string(),
+                    }
+                ),
+                text_document_edit(
+                    &Url::from_file_path("/docs/guide.mdx").unwrap(),
+                    TextEdit {
+                        range,
+                        new_text: format!("<$Partial src=\"{}\" />", file_name),
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_extract_selection_filtered_by_kind() {
+        let text = "# Guide\n\nSome text.\n";
+        let range = Range::new(Position::new(2, 0), Position::new(2, 10));
+
+        for only in [
+            vec![CodeActionKind::REFACTOR],
+            vec![CodeActionKind::REFACTOR_EXTRACT],
+        ] {
+            assert_eq!(get_selection_actions(text, range, Some(&only)).len(), 1);
+        }
+        assert!(get_selection_actions(text, range, Some(&[CodeActionKind::QUICKFIX])).is_empty());
+        assert!(get_selection_actions(
+            text,
+            Range::new(Position::new(1, 0), Position::new(1, 0)),
+            None
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn test_split_file_at_heading() {
+        let text = "# Guide\n\nIntro.\n\n## Setup\n\nInstall it.\n\n### Details\n\nMore.\n\n## Usage\n\nUse it.\n";
+        let actions = get_actions(text, 4);
+
+        assert_eq!(actions.len(), 1);
+        let CodeActionOrCommand::CodeAction(action) = &actions[0] else {
+            panic!("expected a code action");
+        };
+        assert_eq!(action.title, "Split file at this heading");
+
+        let Some(DocumentChanges::Operations(operations)) = action
+            .edit
+            .as_ref()
+            .and_then(|edit| edit.document_changes.clone())
+        else {
+            panic!("expected document change operations");
+        };
+        let new_uri = Url::from_file_path("/docs/setup.mdx").unwrap();
+        assert_eq!(
+            operations,
+            vec![
+                DocumentChangeOperation::Op(ResourceOp::Create(CreateFile {
+                    uri: new_uri.clone(),
+                    options: Some(CreateFileOptions {
+                        overwrite: Some(false),
+                        ignore_if_exists: Some(false),
+                    }),
+                    annotation_id: None,
+                })),
+                text_document_edit(
+                    &new_uri,
+                    TextEdit {
+                        range: Range::default(),
+                        new_text: "## Setup\n\nInstall it.\n\n### Details\n\nMore.\n".to_string(),
+                    }
+                ),
+                text_document_edit(
+                    &Url::from_file_path("/docs/guide.mdx").unwrap(),
+                    TextEdit {
+                        range: Range {
+                            start: Position {
+                                line: 4,
+                                character: 0,
+                            },
+                            end: Position {
+                                line: 12,
+                                character: 0,
+                            },
+                        },
+                        new_text: "<$Partial src=\"setup.mdx\" />\n\n".to_string(),
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_split_file_at_last_heading() {
+        let text = "# Guide\n\n## Usage\n\nUse it.";
+        let actions = get_actions(text, 2);
+
+        let CodeActionOrCommand::CodeAction(action) = &actions[0] else {
+            panic!("expected a code action");
+        };
+        let Some(DocumentChanges::Operations(operations)) = action
+            .edit
+            .as_ref()
+            .and_then(|edit| edit.document_changes.clone())
+        else {
+            panic!("expected document change operations");
+        };
+        assert_eq!(
+            operations[2],
+            text_document_edit(
+                &Url::from_file_path("/docs/guide.mdx").unwrap(),
+                TextEdit {
+                    range: Range {
+                        start: Position {
+                            line: 2,
+                            character: 0,
+                        },
+                        end: Position {
+                            line: 4,
+                            character: 7,
+                        },
+                    },
+                    new_text: "<$Partial src=\"usage.mdx\" />\n".to_string(),
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_no_split_at_first_heading() {
+        assert!(get_actions("# Guide\n\n## Setup", 0).is_empty());
+        assert!(get_actions("# Guide\n\nSome text.", 2).is_empty());
+    }
+
+    #[test]
+    fn test_extract_code_block() {
+        let config = ConfigValues {
+            partials_dirs: vec!["/docs/partials".into()],
+            extract_code_block_min_lines: 2,
+            ..Default::default()
+        };
+        let text = "Intro.\n\n```js\na();\nb();\nc();\n```\n";
+
+        let actions = get_actions_with_config(text, 3, &config);
+        let CodeActionOrCommand::CodeAction(action) = &actions[0] else {
+            panic!("expected a code action");
+        };
+        assert_eq!(action.title, "Extract code block to partial");
+
+        let Some(DocumentChanges::Operations(operations)) = action
+            .edit
+            .as_ref()
+            .and_then(|edit| edit.document_changes.clone())
+        else {
+            panic!("expected document change operations");
+        };
+        let DocumentChangeOperation::Op(ResourceOp::Create(create)) = &operations[0] else {
+            panic!("expected a file creation");
+        };
+        let file_name = create.uri.path().trim_start_matches("/docs/partials/");
+        assert!(file_name.starts_with("code-") && file_name.ends_with(".mdx"));
+        assert_eq!(
+            operations[1],
+            text_document_edit(
+                &create.uri,
+                TextEdit {
+                    range: Range::default(),
+                    new_text: "```js\na();\nb();\nc();\n```\n".to_string(),
+                }
+            )
+        );
+        assert_eq!(
+            operations[2],
+            text_document_edit(
+                &Url::from_file_path("/docs/guide.mdx").unwrap(),
+                TextEdit {
+                    range: Range {
+                        start: Position {
+                            line: 2,
+                            character: 0,
+                        },
+                        end: Position {
+                            line: 6,
+                            character: 3,
+                        },
+                    },
+                    new_text: format!("<$Partial src=\"{}\" />", file_name),
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn test_no_extract_for_short_code_block() {
+        let config = ConfigValues {
+            partials_dirs: vec!["/docs/partials".into()],
+            ..Default::default()
+        };
+
+        assert!(get_actions_with_config("```js\na();\n```", 1, &config).is_empty());
+    }
+
+    fn get_import_edits(text: &str, line: u32) -> Vec<DocumentChangeOperation> {
+        let config = ConfigValues {
+            component_map: [("Button".to_string(), "@acme/button".to_string())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let actions = get_actions_with_config(text, line, &config);
+
+        assert_eq!(actions.len(), 1);
+        let CodeActionOrCommand::CodeAction(action) = &actions[0] else {
+            panic!("expected a code action");
+        };
+        assert_eq!(action.title, "Import Button from component map");
+        assert_eq!(action.kind, Some(CodeActionKind::QUICKFIX));
+
+        match action
+            .edit
+            .as_ref()
+            .and_then(|edit| edit.document_changes.clone())
+        {
+            Some(DocumentChanges::Operations(operations)) => operations,
+            _ => panic!("expected document change operations"),
+        }
+    }
+
+    fn insertion(line: u32, character: u32, new_text: &str) -> Vec<DocumentChangeOperation> {
+        let position = Position { line, character };
+        vec![text_document_edit(
+            &Url::from_file_path("/docs/guide.mdx").unwrap(),
+            TextEdit {
+                range: Range::new(position, position),
+                new_text: new_text.to_string(),
+            },
+        )]
+    }
+
+    #[test]
+    fn test_import_component_into_esm() {
+        let text = "import { Card } from \"@acme/card\"\n\n<Card>\n  <Button />\n</Card>";
+
+        assert_eq!(
+            get_import_edits(text, 3),
+            insertion(0, 33, "\nimport Button from \"@acme/button\"")
+        );
+    }
+
+    #[test]
+    fn test_import_component_after_frontmatter() {
+        let text = "---\ntitle: Guide\n---\n\n<Button />";
+
+        assert_eq!(
+            get_import_edits(text, 4),
+            insertion(2, 3, "\n\nimport Button from \"@acme/button\"")
+        );
+    }
+
+    #[test]
+    fn test_import_component_at_top() {
+        assert_eq!(
+            get_import_edits("# Guide\n\n<Button />", 2),
+            insertion(0, 0, "import Button from \"@acme/button\"\n\n")
+        );
+    }
+
+    #[test]
+    fn test_convert_to_callout() {
+        let text = "# Guide\n\n> **Note:** read this.\n>\n> - First\n\nAfter.";
+        assert!(get_actions(text, 2).is_empty());
+
+        let config = ConfigValues {
+            callout_component_name: Some("Admonition".to_string()),
+            ..Default::default()
+        };
+        let actions = get_actions_with_config(text, 2, &config);
+
+        assert_eq!(actions.len(), 1);
+        let CodeActionOrCommand::CodeAction(action) = &actions[0] else {
+            panic!("expected a code action");
+        };
+        assert_eq!(action.title, "Convert to callout component");
+        assert_eq!(
+            action
+                .edit
+                .as_ref()
+                .and_then(|edit| edit.document_changes.clone()),
+            Some(DocumentChanges::Operations(vec![text_document_edit(
+                &Url::from_file_path("/docs/guide.mdx").unwrap(),
+                TextEdit {
+                    range: Range::new(Position::new(2, 0), Position::new(4, 9)),
+                    new_text: "<Admonition>\n**Note:** read this.\n\n- First\n</Admonition>"
+                        .to_string(),
+                },
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_format_table() {
+        let text = "# Prices\n\n| Item | Price |\n| - | -: |\n| Crème brûlée | 8 |";
+        assert!(get_actions(text, 0).is_empty());
+
+        let actions = get_actions(text, 4);
+        assert_eq!(actions.len(), 1);
+        let CodeActionOrCommand::CodeAction(action) = &actions[0] else {
+            panic!("expected a code action");
+        };
+        assert_eq!(action.title, "Format table");
+        assert_eq!(
+            action
+                .edit
+                .as_ref()
+                .and_then(|edit| edit.document_changes.clone()),
+            Some(DocumentChanges::Operations(vec![text_document_edit(
+                &Url::from_file_path("/docs/guide.mdx").unwrap(),
+                TextEdit {
+                    range: Range::new(Position::new(2, 0), Position::new(4, 20)),
+                    new_text: "| Item         | Price |\n| ------------ | ----: |\n| Crème brûlée |     8 |"
+                        .to_string(),
+                },
+            )]))
+        );
+
+        // An aligned table needs no formatting.
+        let aligned = "| Item | Price |\n| ---- | ----: |\n| Tea  |     3 |";
+        assert!(get_actions(aligned, 2).is_empty());
+    }
+
+    #[test]
+    fn test_convert_to_callout_from_component_map() {
+        let config = ConfigValues {
+            component_map: [("Callout".to_string(), "@/components/Callout".to_string())]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+        let actions = get_actions_with_config("> Quote", 0, &config);
+
+        assert_eq!(actions.len(), 1);
+    }
+
+    #[test]
+    fn test_add_aria_label() {
+        let text = "<Icon name=\"close\" />";
+        assert!(get_actions(text, 0).is_empty());
+
+        let config = ConfigValues {
+            lint_accessibility: true,
+            ..Default::default()
+        };
+        let actions = get_actions_with_config(text, 0, &config);
+
+        assert_eq!(actions.len(), 1);
+        let CodeActionOrCommand::CodeAction(action) = &actions[0] else {
+            panic!("expected a code action");
+        };
+        assert_eq!(action.title, "Add aria-label attribute");
+        assert_eq!(
+            action
+                .edit
+                .as_ref()
+                .and_then(|edit| edit.document_changes.clone()),
+            Some(DocumentChanges::Operations(vec![text_document_edit(
+                &Url::from_file_path("/docs/guide.mdx").unwrap(),
+                TextEdit {
+                    range: Range::new(Position::new(0, 5), Position::new(0, 5)),
+                    new_text: " aria-label=\"\"".to_string(),
+                },
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_missing_src_actions() {
+        let uri = Url::from_file_path("/docs/guide.mdx").unwrap();
+        let edits = |text: &str| -> Vec<(String, DocumentChanges)> {
+            get_actions(text, 0)
+                .into_iter()
+                .filter_map(|action| match action {
+                    CodeActionOrCommand::CodeAction(action) => {
+                        Some((action.title, action.edit?.document_changes?))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            edits("<$Partial title=\"Hero\"  />"),
+            vec![
+                (
+                    "Add src attribute".to_string(),
+                    DocumentChanges::Operations(vec![text_document_edit(
+                        &uri,
+                        TextEdit {
+                            range: Range::new(Position::new(0, 22), Position::new(0, 22)),
+                            new_text: " src=\"\"".to_string(),
+                        },
+                    )])
+                ),
+                (
+                    "Convert to prose".to_string(),
+                    DocumentChanges::Operations(vec![text_document_edit(
+                        &uri,
+                        TextEdit {
+                            range: Range::new(Position::new(0, 0), Position::new(0, 26)),
+                            new_text: String::new(),
+                        },
+                    )])
+                ),
+            ]
+        );
+
+        let paired = edits("<$Partial>\n  Text\n</$Partial>");
+        assert_eq!(
+            paired[0].1,
+            DocumentChanges::Operations(vec![text_document_edit(
+                &uri,
+                TextEdit {
+                    range: Range::new(Position::new(0, 9), Position::new(0, 9)),
+                    new_text: " src=\"\"".to_string(),
+                },
+            )])
+        );
+
+        assert!(edits("<$Partial src=\"\" />").is_empty());
+    }
+
+    #[test]
+    fn test_inline_partial() {
+        let partials_dir = tempfile::tempdir().unwrap();
+        fs::write(
+            partials_dir.path().join("hero.mdx"),
+            "---\ntitle: Hero\n---\n\n# Hero\n\n<$Partial src=\"cta.mdx\" />\n",
+        )
+        .unwrap();
+        fs::write(
+            partials_dir.path().join("cta.mdx"),
+            "Sign up!\n\n<$Partial src=\"hero.mdx\" />",
+        )
+        .unwrap();
+        let config = ConfigValues {
+            partials_dirs: vec![partials_dir.path().to_path_buf()],
+            ..Default::default()
+        };
+
+        let text = "# Home\n\n<$Partial src=\"hero\" />\n";
+        let actions = get_actions_with_config(text, 2, &config);
+        assert_eq!(actions.len(), 1);
+        let CodeActionOrCommand::CodeAction(action) = &actions[0] else {
+            panic!("expected a code action");
+        };
+        assert_eq!(action.title, "Inline partial");
+        assert_eq!(
+            action
+                .edit
+                .as_ref()
+                .and_then(|edit| edit.document_changes.clone()),
+            Some(DocumentChanges::Operations(vec![text_document_edit(
+                &Url::from_file_path("/docs/guide.mdx").unwrap(),
+                TextEdit {
+                    range: Range::new(Position::new(2, 0), Position::new(2, 23)),
+                    new_text: "# Hero\n\nSign up!\n\n<$Partial src=\"hero.mdx\" />".to_string(),
+                },
+            )]))
+        );
+        let diagnostics = action.diagnostics.as_ref().unwrap();
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+
+use dashmap::DashMap;
+use markdown::mdast::Node;
+use serde_json::Value;
+use tower_lsp::lsp_types::{CodeLens, Command, Range, Url};
+
+use crate::{
+    ast::find_all_matches,
+    commands,
+    config::ConfigValues,
+    encoding::get_node_range,
+    nodes::{is_configured_partial, partials::find_matching_partial, NodeExt},
+    references::get_partial_file_references,
+};
+
+/// Code blocks longer than this many characters get a code lens.
+pub const LARGE_CODE_BLOCK_CHARS: usize = 500;
+
+pub fn get_code_lenses(ast: &Node, text: &str, uri: &Url, config: &ConfigValues) -> Vec<CodeLens> {
+    let mut lenses: Vec<CodeLens> = find_all_matches(ast, |node| matches!(node, Node::Code(_)))
+        .into_iter()
+        .flat_map(|node| get_code_block_lenses(node, text))
+        .collect();
+    lenses.extend(get_partial_lenses(ast, text, config));
+
+    // Spell checking is opt-in.
+    if config.spell_check_wordlist.is_some() {
+        lenses.extend(get_spell_check_lenses(ast, text, uri));
+    }
+
+    lenses
+}
+
+/// Tells how many documents in `ast_map` include the partial file at `uri`.
+/// Returns `None` when the file isn't in one of the `partials_dirs`.
+pub fn get_partial_usage_lens(
+    uri: &Url,
+    ast_map: &DashMap<String, Node>,
+    text_map: &DashMap<String, String>,
+    config: &ConfigValues,
+) -> Option<CodeLens> {
+    let references = get_partial_file_references(uri, ast_map, text_map, config)?;
+    let documents: HashSet<&Url> = references.iter().map(|location| &location.uri).collect();
+    let title = match documents.len() {
+        0 => "Not used in any indexed document".to_string(),
+        1 => "Used in 1 document".to_string(),
+        count => format!("Used in {} documents", count),
+    };
+
+    Some(CodeLens {
+        range: Range::default(),
+        command: Some(Command {
+            title,
+            command: String::new(),
+            arguments: None,
+        }),
+        data: None,
+    })
+}
+
+/// Opens the file of each resolved partial. Unresolved partials already have
+/// an error diagnostic, so they get no lens.
+fn get_partial_lenses(ast: &Node, text: &str, config: &ConfigValues) -> Vec<CodeLens> {
+    find_all_matches(ast, |node| is_configured_partial(node, config))
+        .into_iter()
+        .filter_map(|partial| {
+            let src = partial.get_attribute("src")?;
+            let uri = Url::from_file_path(find_matching_partial(src, config)?).ok()?;
+
+            Some(CodeLens {
+                range: get_node_range(text, partial)?,
+                command: Some(Command {
+                    title: format!("✏ Edit {}", src),
+                    command: commands::SHOW_DOCUMENT.to_string(),
+                    arguments: Some(vec![Value::String(uri.to_string())]),
+                }),
+                data: None,
+            })
+        })
+        .collect()
+}
+
+/// Offers a spell check of each top-level section.
+fn get_spell_check_lenses(ast: &Node, text: &str, uri: &Url) -> Vec<CodeLens> {
+    ast.children()
+        .into_iter()
+        .flatten()
+        .filter(|node| matches!(node, Node::Heading(_)))
+        .filter_map(|heading| {
+            let range = get_node_range(text, heading)?;
+
+            Some(CodeLens {
+                range,
+                command: Some(Command {
+                    title: "Check spelling".to_string(),
+                    command: commands::SPELL_CHECK.to_string(),
+                    arguments: Some(vec![
+                        Value::String(uri.to_string()),
+                        Value::from(range.start.line),
+                    ]),
+                }),
+                data: None,
+            })
+        })
+        .collect()
+}
+
+/// Flags oversized code blocks with their length and a command to copy them,
+/// plus their language when one is set.
+fn get_code_block_lenses(node: &Node, text: &str) -> Vec<CodeLens> {
+    let Node::Code(code) = node else {
+        return Vec::new();
+    };
+    let length = code.value.chars().count();
+    let Some(range) = get_node_range(text, node).filter(|_| length > LARGE_CODE_BLOCK_CHARS) else {
+        return Vec::new();
+    };
+
+    let mut lenses = vec![CodeLens {
+        range,
+        command: Some(Command {
+            title: format!("{} characters | Copy", length),
+            command: commands::COPY_TO_CLIPBOARD.to_string(),
+            arguments: Some(vec![Value::String(code.value.clone())]),
+        }),
+        data: None,
+    }];
+    if let Some(lang) = &code.lang {
+        // An empty command makes the lens informational only.
+        lenses.push(CodeLens {
+            range,
+            command: Some(Command {
+                title: format!("lang: {}", lang),
+                command: String::new(),
+                arguments: None,
+            }),
+            data: None,
+        });
+    }
+
+    lenses
+}
+
+#[cfg(test)]
+mod tests {
+    use log::debug;
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    fn get_titles(text: &str) -> Vec<String> {
+        get_titles_with_config(text, &ConfigValues::default())
+    }
+
+    fn get_titles_with_config(text: &str, config: &ConfigValues) -> Vec<String> {
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let uri = Url::parse("file:///docs/index.mdx").unwrap();
+        get_code_lenses(&ast, text, &uri, config)
+            .into_iter()
+            .filter_map(|lens| lens.command.map(|command| command.title))
+            .collect()
+    }
+
+    #[test]
+    fn test_large_code_block() {
+        let body = "x".repeat(600);
+        let titles = get_titles(&format!("# Hello\n\n```rust\n{}\n```", body));
+
+        assert_eq!(titles, vec!["600 characters | Copy", "lang: rust"]);
+    }
+
+    #[test]
+    fn test_large_code_block_without_lang() {
+        let body = "x".repeat(501);
+        let titles = get_titles(&format!("```\n{}\n```", body));
+
+        assert_eq!(titles, vec!["501 characters | Copy"]);
+    }
+
+    #[test]
+    fn test_spell_check_lenses() {
+        let text = "# Guide\n\nText.\n\n## Setup";
+        assert!(get_titles(text).is_empty());
+
+        let config = ConfigValues {
+            spell_check_wordlist: Some("words.txt".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            get_titles_with_config(text, &config),
+            vec!["Check spelling", "Check spelling"]
+        );
+    }
+
+    #[test]
+    fn test_partial_lenses() {
+        let partials_dir = tempfile::tempdir().unwrap();
+        std::fs::write(partials_dir.path().join("hero.mdx"), "# Hero").unwrap();
+        let config = ConfigValues {
+            partials_dirs: vec![partials_dir.path().to_path_buf()],
+            ..Default::default()
+        };
+
+        let titles = get_titles_with_config(
+            "<$Partial src=\"hero.mdx\" />\n\n<$Partial src=\"missing.mdx\" />",
+            &config,
+        );
+
+        assert_eq!(titles, vec!["✏ Edit hero.mdx"]);
+    }
+
+    #[test]
+    fn test_partial_usage_lens() {
+        let partials_dir = tempfile::tempdir().unwrap();
+        let hero = partials_dir.path().join("hero.mdx");
+        std::fs::write(&hero, "# Hero").unwrap();
+        let config = ConfigValues {
+            partials_dirs: vec![partials_dir.path().to_path_buf()],
+            ..Default::default()
+        };
+
+        let ast_map = DashMap::new();
+        let text_map = DashMap::new();
+        let get_title = |ast_map: &DashMap<String, Node>, uri: &Url| {
+            get_partial_usage_lens(uri, ast_map, &text_map, &config)
+                .and_then(|lens| lens.command)
+                .map(|command| command.title)
+        };
+        let hero_uri = Url::from_file_path(&hero).unwrap();
+        assert_eq!(
+            get_title(&ast_map, &hero_uri).as_deref(),
+            Some("Not used in any indexed document")
+        );
+
+        for (path, text) in [
+            (
+                "/docs/index.mdx",
+                "<$Partial src=\"hero.mdx\" />\n\n<$Partial src=\"hero\" />",
+            ),
+            ("/docs/guide.mdx", "<$Partial src=\"hero.mdx\" />"),
+            ("/docs/other.mdx", "<$Partial src=\"other.mdx\" />"),
+        ] {
+            let ast = to_mdast(text, &get_parser_options()).unwrap();
+            let uri = Url::from_file_path(path).unwrap().to_string();
+            ast_map.insert(uri.clone(), ast);
+            text_map.insert(uri, text.to_string());
+        }
+        assert_eq!(
+            get_title(&ast_map, &hero_uri).as_deref(),
+            Some("Used in 2 documents")
+        );
+
+        let document_uri = Url::parse("file:///docs/index.mdx").unwrap();
+        assert_eq!(get_title(&ast_map, &document_uri), None);
+    }
+
+    #[test]
+    fn test_small_code_block() {
+        assert!(get_titles("```rust\nfn main() {}\n```").is_empty());
+    }
+}
@@ -0,0 +1,460 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use dashmap::DashMap;
+use markdown::{mdast::Node, to_mdast};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tower_lsp::lsp_types::{notification::Notification, Position, Range, TextEdit, Url};
+
+use crate::{
+    ast::find_all_matches,
+    config::ConfigValues,
+    encoding::get_node_range,
+    nodes::{
+        headings::get_heading_slugs,
+        is_configured_partial,
+        partials::{
+            get_partial_references, list_all_partials_in_dirs, list_partial_files, resolve_partial,
+        },
+        NodeExt,
+    },
+    parser::get_parser_options,
+};
+
+pub const LIST_PARTIALS: &str = "supermdx/listPartials";
+pub const CHECK_ALL_PARTIALS: &str = "supermdx/checkAllPartials";
+pub const COPY_TO_CLIPBOARD: &str = "supermdx/copyToClipboard";
+/// Opens the URI given as the first argument, e.g. a file created by a code
+/// action.
+pub const SHOW_DOCUMENT: &str = "supermdx/showDocument";
+/// Spell checks the section of the document URI given as the first argument,
+/// starting at the heading on the line given as the second.
+pub const SPELL_CHECK: &str = "supermdx/spellCheck";
+/// Inserts a table of contents into the document URI given as the first
+/// argument.
+pub const GENERATE_TOC: &str = "supermdx/generateToc";
+/// Shows the AST of the document URI given as the first argument, with the
+/// config it was parsed with.
+pub const DUMP_AST: &str = "supermdx/dumpAst";
+
+pub const COMMANDS: [&str; 7] = [
+    LIST_PARTIALS,
+    CHECK_ALL_PARTIALS,
+    COPY_TO_CLIPBOARD,
+    SHOW_DOCUMENT,
+    SPELL_CHECK,
+    GENERATE_TOC,
+    DUMP_AST,
+];
+
+/// Lines marking where the table of contents goes.
+const TOC_PLACEHOLDERS: [&str; 2] = ["<!-- toc -->", "{/* toc */}"];
+
+/// LSP has no clipboard request, so copying is forwarded to the client as a
+/// custom notification that the editor extension handles.
+pub enum CopyToClipboard {}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct CopyToClipboardParams {
+    pub text: String,
+}
+
+impl Notification for CopyToClipboard {
+    type Params = CopyToClipboardParams;
+    const METHOD: &'static str = COPY_TO_CLIPBOARD;
+}
+
+/// Writes a generated Markdown report to the temp directory so that it can
+/// be opened with `window/showDocument`, which only accepts URIs.
+pub async fn write_report(file_name: &str, content: &str) -> std::io::Result<Url> {
+    let dir = std::env::temp_dir().join("supermdx");
+    fs::create_dir_all(&dir).await?;
+
+    let path = dir.join(file_name);
+    fs::write(&path, content).await?;
+
+    Url::from_file_path(&path)
+        .map_err(|_| std::io::Error::other(format!("invalid report path: {}", path.display())))
+}
+
+/// Renders a GFM table of every partial file with its size, modification date
+/// and the open documents that reference it.
+pub async fn list_partials(config: &ConfigValues, ast_map: &DashMap<String, Node>) -> String {
+    let references = get_partial_references(ast_map, config);
+    let partials = list_all_partials_in_dirs(config).await;
+
+    let mut content = String::from("# Partials\n\n");
+    if partials.is_empty() {
+        content.push_str("No partial files found in `partials_dirs`.\n");
+    } else {
+        content.push_str(&get_partials_table(&partials, &references, config).await);
+    }
+
+    content.push_str(&format!("\n## Configuration\n\n```\n{}\n```\n", config));
+    content
+}
+
+async fn get_partials_table(
+    partials: &[(PathBuf, String)],
+    references: &HashMap<PathBuf, Vec<String>>,
+    config: &ConfigValues,
+) -> String {
+    let mut content = String::from("| Partial | Size | Modified | Referenced by |\n");
+    content.push_str("| --- | --- | --- | --- |\n");
+    for (file, src) in partials {
+        let (size, modified) = match fs::metadata(file).await {
+            Ok(metadata) => (
+                format_size(metadata.len()),
+                metadata
+                    .modified()
+                    .map(format_timestamp)
+                    .unwrap_or_else(|_| "-".to_string()),
+            ),
+            Err(_) => ("-".to_string(), "-".to_string()),
+        };
+
+        let mut referenced_by: Vec<_> = references
+            .get(file)
+            .into_iter()
+            .flatten()
+            .map(|uri| display_uri(uri, &config.workspace_root))
+            .collect();
+        referenced_by.sort();
+        let referenced_by = if referenced_by.is_empty() {
+            "-".to_string()
+        } else {
+            referenced_by.join("<br>")
+        };
+
+        content.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            src, size, modified, referenced_by
+        ));
+    }
+
+    content
+}
+
+/// Renders the AST of a document for debugging, followed by the config.
+pub fn dump_ast(uri: &Url, ast: &Node, config: &ConfigValues) -> String {
+    format!(
+        "# AST of {}\n\n```\n{:#?}\n```\n\n## Configuration\n\n```\n{}\n```\n",
+        display_uri(uri.as_str(), &config.workspace_root),
+        ast,
+        config
+    )
+}
+
+/// Parses every file in `partials_dirs` and checks that each `$Partial` it
+/// contains resolves to an existing file.
+pub async fn check_all_partials(config: &ConfigValues) -> String {
+    let mut resolved = 0;
+    let mut unresolved = Vec::new();
+    let mut unparsable = Vec::new();
+
+    for file in list_partial_files(&config.partials_dirs).await {
+        let display_path = display_path(&file, &config.workspace_root);
+        let Ok(text) = fs::read_to_string(&file).await else {
+            unparsable.push(display_path);
+            continue;
+        };
+        let Ok(ast) = to_mdast(&text, &get_parser_options()) else {
+            unparsable.push(display_path);
+            continue;
+        };
+
+        for partial in find_all_matches(&ast, |node| is_configured_partial(node, config)) {
+            let src = partial.get_attribute("src");
+            let resolved_path = match src {
+                Some(src) => resolve_partial(src, config).await,
+                None => None,
+            };
+            if resolved_path.is_some() {
+                resolved += 1;
+            } else {
+                let line = partial.position().map_or(0, |pos| pos.start.line);
+                unresolved.push((display_path.clone(), line, src.map(str::to_string)));
+            }
+        }
+    }
+
+    let mut content = String::from("# Partial check\n\n");
+    content.push_str(&format!(
+        "Checked {} partials: {} resolved, {} unresolved.\n",
+        resolved + unresolved.len(),
+        resolved,
+        unresolved.len()
+    ));
+
+    if !unresolved.is_empty() {
+        content.push_str("\n## Unresolved\n\n| File | Line | src |\n| --- | --- | --- |\n");
+        for (file, line, src) in unresolved {
+            let src = src.map_or("(missing)".to_string(), |src| format!("`{}`", src));
+            content.push_str(&format!("| {} | {} | {} |\n", file, line, src));
+        }
+    }
+
+    if !unparsable.is_empty() {
+        content.push_str("\n## Could not parse\n\n");
+        for file in unparsable {
+            content.push_str(&format!("- {}\n", file));
+        }
+    }
+
+    content
+}
+
+/// Builds a nested list linking to every heading, and the edit inserting it
+/// below the first toc placeholder, or else at the top of the document after
+/// any frontmatter. Returns `None` when the document has no headings.
+pub fn generate_toc(ast: &Node, text: &str) -> Option<(String, TextEdit)> {
+    let headings = get_heading_slugs(ast);
+    let min_depth = headings
+        .iter()
+        .filter_map(|(_, heading)| match heading {
+            Node::Heading(heading) => Some(heading.depth),
+            _ => None,
+        })
+        .min()?;
+
+    let toc = headings
+        .iter()
+        .filter_map(|(slug, heading)| {
+            let Node::Heading(node) = heading else {
+                return None;
+            };
+            Some(format!(
+                "{}- [{}](#{})",
+                "  ".repeat((node.depth - min_depth) as usize),
+                heading.to_string(),
+                slug
+            ))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let placeholder = text
+        .lines()
+        .enumerate()
+        .find(|(_, line)| TOC_PLACEHOLDERS.contains(&line.trim()));
+    let frontmatter = ast
+        .children()
+        .and_then(|children| children.first())
+        .filter(|node| matches!(node, Node::Yaml(_) | Node::Toml(_)));
+
+    let (position, new_text) = match (placeholder, frontmatter) {
+        (Some((line, content)), _) => (
+            Position::new(line as u32, content.encode_utf16().count() as u32),
+            format!("\n\n{}", toc),
+        ),
+        (None, Some(frontmatter)) => (
+            get_node_range(text, frontmatter)?.end,
+            format!("\n\n{}", toc),
+        ),
+        (None, None) => (Position::default(), format!("{}\n\n", toc)),
+    };
+
+    Some((
+        toc,
+        TextEdit {
+            range: Range::new(position, position),
+            new_text,
+        },
+    ))
+}
+
+fn display_uri(uri: &str, workspace_root: &Path) -> String {
+    Url::parse(uri)
+        .ok()
+        .and_then(|url| url.to_file_path().ok())
+        .map(|path| display_path(&path, workspace_root))
+        .unwrap_or_else(|| uri.to_string())
+}
+
+fn display_path(path: &Path, workspace_root: &Path) -> String {
+    path.strip_prefix(workspace_root)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+pub fn format_size(bytes: u64) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    }
+}
+
+/// Formats a timestamp as `YYYY-MM-DD HH:MM UTC`.
+fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let (days, seconds_of_day) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil-from-days conversion, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Duration};
+
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01 00:00 UTC");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(1_709_210_096)),
+            "2024-02-29 12:34 UTC"
+        );
+    }
+
+    #[test]
+    fn test_generate_toc() {
+        let text = "---\ntitle: Guide\n---\n\n# Guide\n\n## Setup\n\n### Install\n\n## Setup";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+
+        let (toc, edit) = generate_toc(&ast, text).unwrap();
+
+        assert_eq!(
+            toc,
+            "- [Guide](#guide)\n  - [Setup](#setup)\n    - [Install](#install)\n  - [Setup](#setup-1)"
+        );
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(2, 3), Position::new(2, 3))
+        );
+        assert_eq!(edit.new_text, format!("\n\n{}", toc));
+    }
+
+    #[test]
+    fn test_generate_toc_at_placeholder() {
+        let text = "Intro.\n\n{/* toc */}\n\n## Usage";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+
+        let (toc, edit) = generate_toc(&ast, text).unwrap();
+
+        assert_eq!(toc, "- [Usage](#usage)");
+        assert_eq!(
+            edit.range,
+            Range::new(Position::new(2, 11), Position::new(2, 11))
+        );
+    }
+
+    #[test]
+    fn test_generate_toc_without_headings() {
+        let text = "Just text.";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+
+        assert_eq!(generate_toc(&ast, text), None);
+    }
+
+    #[tokio::test]
+    async fn test_list_partials() {
+        let workspace = tempfile::tempdir().unwrap();
+        let partials_dir = workspace.path().join("partials");
+        fs::create_dir_all(&partials_dir).unwrap();
+        fs::write(partials_dir.join("hero.mdx"), "# Hero").unwrap();
+        fs::write(partials_dir.join("unused.mdx"), "").unwrap();
+
+        let config = ConfigValues {
+            workspace_root: workspace.path().to_path_buf(),
+            partials_dirs: vec![partials_dir],
+            ..Default::default()
+        };
+        let ast_map = DashMap::new();
+        let document = Url::from_file_path(workspace.path().join("index.mdx")).unwrap();
+        ast_map.insert(
+            document.to_string(),
+            to_mdast(r#"<$Partial src="hero.mdx" />"#, &get_parser_options()).unwrap(),
+        );
+
+        let content = list_partials(&config, &ast_map).await;
+        let rows: Vec<_> = content
+            .lines()
+            .filter(|line| line.starts_with("| "))
+            .skip(2)
+            .collect();
+
+        assert_eq!(rows.len(), 2);
+        assert!(rows[0].starts_with("| hero.mdx | 6 B | "));
+        assert!(rows[0].ends_with(" | index.mdx |"));
+        assert!(rows[1].starts_with("| unused.mdx | 0 B | "));
+        assert!(rows[1].ends_with(" | - |"));
+        assert!(content.contains("## Configuration"));
+    }
+
+    #[test]
+    fn test_dump_ast() {
+        let config = ConfigValues {
+            workspace_root: PathBuf::from("/docs"),
+            ..Default::default()
+        };
+        let uri = Url::from_file_path("/docs/guides/index.mdx").unwrap();
+        let ast = to_mdast("# Guide", &get_parser_options()).unwrap();
+
+        let content = dump_ast(&uri, &ast, &config);
+
+        assert!(content.starts_with("# AST of guides/index.mdx\n\n```\nRoot {"));
+        assert!(content.contains("Heading {"));
+        assert!(content.contains("## Configuration"));
+    }
+
+    #[tokio::test]
+    async fn test_check_all_partials() {
+        let workspace = tempfile::tempdir().unwrap();
+        let partials_dir = workspace.path().join("partials");
+        fs::create_dir_all(&partials_dir).unwrap();
+        fs::write(partials_dir.join("hero.mdx"), "# Hero").unwrap();
+        fs::write(
+            partials_dir.join("page.mdx"),
+            "<$Partial src=\"hero.mdx\" />\n\n<$Partial src=\"missing.mdx\" />\n\n<$Partial />",
+        )
+        .unwrap();
+
+        let config = ConfigValues {
+            workspace_root: workspace.path().to_path_buf(),
+            partials_dirs: vec![partials_dir],
+            ..Default::default()
+        };
+
+        let content = check_all_partials(&config).await;
+
+        assert!(content.contains("Checked 3 partials: 1 resolved, 2 unresolved."));
+        assert!(content.contains("| partials/page.mdx | 3 | `missing.mdx` |"));
+        assert!(content.contains("| partials/page.mdx | 5 | (missing) |"));
+    }
+}
@@ -0,0 +1,1460 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use dashmap::{DashMap, DashSet};
+use markdown::mdast::Node;
+use serde_json::Value;
+use tower_lsp::lsp_types::{
+    Command, CompletionItem, CompletionItemKind, CompletionTextEdit, Documentation,
+    InsertTextFormat, Position, Range, TextEdit, Url,
+};
+
+use crate::{
+    ast::{find_all_matches, find_deepest_match, get_ancestor_chain},
+    config::ConfigValues,
+    encoding::{offset_to_position, position_to_offset},
+    formatting::split_table_row,
+    nodes::{esm::get_declared_names, headings::get_heading_slugs},
+    workspace::relative_path,
+};
+
+const MAX_WORD_COMPLETIONS: usize = 20;
+/// Extensions of the workspace files offered as link destinations.
+pub const LINK_TARGET_EXTENSIONS: [&str; 8] = ["mdx", "md", "js", "ts", "tsx", "png", "jpg", "svg"];
+
+/// Which completions a document gets, set with a
+/// `{/* @supermdx completions=... */}` comment at its top.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompletionMode {
+    #[default]
+    All,
+    Off,
+    SnippetsOnly,
+}
+
+impl CompletionMode {
+    /// Reads the mode from the first non-blank line of the document.
+    pub fn from_pragma(text: &str) -> Self {
+        let Some(pragma) = text
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .and_then(|line| line.trim().strip_prefix("{/*"))
+            .and_then(|line| line.strip_suffix("*/}"))
+            .and_then(|comment| comment.trim().strip_prefix("@supermdx "))
+        else {
+            return Self::All;
+        };
+
+        match pragma
+            .split_whitespace()
+            .find_map(|option| option.strip_prefix("completions="))
+        {
+            Some("off") => Self::Off,
+            Some("snippets-only") => Self::SnippetsOnly,
+            _ => Self::All,
+        }
+    }
+}
+
+pub fn get_completions(
+    text: &str,
+    position: &Position,
+    config: &ConfigValues,
+) -> Vec<CompletionItem> {
+    let prefix = get_line_prefix(text, position);
+
+    match get_element_name_start(&prefix) {
+        Some(start) => get_snippet_completions(position, start, config),
+        None => Vec::new(),
+    }
+}
+
+/// Returns the text of the cursor's line up to the cursor. LSP columns are
+/// UTF-16 code units.
+pub fn get_line_prefix(text: &str, position: &Position) -> String {
+    let line = text.lines().nth(position.line as usize).unwrap_or_default();
+
+    let mut utf16_column = 0;
+    line.chars()
+        .take_while(|ch| {
+            utf16_column += ch.len_utf16() as u32;
+            utf16_column <= position.character
+        })
+        .collect()
+}
+
+/// If the cursor is right after `<` or a partially typed element name, returns
+/// the UTF-16 column of the `<`.
+fn get_element_name_start(prefix: &str) -> Option<u32> {
+    let open = prefix.rfind('<')?;
+    let name = &prefix[open + 1..];
+    if !name.chars().all(is_element_name_char) {
+        return None;
+    }
+
+    Some(prefix[..open].encode_utf16().count() as u32)
+}
+
+/// Offers the names declared in the document's ESM when the cursor is in a JSX
+/// attribute expression (`<Chart data={...`). Returns `None` elsewhere.
+pub fn get_expression_completions(
+    ast: &Node,
+    text: &str,
+    position: &Position,
+) -> Option<Vec<CompletionItem>> {
+    let prefix = get_line_prefix(text, position);
+    let tag = &prefix[prefix.rfind('<')?..];
+    let expression = &tag[tag.rfind("={")? + "={".len()..];
+    let typed = expression.trim_start();
+    if !typed
+        .chars()
+        .all(|ch| ch.is_alphanumeric() || matches!(ch, '_' | '$'))
+    {
+        return None;
+    }
+
+    let range = Range {
+        start: Position {
+            line: position.line,
+            character: position.character - typed.encode_utf16().count() as u32,
+        },
+        end: *position,
+    };
+
+    let completions = get_declared_names(ast)
+        .into_iter()
+        .filter(|name| name.starts_with(typed))
+        .map(|name| CompletionItem {
+            label: name.clone(),
+            kind: Some(CompletionItemKind::VARIABLE),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                range,
+                new_text: name,
+            })),
+            ..Default::default()
+        })
+        .collect();
+
+    Some(completions)
+}
+
+/// Returns the part of a `$Partial` `src` value typed so far when the cursor is
+/// inside its quotes. `partial_element_name` is the configured name of
+/// `$Partial`.
+pub fn get_partial_src_prefix(
+    text: &str,
+    position: &Position,
+    partial_element_name: &str,
+) -> Option<String> {
+    let prefix = get_line_prefix(text, position);
+    let tag = &prefix[prefix.rfind('<')?..];
+    let is_partial = tag
+        .strip_prefix('<')
+        .and_then(|tag| tag.strip_prefix(partial_element_name))
+        .is_some_and(|rest| rest.starts_with(char::is_whitespace));
+    if !is_partial {
+        return None;
+    }
+
+    let value = &tag[tag.rfind("src=")? + "src=".len()..];
+    let quote = value.chars().next().filter(|ch| matches!(ch, '"' | '\''))?;
+    let typed = &value[1..];
+    if typed.contains(quote) {
+        return None;
+    }
+
+    Some(typed.to_string())
+}
+
+/// Completes a `$Partial` `src` one path segment at a time: the files and
+/// subdirectories inside the directory typed so far, as listed by
+/// `list_all_partials_in_dirs`. Picking a subdirectory re-triggers completion
+/// for its contents.
+pub fn get_partial_src_completions(
+    position: &Position,
+    typed: &str,
+    partials: &[(PathBuf, String)],
+) -> Vec<CompletionItem> {
+    let (dir, segment) = match typed.rfind('/') {
+        Some(slash) => typed.split_at(slash + 1),
+        None => ("", typed),
+    };
+    let range = Range {
+        start: Position {
+            line: position.line,
+            character: position.character - segment.encode_utf16().count() as u32,
+        },
+        end: *position,
+    };
+
+    let mut completions: Vec<CompletionItem> = Vec::new();
+    for (_, src) in partials {
+        let Some(rest) = src.strip_prefix(dir) else {
+            continue;
+        };
+        let (label, is_dir) = match rest.find('/') {
+            Some(slash) => (&rest[..=slash], true),
+            None => (rest, false),
+        };
+        if !label.starts_with(segment) || completions.iter().any(|item| item.label == label) {
+            continue;
+        }
+
+        completions.push(CompletionItem {
+            label: label.to_string(),
+            kind: Some(if is_dir {
+                CompletionItemKind::FOLDER
+            } else {
+                CompletionItemKind::FILE
+            }),
+            detail: (!is_dir).then(|| src.clone()),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                range,
+                new_text: label.to_string(),
+            })),
+            command: is_dir.then(|| Command {
+                title: "Suggest".to_string(),
+                command: "editor.action.triggerSuggest".to_string(),
+                arguments: None,
+            }),
+            ..Default::default()
+        });
+    }
+
+    completions
+}
+
+/// Offers the indexed `.mdx` and `.md` files as import specifiers when the
+/// cursor is inside the string of an `import ... from "..."` statement. Paths
+/// are relative to `document_path`, and files in `partials_dirs` come first,
+/// with their title as detail. Returns `None` when the cursor isn't on an
+/// import specifier.
+pub fn get_import_completions<'a>(
+    text: &str,
+    position: &Position,
+    document_path: &Path,
+    files: impl IntoIterator<Item = &'a PathBuf>,
+    titles: &DashMap<Url, String>,
+    config: &ConfigValues,
+) -> Option<Vec<CompletionItem>> {
+    let prefix = get_line_prefix(text, position);
+    let (start, typed) = get_import_specifier(&prefix)?;
+    let document_dir = document_path.parent()?;
+
+    let range = Range {
+        start: Position {
+            line: position.line,
+            character: start,
+        },
+        end: *position,
+    };
+
+    let mut candidates: Vec<(bool, String, Option<Url>)> = files
+        .into_iter()
+        .filter(|file| file.as_path() != document_path)
+        .map(|file| {
+            let in_partials_dir = config.partials_dirs.iter().any(|dir| file.starts_with(dir));
+            let relative = relative_path(document_dir, file)
+                .to_string_lossy()
+                .replace('\\', "/");
+            let specifier = if relative.starts_with("../") {
+                relative
+            } else {
+                format!("./{}", relative)
+            };
+            (in_partials_dir, specifier, Url::from_file_path(file).ok())
+        })
+        .filter(|(_, specifier, _)| specifier.starts_with(typed))
+        .collect();
+    candidates
+        .sort_by(|(a_partial, a, _), (b_partial, b, _)| b_partial.cmp(a_partial).then(a.cmp(b)));
+
+    let completions = candidates
+        .into_iter()
+        .enumerate()
+        .map(
+            |(index, (in_partials_dir, specifier, uri))| CompletionItem {
+                label: specifier.clone(),
+                kind: Some(CompletionItemKind::FILE),
+                detail: uri
+                    .as_ref()
+                    .and_then(|uri| titles.get(uri))
+                    .map(|title| title.clone())
+                    .or_else(|| in_partials_dir.then(|| "Partial".to_string())),
+                data: uri.map(|uri| Value::String(uri.to_string())),
+                sort_text: Some(format!("{:05}", index)),
+                text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                    range,
+                    new_text: specifier,
+                })),
+                ..Default::default()
+            },
+        )
+        .collect();
+
+    Some(completions)
+}
+
+/// The part of an opening tag before the cursor.
+#[derive(Debug, PartialEq)]
+pub struct TagPrefix<'a> {
+    pub name: &'a str,
+    /// The attribute names written before the cursor, in order.
+    pub attributes: Vec<&'a str>,
+    pub cursor: TagCursor<'a>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TagCursor<'a> {
+    /// Where an attribute name goes, with the part of it typed so far.
+    AttributeName(&'a str),
+    /// In the value of the last of the `attributes`.
+    AttributeValue,
+    /// Anywhere else, e.g. right after a closing quote.
+    Other,
+}
+
+/// Reads the opening tag the cursor is in from the line before it. Returns
+/// `None` outside of an opening tag, or while its name is being typed.
+pub fn parse_tag_prefix(prefix: &str) -> Option<TagPrefix<'_>> {
+    let tag = &prefix[prefix.rfind('<')? + 1..];
+    let name_end = tag
+        .find(|ch: char| !is_element_name_char(ch))
+        .filter(|end| tag[*end..].starts_with(char::is_whitespace))?;
+
+    // Attribute values can hold anything, including `<` and spaces, so only
+    // words outside of them are attribute names.
+    let mut names: Vec<&str> = Vec::new();
+    let mut word_start = None;
+    let mut quote = None;
+    let mut depth = 0;
+    let attributes = &tag[name_end..];
+    for (index, ch) in attributes.char_indices() {
+        match (quote, ch) {
+            (Some(open), _) if ch == open => quote = None,
+            (Some(_), _) => {}
+            (None, _) if depth == 0 && (ch.is_alphanumeric() || matches!(ch, '-' | '_')) => {
+                word_start.get_or_insert(index);
+                continue;
+            }
+            (None, '"' | '\'') if depth == 0 => quote = Some(ch),
+            (None, '{') => depth += 1,
+            (None, '}') => depth -= 1,
+            (None, '>') if depth == 0 => return None,
+            _ => {}
+        }
+        if let Some(start) = word_start.take() {
+            names.push(&attributes[start..index]);
+        }
+    }
+
+    let typed_start = word_start.unwrap_or(attributes.len());
+    let cursor = if quote.is_some() || depth != 0 || attributes.ends_with('=') {
+        TagCursor::AttributeValue
+    } else if attributes[..typed_start].ends_with(char::is_whitespace) {
+        TagCursor::AttributeName(&attributes[typed_start..])
+    } else {
+        TagCursor::Other
+    };
+
+    Some(TagPrefix {
+        name: &tag[..name_end],
+        attributes: names,
+        cursor,
+    })
+}
+
+/// Offers the props of `config.prop_schemas` for the element whose opening
+/// tag the cursor is in, when it's where an attribute name goes. Props the
+/// tag already has are left out. Returns `None` outside of an attribute name.
+pub fn get_prop_completions(
+    text: &str,
+    position: &Position,
+    config: &ConfigValues,
+) -> Option<Vec<CompletionItem>> {
+    let prefix = get_line_prefix(text, position);
+    let tag = parse_tag_prefix(&prefix)?;
+    let schema = config.prop_schemas.get(tag.name)?;
+    let TagCursor::AttributeName(typed) = tag.cursor else {
+        return None;
+    };
+    let range = Range {
+        start: Position {
+            line: position.line,
+            character: position.character - typed.encode_utf16().count() as u32,
+        },
+        end: *position,
+    };
+
+    let completions = schema
+        .fields
+        .iter()
+        .filter(|field| field.starts_with(typed) && !tag.attributes.contains(&field.as_str()))
+        .map(|field| CompletionItem {
+            label: field.clone(),
+            kind: Some(CompletionItemKind::PROPERTY),
+            detail: Some(format!("{} prop", tag.name)),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                range,
+                new_text: field.clone(),
+            })),
+            ..Default::default()
+        })
+        .collect();
+
+    Some(completions)
+}
+
+/// Returns where the destination of the link or image under the cursor starts,
+/// along with the part of it typed so far, when the cursor is inside it.
+/// Fragments are left to `get_anchor_completions`, and web URLs aren't
+/// completed.
+pub fn get_link_destination_prefix(
+    ast: &Node,
+    text: &str,
+    position: &Position,
+) -> Option<(Position, String)> {
+    let ancestor_chain = get_ancestor_chain(ast, text, position);
+    let link = find_deepest_match(&ancestor_chain, |node| {
+        matches!(node, Node::Link(_) | Node::Image(_))
+    })?;
+    let link_position = link.position()?;
+    let source = text.get(link_position.start.offset..link_position.end.offset)?;
+
+    let destination_start = link_position.start.offset + source.rfind("](")? + "](".len();
+    let cursor = position_to_offset(text, position);
+    let typed = text.get(destination_start..cursor)?;
+    if typed.contains([' ', ')', '#']) || typed.contains("://") {
+        return None;
+    }
+
+    Some((
+        offset_to_position(text, destination_start),
+        typed.to_string(),
+    ))
+}
+
+/// Offers `files` as link destinations relative to the directory of
+/// `document_path`, with a leading `./` when the typed destination has one.
+pub fn get_link_path_completions(
+    start: Position,
+    position: &Position,
+    typed: &str,
+    document_path: &Path,
+    files: &[PathBuf],
+) -> Vec<CompletionItem> {
+    let Some(document_dir) = document_path.parent() else {
+        return Vec::new();
+    };
+    let range = Range {
+        start,
+        end: *position,
+    };
+
+    files
+        .iter()
+        .filter(|file| file.as_path() != document_path)
+        .map(|file| {
+            let relative = relative_path(document_dir, file)
+                .to_string_lossy()
+                .replace('\\', "/");
+            if typed.starts_with("./") && !relative.starts_with("../") {
+                format!("./{}", relative)
+            } else {
+                relative
+            }
+        })
+        .filter(|destination| destination.starts_with(typed))
+        .map(|destination| CompletionItem {
+            label: destination.clone(),
+            kind: Some(CompletionItemKind::FILE),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                range,
+                new_text: destination,
+            })),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Offers the delimiter row of a GFM table when the cursor is on a `|` line
+/// right below the header row. Returns `None` elsewhere.
+pub fn get_table_delimiter_completions(
+    text: &str,
+    position: &Position,
+) -> Option<Vec<CompletionItem>> {
+    let lines: Vec<&str> = text.lines().collect();
+    let line_index = position.line as usize;
+    let is_row = |index: usize| {
+        lines
+            .get(index)
+            .is_some_and(|line| line.trim_start().starts_with('|'))
+    };
+
+    // The line above must be the header, i.e. the first row of the table.
+    let header_index = line_index.checked_sub(1)?;
+    if !get_line_prefix(text, position)
+        .trim_start()
+        .starts_with('|')
+        || !is_row(header_index)
+        || header_index.checked_sub(1).is_some_and(is_row)
+    {
+        return None;
+    }
+
+    let columns = split_table_row(lines[header_index]).len();
+    let delimiter_row = format!("|{}", " --- |".repeat(columns));
+    let line = lines.get(line_index).copied().unwrap_or_default();
+
+    Some(vec![CompletionItem {
+        label: delimiter_row.clone(),
+        kind: Some(CompletionItemKind::SNIPPET),
+        detail: Some(format!("Table delimiter row ({} columns)", columns)),
+        filter_text: Some(line.trim().to_string()),
+        text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+            range: Range {
+                start: Position {
+                    line: position.line,
+                    character: 0,
+                },
+                end: Position {
+                    line: position.line,
+                    character: line.encode_utf16().count() as u32,
+                },
+            },
+            new_text: delimiter_row,
+        })),
+        ..Default::default()
+    }])
+}
+
+const PRAGMAS: [(&str, &str); 4] = [
+    ("@jsxRuntime classic", "Use the classic JSX runtime"),
+    ("@jsxRuntime automatic", "Use the automatic JSX runtime"),
+    (
+        "@jsx React.createElement",
+        "JSX factory for the classic runtime",
+    ),
+    (
+        "@jsxFrag React.Fragment",
+        "JSX fragment for the classic runtime",
+    ),
+];
+
+/// Offers MDX pragmas when `@` is typed inside a `/* */` comment at the top of
+/// the file, before any content other than comments. Returns `None` elsewhere.
+pub fn get_pragma_completions(text: &str, position: &Position) -> Option<Vec<CompletionItem>> {
+    let before_line: String = text
+        .split_inclusive('\n')
+        .take(position.line as usize)
+        .collect();
+    let prefix = get_line_prefix(text, position);
+    let before_cursor = format!("{}{}", before_line, prefix);
+
+    let comment_start = before_cursor.rfind("/*")?;
+    let comment = &before_cursor[comment_start..];
+    if comment.contains("*/") || !is_only_comments(&before_cursor[..comment_start]) {
+        return None;
+    }
+
+    let at = prefix.rfind('@')?;
+    if !prefix[at + 1..]
+        .chars()
+        .all(|ch| ch.is_alphanumeric() || ch == '.' || ch == ' ')
+    {
+        return None;
+    }
+    let range = Range {
+        start: Position {
+            line: position.line,
+            character: prefix[..at].encode_utf16().count() as u32,
+        },
+        end: *position,
+    };
+
+    let completions = PRAGMAS
+        .iter()
+        .map(|(pragma, detail)| CompletionItem {
+            label: pragma.to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            detail: Some(detail.to_string()),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                range,
+                new_text: pragma.to_string(),
+            })),
+            ..Default::default()
+        })
+        .collect();
+
+    Some(completions)
+}
+
+/// Checks that `text` holds nothing but whitespace and closed `/* */`
+/// comments, optionally wrapped in MDX expression braces.
+fn is_only_comments(text: &str) -> bool {
+    let mut rest = text.trim_start();
+    loop {
+        rest = rest.trim_start_matches(|ch: char| ch.is_whitespace() || ch == '{' || ch == '}');
+        if rest.is_empty() {
+            return true;
+        }
+        let Some(comment) = rest.strip_prefix("/*") else {
+            return false;
+        };
+        let Some(end) = comment.find("*/") else {
+            return false;
+        };
+        rest = &comment[end + 2..];
+    }
+}
+
+/// Adds the title of the file an item refers to as its documentation.
+pub fn resolve_completion(
+    mut item: CompletionItem,
+    titles: &DashMap<Url, String>,
+) -> CompletionItem {
+    let title = item
+        .data
+        .as_ref()
+        .and_then(Value::as_str)
+        .and_then(|uri| Url::parse(uri).ok())
+        .and_then(|uri| titles.get(&uri).map(|title| title.clone()));
+    if let Some(title) = title {
+        item.documentation = Some(Documentation::String(title));
+    }
+
+    item
+}
+
+/// Offers the slugs of the document's headings when the cursor is in the
+/// `#fragment` of a link destination (`[text](#...)`). Returns `None` outside
+/// of one.
+pub fn get_anchor_completions(
+    ast: &Node,
+    text: &str,
+    position: &Position,
+) -> Option<Vec<CompletionItem>> {
+    let prefix = get_line_prefix(text, position);
+    let start = prefix.rfind("](#")? + "](#".len();
+    if prefix[start..].contains([')', ' ']) {
+        return None;
+    }
+
+    let range = Range {
+        start: Position {
+            line: position.line,
+            character: prefix[..start].encode_utf16().count() as u32,
+        },
+        end: *position,
+    };
+
+    let completions = get_heading_slugs(ast)
+        .into_iter()
+        .map(|(slug, heading)| CompletionItem {
+            label: slug.clone(),
+            kind: Some(CompletionItemKind::REFERENCE),
+            detail: Some(heading.to_string()),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                range,
+                new_text: slug,
+            })),
+            ..Default::default()
+        })
+        .collect();
+
+    Some(completions)
+}
+
+/// Offers words from the document's prose and from `wordlist` that start with
+/// the word being typed, most frequent first. Returns `None` when the cursor
+/// isn't at the end of a word in a text node.
+pub fn get_word_completions(
+    ast: &Node,
+    text: &str,
+    position: &Position,
+    wordlist: Option<&DashSet<String>>,
+) -> Option<Vec<CompletionItem>> {
+    if !matches!(
+        get_ancestor_chain(ast, text, position).last(),
+        Some(Node::Text(_))
+    ) {
+        return None;
+    }
+
+    let prefix = get_line_prefix(text, position);
+    let typed = &prefix[prefix.trim_end_matches(is_word_char).len()..];
+    if typed.is_empty() {
+        return None;
+    }
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for node in find_all_matches(ast, |node| matches!(node, Node::Text(_))) {
+        for word in node.to_string().split(|ch: char| !is_word_char(ch)) {
+            if !word.is_empty() {
+                *counts.entry(word.to_string()).or_default() += 1;
+            }
+        }
+    }
+    for word in wordlist.iter().flat_map(|wordlist| wordlist.iter()) {
+        counts.entry(word.clone()).or_default();
+    }
+
+    let typed_lowercase = typed.to_lowercase();
+    let mut words: Vec<_> = counts
+        .into_iter()
+        .filter(|(word, _)| word != typed && word.to_lowercase().starts_with(&typed_lowercase))
+        .collect();
+    words.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+
+    let range = Range {
+        start: Position {
+            line: position.line,
+            character: position.character - typed.encode_utf16().count() as u32,
+        },
+        end: *position,
+    };
+
+    let completions = words
+        .into_iter()
+        .take(MAX_WORD_COMPLETIONS)
+        .enumerate()
+        .map(|(rank, (word, _))| CompletionItem {
+            label: word.clone(),
+            kind: Some(CompletionItemKind::TEXT),
+            sort_text: Some(format!("{:02}", rank)),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                range,
+                new_text: word,
+            })),
+            ..Default::default()
+        })
+        .collect();
+
+    Some(completions)
+}
+
+fn is_word_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// If the line is an ESM import and the cursor is inside its specifier string,
+/// returns the UTF-16 column where the specifier starts and the part of it
+/// typed so far.
+fn get_import_specifier(prefix: &str) -> Option<(u32, &str)> {
+    if !prefix.starts_with("import ") {
+        return None;
+    }
+
+    let quote = prefix.rfind(['"', '\''])?;
+    let before_quote = prefix[..quote].trim_end();
+    if !before_quote.ends_with(" from") && before_quote != "import" {
+        return None;
+    }
+    // The quote must open the specifier rather than close it.
+    if prefix[..quote].matches(['"', '\'']).count() % 2 == 1 {
+        return None;
+    }
+
+    let start = quote + 1;
+    Some((
+        prefix[..start].encode_utf16().count() as u32,
+        &prefix[start..],
+    ))
+}
+
+fn is_element_name_char(ch: char) -> bool {
+    ch.is_alphanumeric() || matches!(ch, '$' | '_' | '.' | ':' | '-')
+}
+
+fn get_snippet_completions(
+    position: &Position,
+    start: u32,
+    config: &ConfigValues,
+) -> Vec<CompletionItem> {
+    // Snippets include the opening `<`, so they replace what has been typed so
+    // far rather than being inserted after it.
+    let range = Range {
+        start: Position {
+            line: position.line,
+            character: start,
+        },
+        end: *position,
+    };
+
+    config
+        .snippets
+        .iter()
+        .map(|snippet| CompletionItem {
+            label: snippet.label.clone(),
+            kind: Some(CompletionItemKind::SNIPPET),
+            detail: snippet.detail.clone(),
+            filter_text: Some(format!("<{}", snippet.label)),
+            insert_text_format: Some(InsertTextFormat::SNIPPET),
+            text_edit: Some(CompletionTextEdit::Edit(TextEdit {
+                range,
+                new_text: snippet.snippet_text.clone(),
+            })),
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::{config::PropSchema, nodes::PARTIAL, parser::get_parser_options};
+
+    #[test]
+    fn test_get_line_prefix() {
+        let text = "first line\nsecond 😀 line";
+
+        assert_eq!(
+            get_line_prefix(
+                text,
+                &Position {
+                    line: 1,
+                    character: 9,
+                }
+            ),
+            "second 😀"
+        );
+        assert_eq!(
+            get_line_prefix(
+                text,
+                &Position {
+                    line: 5,
+                    character: 3,
+                }
+            ),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_completion_mode_from_pragma() {
+        assert_eq!(
+            CompletionMode::from_pragma("\n{/* @supermdx completions=off */}\n\n# Generated"),
+            CompletionMode::Off
+        );
+        assert_eq!(
+            CompletionMode::from_pragma("{/* @supermdx completions=snippets-only */}"),
+            CompletionMode::SnippetsOnly
+        );
+        assert_eq!(
+            CompletionMode::from_pragma("# Title\n\n{/* @supermdx completions=off */}"),
+            CompletionMode::All
+        );
+        assert_eq!(
+            CompletionMode::from_pragma("{/* @supermdx completions=maybe */}"),
+            CompletionMode::All
+        );
+    }
+
+    #[test]
+    fn test_snippet_completions() {
+        let position = Position {
+            line: 2,
+            character: 3,
+        };
+        let completions = get_completions("# Hello\n\n<Ca", &position, &ConfigValues::default());
+
+        let labels: Vec<_> = completions.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["$Partial", "Callout", "CodeBlock"]);
+
+        let callout = &completions[1];
+        assert_eq!(callout.insert_text_format, Some(InsertTextFormat::SNIPPET));
+        assert_eq!(
+            callout.text_edit,
+            Some(CompletionTextEdit::Edit(TextEdit {
+                range: Range {
+                    start: Position {
+                        line: 2,
+                        character: 0,
+                    },
+                    end: position,
+                },
+                new_text: "<Callout type=\"$1\">$2</Callout>".to_string(),
+            }))
+        );
+    }
+
+    fn get_import_labels(text: &str, position: Position) -> Option<Vec<String>> {
+        let files = [
+            PathBuf::from("/docs/guides/intro.mdx"),
+            PathBuf::from("/docs/guides/setup.md"),
+            PathBuf::from("/docs/partials/hero.mdx"),
+        ];
+        let config = ConfigValues {
+            partials_dirs: vec![PathBuf::from("/docs/partials")],
+            ..Default::default()
+        };
+
+        get_import_completions(
+            text,
+            &position,
+            Path::new("/docs/guides/intro.mdx"),
+            &files,
+            &DashMap::new(),
+            &config,
+        )
+        .map(|items| items.into_iter().map(|item| item.label).collect())
+    }
+
+    #[test]
+    fn test_import_completions_with_titles() {
+        let titles = DashMap::new();
+        let hero = Url::from_file_path("/docs/partials/hero.mdx").unwrap();
+        titles.insert(hero.clone(), "Hero Section".to_string());
+        let config = ConfigValues {
+            partials_dirs: vec![PathBuf::from("/docs/partials")],
+            ..Default::default()
+        };
+
+        let completions = get_import_completions(
+            "import Hero from \"../p",
+            &Position {
+                line: 0,
+                character: 22,
+            },
+            Path::new("/docs/guides/intro.mdx"),
+            &[PathBuf::from("/docs/partials/hero.mdx")],
+            &titles,
+            &config,
+        )
+        .unwrap();
+        assert_eq!(completions[0].detail.as_deref(), Some("Hero Section"));
+
+        let resolved = resolve_completion(completions[0].clone(), &titles);
+        assert_eq!(
+            resolved.documentation,
+            Some(Documentation::String("Hero Section".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_import_completions() {
+        let labels = get_import_labels(
+            "import Hero from \"",
+            Position {
+                line: 0,
+                character: 18,
+            },
+        );
+
+        assert_eq!(
+            labels,
+            Some(vec![
+                "../partials/hero.mdx".to_string(),
+                "./setup.md".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_import_completions_filtered_by_prefix() {
+        let labels = get_import_labels(
+            "import Setup from './s",
+            Position {
+                line: 0,
+                character: 22,
+            },
+        );
+
+        assert_eq!(labels, Some(vec!["./setup.md".to_string()]));
+    }
+
+    #[test]
+    fn test_no_import_completions_outside_specifier() {
+        let labels = get_import_labels(
+            "import Hero from \"./hero.mdx\"",
+            Position {
+                line: 0,
+                character: 30,
+            },
+        );
+
+        assert_eq!(labels, None);
+    }
+
+    fn get_link_labels(text: &str, position: Position) -> Option<Vec<String>> {
+        let files = [
+            PathBuf::from("/docs/guides/intro.mdx"),
+            PathBuf::from("/docs/guides/setup.md"),
+            PathBuf::from("/docs/images/logo.png"),
+        ];
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+
+        let (start, typed) = get_link_destination_prefix(&ast, text, &position)?;
+        let completions = get_link_path_completions(
+            start,
+            &position,
+            &typed,
+            Path::new("/docs/guides/intro.mdx"),
+            &files,
+        );
+        Some(completions.into_iter().map(|item| item.label).collect())
+    }
+
+    #[test]
+    fn test_link_path_completions() {
+        assert_eq!(
+            get_link_labels("See [the setup]() first.", Position::new(0, 16)),
+            Some(vec![
+                "setup.md".to_string(),
+                "../images/logo.png".to_string()
+            ])
+        );
+        assert_eq!(
+            get_link_labels("Café ![logo](./s)", Position::new(0, 16)),
+            Some(vec!["./setup.md".to_string()])
+        );
+        assert_eq!(
+            get_link_labels("[a](../im)", Position::new(0, 9)),
+            Some(vec!["../images/logo.png".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_no_link_path_completions_outside_destination() {
+        assert_eq!(
+            get_link_labels("See [the setup](setup.md) first.", Position::new(0, 8)),
+            None
+        );
+        assert_eq!(
+            get_link_labels("See [the setup](setup.md) first.", Position::new(0, 26)),
+            None
+        );
+        assert_eq!(
+            get_link_labels("[site](https://ex)", Position::new(0, 17)),
+            None
+        );
+        assert_eq!(get_link_labels("[a](#ne)", Position::new(0, 7)), None);
+    }
+
+    fn get_prop_labels(text: &str, character: u32) -> Option<Vec<String>> {
+        let config = ConfigValues {
+            prop_schemas: [(
+                PARTIAL.to_string(),
+                PropSchema {
+                    fields: vec!["src".to_string(), "id".to_string(), "class".to_string()],
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+
+        get_prop_completions(text, &Position::new(0, character), &config)
+            .map(|items| items.into_iter().map(|item| item.label).collect())
+    }
+
+    #[test]
+    fn test_prop_completions() {
+        assert_eq!(
+            get_prop_labels("<$Partial ", 10),
+            Some(vec![
+                "src".to_string(),
+                "id".to_string(),
+                "class".to_string()
+            ])
+        );
+        assert_eq!(
+            get_prop_labels("<$Partial src=\"a id\" title={x > 1} c", 36),
+            Some(vec!["class".to_string()])
+        );
+        assert_eq!(
+            get_prop_labels("<$Partial src=\"hero.mdx\" i", 26),
+            Some(vec!["id".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_no_prop_completions_outside_attribute_name() {
+        assert_eq!(get_prop_labels("<$Partial src=\"i", 16), None);
+        assert_eq!(get_prop_labels("<$Partial title={i", 18), None);
+        assert_eq!(get_prop_labels("<$Partial />", 12), None);
+        assert_eq!(get_prop_labels("<$Partial src=\"a\"i", 18), None);
+        assert_eq!(get_prop_labels("<Card ", 6), None);
+    }
+
+    #[test]
+    fn test_anchor_completions() {
+        let text = "# Getting Started\n\n## Next steps\n\nSee [below](#ne";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        let position = Position {
+            line: 4,
+            character: 15,
+        };
+
+        let completions = get_anchor_completions(&ast, text, &position).unwrap();
+
+        let items: Vec<_> = completions
+            .iter()
+            .map(|item| (item.label.as_str(), item.detail.as_deref()))
+            .collect();
+        assert_eq!(
+            items,
+            vec![
+                ("getting-started", Some("Getting Started")),
+                ("next-steps", Some("Next steps"))
+            ]
+        );
+        assert_eq!(completions[0].kind, Some(CompletionItemKind::REFERENCE));
+        assert_eq!(
+            completions[1].text_edit,
+            Some(CompletionTextEdit::Edit(TextEdit {
+                range: Range {
+                    start: Position {
+                        line: 4,
+                        character: 13,
+                    },
+                    end: position,
+                },
+                new_text: "next-steps".to_string(),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_word_completions() {
+        let text = "Supabase Auth uses Supabase Storage.\n\nSuper fast. Sup";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        let position = Position {
+            line: 2,
+            character: 15,
+        };
+        let wordlist = DashSet::from_iter(["superuser".to_string(), "auth".to_string()]);
+
+        let completions = get_word_completions(&ast, text, &position, Some(&wordlist)).unwrap();
+
+        let labels: Vec<_> = completions.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["Supabase", "Super", "superuser"]);
+        assert_eq!(
+            completions[0].text_edit,
+            Some(CompletionTextEdit::Edit(TextEdit {
+                range: Range {
+                    start: Position {
+                        line: 2,
+                        character: 12,
+                    },
+                    end: position,
+                },
+                new_text: "Supabase".to_string(),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_no_word_completions_in_code() {
+        let text = "Supabase\n\n```\nSup\n```";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        let position = Position {
+            line: 3,
+            character: 3,
+        };
+
+        assert_eq!(get_word_completions(&ast, text, &position, None), None);
+    }
+
+    #[test]
+    fn test_get_partial_src_prefix() {
+        let text = "<$Partial src=\"buttons/pri\" />\n<Callout src=\"";
+
+        assert_eq!(
+            get_partial_src_prefix(
+                text,
+                &Position {
+                    line: 0,
+                    character: 26,
+                },
+                PARTIAL,
+            ),
+            Some("buttons/pri".to_string())
+        );
+        assert_eq!(
+            get_partial_src_prefix(
+                text,
+                &Position {
+                    line: 0,
+                    character: 28,
+                },
+                PARTIAL,
+            ),
+            None
+        );
+        assert_eq!(
+            get_partial_src_prefix(
+                text,
+                &Position {
+                    line: 1,
+                    character: 14,
+                },
+                PARTIAL,
+            ),
+            None
+        );
+        assert_eq!(
+            get_partial_src_prefix(
+                "<Include src=\"her",
+                &Position {
+                    line: 0,
+                    character: 17,
+                },
+                "Include",
+            ),
+            Some("her".to_string())
+        );
+    }
+
+    #[test]
+    fn test_partial_src_completions() {
+        let partials: Vec<_> = [
+            "hero.mdx",
+            "buttons/primary.mdx",
+            "buttons/secondary.mdx",
+            "buttons/icons/close.mdx",
+        ]
+        .into_iter()
+        .map(|src| (PathBuf::from("/partials").join(src), src.to_string()))
+        .collect();
+        let labels = |typed: &str| {
+            let position = Position {
+                line: 0,
+                character: 15 + typed.len() as u32,
+            };
+            get_partial_src_completions(&position, typed, &partials)
+                .into_iter()
+                .map(|item| item.label)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(labels(""), vec!["hero.mdx", "buttons/"]);
+        assert_eq!(labels("but"), vec!["buttons/"]);
+        assert_eq!(
+            labels("buttons/"),
+            vec!["primary.mdx", "secondary.mdx", "icons/"]
+        );
+        assert_eq!(labels("buttons/s"), vec!["secondary.mdx"]);
+
+        let position = Position {
+            line: 0,
+            character: 24,
+        };
+        let completions = get_partial_src_completions(&position, "buttons/s", &partials);
+        assert_eq!(
+            completions[0].detail.as_deref(),
+            Some("buttons/secondary.mdx")
+        );
+        assert_eq!(
+            completions[0].text_edit,
+            Some(CompletionTextEdit::Edit(TextEdit {
+                range: Range {
+                    start: Position {
+                        line: 0,
+                        character: 23,
+                    },
+                    end: position,
+                },
+                new_text: "secondary.mdx".to_string(),
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_partial_src_completions_from_dirs() {
+        let docs = tempfile::tempdir().unwrap();
+        let shared = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(docs.path().join("buttons")).unwrap();
+        std::fs::write(docs.path().join("hero.mdx"), "").unwrap();
+        std::fs::write(docs.path().join("logo.png"), "").unwrap();
+        std::fs::write(docs.path().join("buttons/primary.mdx"), "").unwrap();
+        std::fs::write(shared.path().join("hero.mdx"), "").unwrap();
+        std::fs::write(shared.path().join("footer.mdx"), "").unwrap();
+
+        let config = ConfigValues {
+            partials_dirs: vec![docs.path().to_path_buf(), shared.path().to_path_buf()],
+            ..Default::default()
+        };
+        let partials = crate::nodes::partials::list_all_partials_in_dirs(&config).await;
+
+        let text = "<$Partial src=\"";
+        let position = Position {
+            line: 0,
+            character: 15,
+        };
+        let typed = get_partial_src_prefix(text, &position, PARTIAL).unwrap();
+        let completions = get_partial_src_completions(&position, &typed, &partials);
+
+        let items: Vec<_> = completions
+            .iter()
+            .map(|item| (item.label.as_str(), item.kind))
+            .collect();
+        assert_eq!(
+            items,
+            vec![
+                ("hero.mdx", Some(CompletionItemKind::FILE)),
+                ("buttons/", Some(CompletionItemKind::FOLDER)),
+                ("footer.mdx", Some(CompletionItemKind::FILE)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_expression_completions() {
+        let esm =
+            "import { Chart } from \"./chart\"\nexport const data = [1]\nexport const dates = []";
+        // The unfinished tag doesn't parse, so the AST is the one from before.
+        let ast = to_mdast(esm, &get_parser_options()).unwrap();
+        let text = format!("{}\n\n<Chart data={{da", esm);
+        let text = text.as_str();
+        let position = Position {
+            line: 4,
+            character: 15,
+        };
+
+        let completions = get_expression_completions(&ast, text, &position).unwrap();
+
+        let labels: Vec<_> = completions.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["data", "dates"]);
+        assert_eq!(completions[0].kind, Some(CompletionItemKind::VARIABLE));
+        assert_eq!(
+            get_expression_completions(
+                &ast,
+                "<Chart data={data.map(",
+                &Position {
+                    line: 0,
+                    character: 22,
+                }
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_no_anchor_completions_after_link() {
+        let text = "# Hello\n\n[link](#hello) ";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        let position = Position {
+            line: 2,
+            character: 15,
+        };
+
+        assert!(get_anchor_completions(&ast, text, &position).is_none());
+    }
+
+    #[test]
+    fn test_table_delimiter_completions() {
+        let text = "Intro\n\n| Name | Type | Default |\n|";
+        let position = Position {
+            line: 3,
+            character: 1,
+        };
+
+        let completions = get_table_delimiter_completions(text, &position).unwrap();
+
+        assert_eq!(completions.len(), 1);
+        assert_eq!(
+            completions[0].text_edit,
+            Some(CompletionTextEdit::Edit(TextEdit {
+                range: Range {
+                    start: Position {
+                        line: 3,
+                        character: 0,
+                    },
+                    end: Position {
+                        line: 3,
+                        character: 1,
+                    },
+                },
+                new_text: "| --- | --- | --- |".to_string(),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_no_table_delimiter_completions_in_body() {
+        let text = "| a | b |\n| --- | --- |\n| 1 | 2 |\n|";
+        let position = Position {
+            line: 3,
+            character: 1,
+        };
+
+        assert!(get_table_delimiter_completions(text, &position).is_none());
+        assert!(get_table_delimiter_completions(
+            "Text\n|",
+            &Position {
+                line: 1,
+                character: 1,
+            }
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_pragma_completions() {
+        let text = "{/* @jsxImportSource react */}\n{/* @js";
+        let position = Position {
+            line: 1,
+            character: 7,
+        };
+
+        let completions = get_pragma_completions(text, &position).unwrap();
+
+        let labels: Vec<_> = completions.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(
+            labels,
+            vec![
+                "@jsxRuntime classic",
+                "@jsxRuntime automatic",
+                "@jsx React.createElement",
+                "@jsxFrag React.Fragment"
+            ]
+        );
+        assert_eq!(
+            completions[0].text_edit,
+            Some(CompletionTextEdit::Edit(TextEdit {
+                range: Range {
+                    start: Position {
+                        line: 1,
+                        character: 4,
+                    },
+                    end: position,
+                },
+                new_text: "@jsxRuntime classic".to_string(),
+            }))
+        );
+    }
+
+    #[test]
+    fn test_no_pragma_completions_after_content() {
+        let position = Position {
+            line: 2,
+            character: 4,
+        };
+
+        assert!(get_pragma_completions("# Hello\n\n/* @", &position).is_none());
+        assert!(get_pragma_completions(
+            "/* a */ @",
+            &Position {
+                line: 0,
+                character: 9
+            }
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn test_no_completions_outside_element_name() {
+        let position = Position {
+            line: 0,
+            character: 9,
+        };
+        let completions = get_completions("<Callout ", &position, &ConfigValues::default());
+
+        assert!(completions.is_empty());
+    }
+}
@@ -0,0 +1,1128 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use toml::Table;
+use tower_lsp::lsp_types::InitializeParams;
+use tracing::level_filters::LevelFilter;
+
+use crate::{nodes::PARTIAL, workspace::normalize_path};
+
+pub const CONFIG_FILE_NAME: &str = ".supermdx.toml";
+pub const JSON_CONFIG_FILE_NAME: &str = ".supermdx.json";
+/// Key of `initializationOptions` holding config overrides.
+pub const INITIALIZATION_OPTIONS_KEY: &str = "supermdx";
+/// Section of the client's settings requested with `workspace/configuration`.
+pub const SETTINGS_SECTION: &str = "supermdx";
+
+/// Shared handle to the server configuration.
+///
+/// `Clone::clone` is shallow: the clone shares the same `ConfigValues`, so an
+/// update through either handle is seen by both. Use [`Config::deep_clone`]
+/// for an independent copy.
+#[derive(Debug, Clone, Default)]
+pub struct Config(pub Arc<Mutex<ConfigValues>>);
+
+impl Config {
+    /// Serializes the current values in the format of `.supermdx.toml`, e.g.
+    /// to check that a written config reads back the same.
+    pub fn to_toml_string(&self) -> Result<String, toml::ser::Error> {
+        toml::to_string(&*self.0.lock().unwrap())
+    }
+
+    /// Copies the current values into a new `Config` that doesn't share state
+    /// with this one.
+    pub fn deep_clone(&self) -> Config {
+        Config(Arc::new(Mutex::new(self.0.lock().unwrap().clone())))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfigValues {
+    #[serde(skip)]
+    pub workspace_root: PathBuf,
+    /// Replaces the workspace root announced by the client, relative to the
+    /// directory of the config file, e.g. `apps/docs` in a monorepo. Other
+    /// paths are resolved against it.
+    #[serde(rename = "workspace_root")]
+    pub workspace_root_override: Option<PathBuf>,
+    pub partials_dirs: Vec<PathBuf>,
+    pub snippets: Vec<SnippetConfig>,
+    /// Parses slower than this are reported with a `$/progress` notification.
+    pub progress_threshold_ms: u64,
+    /// Extensions tried in order when a partial `src` has none, with or
+    /// without the leading dot. Also accepted as `implicit_extensions`.
+    #[serde(alias = "implicit_extensions")]
+    pub extension_fallback_chain: Vec<String>,
+    pub diagnostics_scope: DiagnosticsScope,
+    /// Directories skipped when scanning the workspace.
+    pub exclude_dirs: Vec<PathBuf>,
+    /// Word list (one word per line) used by the spell check code lens. Spell
+    /// checking is disabled when unset.
+    pub spell_check_wordlist: Option<PathBuf>,
+    /// Parser overrides for `.md` and `.mdx` files.
+    pub extensions: ExtensionsConfig,
+    /// Code blocks with more lines than this can be extracted to a partial.
+    pub extract_code_block_min_lines: u32,
+    /// Whether formatting removes trailing whitespace.
+    pub trim_trailing_whitespace: bool,
+    /// Whether go to definition follows package imports into `node_modules`.
+    pub resolve_node_modules_imports: bool,
+    /// Components that can be imported, mapped to their import path, e.g.
+    /// `Button = "@/components/Button"`.
+    pub component_map: BTreeMap<String, String>,
+    /// Prefixes of partial `src` values mapped to the directory they stand
+    /// for, relative to the workspace root, e.g. `"@partials" =
+    /// "src/partials"` for `@partials/hero.mdx`.
+    pub aliases: BTreeMap<String, PathBuf>,
+    /// Component that block quotes are converted to. Defaults to `Callout`,
+    /// but the conversion is only offered when this is set or `Callout` is in
+    /// `component_map`.
+    pub callout_component_name: Option<String>,
+    /// Number of lines of a partial shown when hovering over it.
+    pub partial_preview_lines: usize,
+    /// Whether to hint at JSX elements that have neither text nor an
+    /// `aria-label`.
+    pub lint_accessibility: bool,
+    /// Name of the element that includes a partial. Defaults to `$Partial`.
+    pub partial_element_name: Option<String>,
+    /// Elements named `$<namespace>:<name>` include partials too, e.g.
+    /// `<$Docs:Hero src="hero.mdx" />` for the `Docs` namespace.
+    pub partials_namespace: Option<String>,
+    /// How many levels of nested partials "Inline partial" replaces along with
+    /// the partial itself.
+    pub inline_partial_depth: usize,
+    /// Whether web links are checked with a `HEAD` request, warning about the
+    /// ones that respond with an error status.
+    pub check_external_links: bool,
+    /// Known props of components, offered as attribute completions, e.g.
+    /// `[prop_schemas."$Partial"]` with `fields = ["src", "id"]`.
+    pub prop_schemas: BTreeMap<String, PropSchema>,
+    /// How long scanning the partials directories may take before it's
+    /// given up on.
+    pub partial_scan_timeout_ms: u64,
+    /// The files of the partials directories, aliased ones included, as the
+    /// server last scanned them. When set, `find_matching_partial` looks them
+    /// up before checking the disk.
+    #[serde(skip)]
+    pub partial_files: Option<Arc<BTreeSet<PathBuf>>>,
+    /// Whether the workspace's documents are parsed in the background at
+    /// startup, so that cross-file features also see the ones that aren't
+    /// open.
+    pub index_workspace: bool,
+    /// How many documents are indexed between two progress reports.
+    pub index_progress_interval: usize,
+    /// The `prettier` binary that documents are formatted with, e.g. the
+    /// project's `node_modules/.bin/prettier`. Defaults to the one on the
+    /// `PATH`.
+    pub prettier_path: Option<PathBuf>,
+    /// Minimum level of the server's logs: `error`, `warn`, `info`, `debug`
+    /// or `trace`. Defaults to `info`. `RUST_LOG` directives still apply on
+    /// top.
+    pub log_level: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExtensionsConfig {
+    pub md: Option<ParserConfig>,
+    pub mdx: Option<ParserConfig>,
+}
+
+/// Markdown constructs to turn on or off. Unset fields keep the default from
+/// `get_parser_options`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParserConfig {
+    pub frontmatter: Option<bool>,
+    pub gfm_table: Option<bool>,
+    pub html_flow: Option<bool>,
+    pub html_text: Option<bool>,
+    pub mdx_esm: Option<bool>,
+    pub mdx_expression_flow: Option<bool>,
+    pub mdx_expression_text: Option<bool>,
+    pub mdx_jsx_flow: Option<bool>,
+    pub mdx_jsx_text: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticsScope {
+    /// Only documents open in the editor.
+    #[default]
+    OpenDocuments,
+    /// Every `.mdx` file under `workspace_root`, parsed on demand.
+    Workspace,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PropSchema {
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnippetConfig {
+    pub label: String,
+    pub detail: Option<String>,
+    pub snippet_text: String,
+}
+
+impl Default for ConfigValues {
+    fn default() -> Self {
+        Self {
+            workspace_root: PathBuf::new(),
+            workspace_root_override: None,
+            partials_dirs: Vec::new(),
+            snippets: default_snippets(),
+            progress_threshold_ms: 500,
+            extension_fallback_chain: vec![".mdx".to_string(), ".md".to_string()],
+            diagnostics_scope: DiagnosticsScope::default(),
+            exclude_dirs: vec![PathBuf::from("node_modules"), PathBuf::from(".git")],
+            spell_check_wordlist: None,
+            extensions: ExtensionsConfig::default(),
+            extract_code_block_min_lines: 20,
+            trim_trailing_whitespace: true,
+            resolve_node_modules_imports: false,
+            component_map: BTreeMap::new(),
+            aliases: BTreeMap::new(),
+            callout_component_name: None,
+            partial_preview_lines: 20,
+            lint_accessibility: false,
+            partial_element_name: None,
+            partials_namespace: None,
+            inline_partial_depth: 1,
+            check_external_links: false,
+            prop_schemas: BTreeMap::new(),
+            partial_scan_timeout_ms: 500,
+            partial_files: None,
+            index_workspace: true,
+            index_progress_interval: 50,
+            prettier_path: None,
+            log_level: None,
+        }
+    }
+}
+
+fn default_snippets() -> Vec<SnippetConfig> {
+    [
+        ("$Partial", "Include a partial", "<\\$Partial src=\"$1\" />"),
+        (
+            "Callout",
+            "Callout component",
+            "<Callout type=\"$1\">$2</Callout>",
+        ),
+        (
+            "CodeBlock",
+            "Code block component",
+            "<CodeBlock language=\"$1\">\n$2\n</CodeBlock>",
+        ),
+    ]
+    .into_iter()
+    .map(|(label, detail, snippet_text)| SnippetConfig {
+        label: label.to_string(),
+        detail: Some(detail.to_string()),
+        snippet_text: snippet_text.to_string(),
+    })
+    .collect()
+}
+
+/// Formats of the config file, in order of precedence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+}
+
+impl ConfigFormat {
+    pub const ALL: [ConfigFormat; 2] = [ConfigFormat::Toml, ConfigFormat::Json];
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => CONFIG_FILE_NAME,
+            ConfigFormat::Json => JSON_CONFIG_FILE_NAME,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    Json(serde_json::Error),
+    InitializationOptions(String),
+    Settings(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "failed to read the config file: {}", err),
+            ConfigError::Toml(err) => write!(f, "failed to parse {}: {}", CONFIG_FILE_NAME, err),
+            ConfigError::Json(err) => {
+                write!(f, "failed to parse {}: {}", JSON_CONFIG_FILE_NAME, err)
+            }
+            ConfigError::InitializationOptions(err) => write!(
+                f,
+                "invalid `{}` initialization options: {}",
+                INITIALIZATION_OPTIONS_KEY, err
+            ),
+            ConfigError::Settings(err) => {
+                write!(f, "invalid `{}` settings: {}", SETTINGS_SECTION, err)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.lock().unwrap())
+    }
+}
+
+impl fmt::Display for ConfigValues {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let home = std::env::var_os("HOME").map(PathBuf::from);
+        let display = |path: &Path| display_from_home(path, home.as_deref());
+
+        writeln!(f, "workspace_root: {}", display(&self.workspace_root))?;
+        writeln!(f, "partials_dirs:")?;
+        for dir in &self.partials_dirs {
+            writeln!(f, "  - {}", display(dir))?;
+        }
+        let snippets: Vec<_> = self.snippets.iter().map(|s| s.label.as_str()).collect();
+        write!(f, "snippets: {}", snippets.join(", "))
+    }
+}
+
+/// Shortens paths under the home directory to `~/...`.
+fn display_from_home(path: &Path, home: Option<&Path>) -> String {
+    match home.and_then(|home| path.strip_prefix(home).ok()) {
+        Some(relative) => Path::new("~").join(relative).display().to_string(),
+        None => path.display().to_string(),
+    }
+}
+
+impl ConfigValues {
+    /// Loads `.supermdx.toml`, or else `.supermdx.json`, from the workspace
+    /// root announced by the client, then applies the overrides found under
+    /// the `supermdx` key of `initializationOptions` and then the `supermdx`
+    /// section of the client's settings, when it was pulled. A missing config
+    /// file is not an error: the defaults are kept. Returns warnings to show
+    /// the user about settings that were accepted but look wrong.
+    pub fn update(
+        &mut self,
+        params: &InitializeParams,
+        settings: Option<&Value>,
+    ) -> Result<Vec<String>, ConfigError> {
+        let workspace_root = params
+            .root_uri
+            .as_ref()
+            .and_then(|uri| uri.to_file_path().ok());
+
+        let file = match &workspace_root {
+            Some(root) => read_config_file(root)?,
+            None => None,
+        };
+
+        let mut values = Self::load(
+            file.as_ref().map(|(text, format)| (text.as_str(), *format)),
+            params.initialization_options.as_ref(),
+            settings,
+        )?;
+        let mut warnings = Vec::new();
+        if let Some(root) = &workspace_root {
+            // The config file is read from the client's root, so the override
+            // is relative to it.
+            let root = match &values.workspace_root_override {
+                Some(dir) => normalize_path(&root.join(dir)),
+                None => root.clone(),
+            };
+            values.resolve_paths(&root);
+            warnings.extend(values.expand_partials_dirs());
+        }
+        warnings.extend(values.validate_log_level());
+        // Kept until the directories are scanned again, so that partials
+        // keep resolving meanwhile.
+        values.partial_files = self.partial_files.take();
+
+        *self = values;
+        Ok(warnings)
+    }
+
+    /// Builds the config from the config file contents, the client's
+    /// `initializationOptions` and its settings, in increasing precedence.
+    /// Overrides are merged key by key, so they only replace the settings they
+    /// mention.
+    fn load(
+        file: Option<(&str, ConfigFormat)>,
+        initialization_options: Option<&Value>,
+        settings: Option<&Value>,
+    ) -> Result<Self, ConfigError> {
+        let mut table: Table = match file {
+            Some((text, ConfigFormat::Toml)) => toml::from_str(text).map_err(ConfigError::Toml)?,
+            Some((text, ConfigFormat::Json)) => {
+                // Checked against `ConfigValues` first, so that mistakes are
+                // reported with JSON line numbers.
+                serde_json::from_str::<ConfigValues>(text).map_err(ConfigError::Json)?;
+                serde_json::from_str(text).map_err(ConfigError::Json)?
+            }
+            None => Table::new(),
+        };
+
+        let initialization_options =
+            initialization_options.and_then(|options| options.get(INITIALIZATION_OPTIONS_KEY));
+        if let Some(overrides) =
+            get_overrides(initialization_options, ConfigError::InitializationOptions)?
+        {
+            merge_tables(&mut table, overrides);
+        }
+        if let Some(overrides) = get_overrides(settings, ConfigError::Settings)? {
+            merge_tables(&mut table, overrides);
+        }
+
+        // The overrides were validated on their own, so what's left to fail
+        // comes from the file.
+        toml::Value::Table(table)
+            .try_into()
+            .map_err(ConfigError::Toml)
+    }
+
+    fn resolve_paths(&mut self, workspace_root: &Path) {
+        self.workspace_root = workspace_root.to_path_buf();
+        self.partials_dirs = self
+            .partials_dirs
+            .iter()
+            .map(|dir| workspace_root.join(dir))
+            .collect();
+        self.exclude_dirs = self
+            .exclude_dirs
+            .iter()
+            .map(|dir| workspace_root.join(dir))
+            .collect();
+        for dir in self.aliases.values_mut() {
+            *dir = workspace_root.join(&dir);
+        }
+        self.spell_check_wordlist = self
+            .spell_check_wordlist
+            .as_ref()
+            .map(|path| workspace_root.join(path));
+        self.prettier_path = self
+            .prettier_path
+            .as_ref()
+            .map(|path| workspace_root.join(path));
+    }
+
+    pub fn get_partial_element_name(&self) -> &str {
+        self.partial_element_name.as_deref().unwrap_or(PARTIAL)
+    }
+
+    /// Unsets a `log_level` that isn't a level, so that the default is used,
+    /// and returns a warning about it.
+    fn validate_log_level(&mut self) -> Option<String> {
+        let level = self.log_level.as_deref()?;
+        if level.parse::<LevelFilter>().is_ok() {
+            return None;
+        }
+
+        let warning = format!("Invalid log_level '{}', using info instead", level);
+        warn!("{}", warning);
+        self.log_level = None;
+        Some(warning)
+    }
+
+    /// Replaces the `partials_dirs` that are glob patterns, like
+    /// `packages/*/components`, with the directories they match. Returns a
+    /// warning for each pattern that matches none.
+    fn expand_partials_dirs(&mut self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let mut partials_dirs = Vec::new();
+
+        for dir in &self.partials_dirs {
+            let pattern = dir.to_string_lossy();
+            if !pattern.contains(['*', '?', '[']) {
+                partials_dirs.push(dir.clone());
+                continue;
+            }
+
+            let matches: Vec<PathBuf> = match glob::glob(&pattern) {
+                Ok(paths) => paths
+                    .filter_map(Result::ok)
+                    .filter(|path| path.is_dir())
+                    .collect(),
+                Err(err) => {
+                    warnings.push(format!(
+                        "Invalid partials_dirs pattern '{}': {}",
+                        pattern, err
+                    ));
+                    continue;
+                }
+            };
+            if matches.is_empty() {
+                warnings.push(format!(
+                    "partials_dirs pattern '{}' matches no directories",
+                    pattern
+                ));
+            }
+            for path in matches {
+                if !partials_dirs.contains(&path) {
+                    partials_dirs.push(path);
+                }
+            }
+        }
+
+        self.partials_dirs = partials_dirs;
+        warnings
+    }
+}
+
+/// Reads the first config file found in `workspace_root`, warning when a
+/// file of another format is shadowed by it.
+fn read_config_file(workspace_root: &Path) -> Result<Option<(String, ConfigFormat)>, ConfigError> {
+    let mut found: Option<(String, ConfigFormat)> = None;
+
+    for format in ConfigFormat::ALL {
+        let path = workspace_root.join(format.file_name());
+        if let Some((_, used)) = &found {
+            if path.is_file() {
+                warn!(
+                    "Both {} and {} exist, ignoring {}",
+                    used.file_name(),
+                    format.file_name(),
+                    format.file_name()
+                );
+            }
+            continue;
+        }
+
+        match fs::read_to_string(&path) {
+            Ok(text) => found = Some((text, format)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(ConfigError::Io(err)),
+        }
+    }
+
+    Ok(found)
+}
+
+/// Converts client overrides to a table, validating them on their own so that
+/// mistakes are reported against where they came from rather than the config
+/// file.
+fn get_overrides(
+    overrides: Option<&Value>,
+    to_error: fn(String) -> ConfigError,
+) -> Result<Option<Table>, ConfigError> {
+    let Some(overrides) = overrides.filter(|overrides| !overrides.is_null()) else {
+        return Ok(None);
+    };
+
+    let table =
+        Table::try_from(without_nulls(overrides)).map_err(|err| to_error(err.to_string()))?;
+    ConfigValues::deserialize(toml::Value::Table(table.clone()))
+        .map_err(|err| to_error(err.to_string()))?;
+
+    Ok(Some(table))
+}
+
+/// Drops the `null` members of client overrides, which TOML can't hold.
+/// Clients send them for settings the user left unset, so they keep the
+/// config file's value.
+fn without_nulls(value: &Value) -> Value {
+    match value {
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key.clone(), without_nulls(value)))
+                .collect(),
+        ),
+        Value::Array(array) => Value::Array(
+            array
+                .iter()
+                .filter(|value| !value.is_null())
+                .map(without_nulls)
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+fn merge_tables(base: &mut Table, overrides: Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overrides)) => {
+                merge_tables(base, overrides)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::Url;
+
+    use super::*;
+
+    fn parse(text: &str) -> Result<ConfigValues, ConfigError> {
+        ConfigValues::load(Some((text, ConfigFormat::Toml)), None, None)
+    }
+
+    fn parse_json(text: &str) -> Result<ConfigValues, ConfigError> {
+        ConfigValues::load(Some((text, ConfigFormat::Json)), None, None)
+    }
+
+    #[test]
+    fn test_workspace_root_override() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(
+            root.path().join(CONFIG_FILE_NAME),
+            "workspace_root = \"apps/docs\"\npartials_dirs = [\"partials\"]",
+        )
+        .unwrap();
+        let params = InitializeParams {
+            root_uri: Url::from_file_path(root.path()).ok(),
+            ..Default::default()
+        };
+
+        let mut values = ConfigValues::default();
+        values.update(&params, None).unwrap();
+
+        let docs_root = root.path().join("apps/docs");
+        assert_eq!(values.workspace_root, docs_root);
+        assert_eq!(values.partials_dirs, vec![docs_root.join("partials")]);
+
+        // Without the override, paths are resolved against the client's root.
+        fs::write(
+            root.path().join(CONFIG_FILE_NAME),
+            "partials_dirs = [\"partials\"]",
+        )
+        .unwrap();
+        values.update(&params, None).unwrap();
+        assert_eq!(values.workspace_root, root.path());
+        assert_eq!(values.partials_dirs, vec![root.path().join("partials")]);
+    }
+
+    #[test]
+    fn test_parse_implicit_extensions() {
+        assert_eq!(
+            parse("implicit_extensions = [\"md\", \"mdx\"]")
+                .unwrap()
+                .extension_fallback_chain,
+            vec!["md", "mdx"]
+        );
+    }
+
+    #[test]
+    fn test_parse_aliases() {
+        let mut values = parse(
+            r#"
+[aliases]
+"@partials" = "src/partials"
+"#,
+        )
+        .unwrap();
+        values.resolve_paths(Path::new("/workspace"));
+
+        assert_eq!(
+            values.aliases.get("@partials"),
+            Some(&PathBuf::from("/workspace/src/partials"))
+        );
+    }
+
+    #[test]
+    fn test_parse_partials_dirs() {
+        let mut values = parse(
+            r#"
+partials_dirs = ["partials", "shared/snippets"]
+"#,
+        )
+        .unwrap();
+        values.resolve_paths(Path::new("/workspace"));
+
+        assert_eq!(values.workspace_root, PathBuf::from("/workspace"));
+        assert_eq!(
+            values.partials_dirs,
+            vec![
+                PathBuf::from("/workspace/partials"),
+                PathBuf::from("/workspace/shared/snippets")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_empty() {
+        let values = parse("").unwrap();
+        assert!(values.partials_dirs.is_empty());
+        assert_eq!(values.snippets, default_snippets());
+    }
+
+    #[test]
+    fn test_parse_progress_threshold() {
+        assert_eq!(parse("").unwrap().progress_threshold_ms, 500);
+        assert_eq!(
+            parse("progress_threshold_ms = 50")
+                .unwrap()
+                .progress_threshold_ms,
+            50
+        );
+    }
+
+    #[test]
+    fn test_parse_diagnostics_scope() {
+        let mut values = parse("").unwrap();
+        values.resolve_paths(Path::new("/workspace"));
+        assert_eq!(values.diagnostics_scope, DiagnosticsScope::OpenDocuments);
+        assert_eq!(
+            values.exclude_dirs,
+            vec![
+                PathBuf::from("/workspace/node_modules"),
+                PathBuf::from("/workspace/.git")
+            ]
+        );
+
+        let values = parse(
+            r#"
+diagnostics_scope = "workspace"
+exclude_dirs = ["build"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(values.diagnostics_scope, DiagnosticsScope::Workspace);
+        assert_eq!(values.exclude_dirs, vec![PathBuf::from("build")]);
+    }
+
+    #[test]
+    fn test_parse_spell_check_wordlist() {
+        let mut values = parse("").unwrap();
+        values.resolve_paths(Path::new("/workspace"));
+        assert_eq!(values.spell_check_wordlist, None);
+
+        let mut values = parse(r#"spell_check_wordlist = "words.txt""#).unwrap();
+        values.resolve_paths(Path::new("/workspace"));
+        assert_eq!(
+            values.spell_check_wordlist,
+            Some(PathBuf::from("/workspace/words.txt"))
+        );
+    }
+
+    #[test]
+    fn test_parse_prettier_path() {
+        let mut values = parse(r#"prettier_path = "node_modules/.bin/prettier""#).unwrap();
+        values.resolve_paths(Path::new("/workspace"));
+        assert_eq!(
+            values.prettier_path,
+            Some(PathBuf::from("/workspace/node_modules/.bin/prettier"))
+        );
+
+        let mut values = parse(r#"prettier_path = "/usr/bin/prettier""#).unwrap();
+        values.resolve_paths(Path::new("/workspace"));
+        assert_eq!(
+            values.prettier_path,
+            Some(PathBuf::from("/usr/bin/prettier"))
+        );
+    }
+
+    #[test]
+    fn test_parse_log_level() {
+        let mut values = parse(r#"log_level = "debug""#).unwrap();
+        assert_eq!(values.validate_log_level(), None);
+        assert_eq!(values.log_level.as_deref(), Some("debug"));
+
+        let mut values = parse(r#"log_level = "verbose""#).unwrap();
+        assert_eq!(
+            values.validate_log_level(),
+            Some("Invalid log_level 'verbose', using info instead".to_string())
+        );
+        assert_eq!(values.log_level, None);
+    }
+
+    #[test]
+    fn test_parse_extensions() {
+        let values = parse(
+            r#"
+[extensions.md]
+mdx_jsx_flow = false
+mdx_jsx_text = false
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            values.extensions,
+            ExtensionsConfig {
+                md: Some(ParserConfig {
+                    mdx_jsx_flow: Some(false),
+                    mdx_jsx_text: Some(false),
+                    ..Default::default()
+                }),
+                mdx: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_component_map() {
+        let values = parse(
+            r#"
+[component_map]
+Button = "@/components/Button"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            values.component_map.get("Button").map(String::as_str),
+            Some("@/components/Button")
+        );
+    }
+
+    #[test]
+    fn test_parse_prop_schemas() {
+        let values = parse(
+            r#"
+[prop_schemas."$Partial"]
+fields = ["src", "id", "class"]
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            values.prop_schemas.get("$Partial"),
+            Some(&PropSchema {
+                fields: vec!["src".to_string(), "id".to_string(), "class".to_string()]
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_snippets() {
+        let values = parse(
+            r#"
+[[snippets]]
+label = "Note"
+snippet_text = "<Note>$1</Note>"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            values.snippets,
+            vec![SnippetConfig {
+                label: "Note".to_string(),
+                detail: None,
+                snippet_text: "<Note>$1</Note>".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_display_from_home() {
+        let home = Path::new("/home/user");
+
+        assert_eq!(
+            display_from_home(Path::new("/home/user/docs/partials"), Some(home)),
+            "~/docs/partials"
+        );
+        assert_eq!(
+            display_from_home(Path::new("/srv/docs"), Some(home)),
+            "/srv/docs"
+        );
+        assert_eq!(display_from_home(Path::new("/srv/docs"), None), "/srv/docs");
+    }
+
+    #[test]
+    fn test_display_config_values() {
+        let mut values = parse(
+            r#"
+partials_dirs = ["partials", "shared"]
+"#,
+        )
+        .unwrap();
+        values.resolve_paths(Path::new("/workspace"));
+
+        assert_eq!(
+            values.to_string(),
+            "workspace_root: /workspace\npartials_dirs:\n  - /workspace/partials\n  - /workspace/shared\nsnippets: $Partial, Callout, CodeBlock"
+        );
+    }
+
+    #[test]
+    fn test_initialization_options_override_file() {
+        let options = serde_json::json!({
+            "supermdx": {
+                "partials_dirs": ["overridden"],
+                "progress_threshold_ms": 100,
+            }
+        });
+        let values = ConfigValues::load(
+            Some((
+                r#"
+partials_dirs = ["partials"]
+diagnostics_scope = "workspace"
+"#,
+                ConfigFormat::Toml,
+            )),
+            Some(&options),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(values.partials_dirs, vec![PathBuf::from("overridden")]);
+        assert_eq!(values.progress_threshold_ms, 100);
+        // Settings the overrides don't mention come from the file.
+        assert_eq!(values.diagnostics_scope, DiagnosticsScope::Workspace);
+    }
+
+    #[test]
+    fn test_initialization_options_without_file() {
+        let options = serde_json::json!({
+            "supermdx": {
+                "snippets": [{ "label": "Note", "snippet_text": "<Note>$1</Note>" }],
+            }
+        });
+        let values = ConfigValues::load(None, Some(&options), None).unwrap();
+
+        assert_eq!(values.snippets.len(), 1);
+        assert_eq!(values.snippets[0].label, "Note");
+        assert_eq!(values.progress_threshold_ms, 500);
+    }
+
+    #[test]
+    fn test_initialization_options_ignored() {
+        for options in [
+            None,
+            Some(serde_json::json!(null)),
+            Some(serde_json::json!({ "other": { "partials_dirs": ["x"] } })),
+            Some(serde_json::json!({ "supermdx": null })),
+        ] {
+            let values = ConfigValues::load(
+                Some(("partials_dirs = [\"partials\"]", ConfigFormat::Toml)),
+                options.as_ref(),
+                None,
+            )
+            .unwrap();
+            assert_eq!(values.partials_dirs, vec![PathBuf::from("partials")]);
+        }
+    }
+
+    #[test]
+    fn test_initialization_options_invalid() {
+        for options in [
+            serde_json::json!({ "supermdx": "partials" }),
+            serde_json::json!({ "supermdx": { "partials_dirs": 1 } }),
+        ] {
+            assert!(matches!(
+                ConfigValues::load(None, Some(&options), None),
+                Err(ConfigError::InitializationOptions(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_settings_override_initialization_options() {
+        let options = serde_json::json!({
+            "supermdx": { "partials_dirs": ["options"], "progress_threshold_ms": 100 }
+        });
+        let settings = serde_json::json!({ "partials_dirs": ["settings"] });
+        let values = ConfigValues::load(
+            Some(("partials_dirs = [\"partials\"]", ConfigFormat::Toml)),
+            Some(&options),
+            Some(&settings),
+        )
+        .unwrap();
+
+        assert_eq!(values.partials_dirs, vec![PathBuf::from("settings")]);
+        assert_eq!(values.progress_threshold_ms, 100);
+
+        assert!(matches!(
+            ConfigValues::load(None, None, Some(&serde_json::json!({ "partials_dirs": 1 }))),
+            Err(ConfigError::Settings(_))
+        ));
+    }
+
+    #[test]
+    fn test_settings_with_null_members() {
+        let settings = serde_json::json!({
+            "partials_dirs": ["settings"],
+            "prettier_path": null,
+            "callout_component_name": null,
+            "component_map": { "Button": null },
+        });
+        let values = ConfigValues::load(
+            Some(("callout_component_name = \"Note\"", ConfigFormat::Toml)),
+            None,
+            Some(&settings),
+        )
+        .unwrap();
+
+        assert_eq!(values.partials_dirs, vec![PathBuf::from("settings")]);
+        assert_eq!(values.prettier_path, None);
+        assert_eq!(values.callout_component_name.as_deref(), Some("Note"));
+        assert!(values.component_map.is_empty());
+    }
+
+    #[test]
+    fn test_to_toml_string_roundtrip() {
+        let mut values = parse(
+            r#"
+partials_dirs = ["partials"]
+diagnostics_scope = "workspace"
+trim_trailing_whitespace = false
+
+[extensions.md]
+mdx_jsx_flow = false
+
+[component_map]
+Button = "@/components/Button"
+"#,
+        )
+        .unwrap();
+        values.resolve_paths(Path::new("/workspace"));
+        let config = Config(Arc::new(Mutex::new(values)));
+
+        let text = config.to_toml_string().unwrap();
+        let reread = parse(&text).unwrap();
+
+        assert_eq!(
+            reread.partials_dirs,
+            vec![PathBuf::from("/workspace/partials")]
+        );
+        assert_eq!(reread.diagnostics_scope, DiagnosticsScope::Workspace);
+        assert!(!reread.trim_trailing_whitespace);
+        assert_eq!(
+            reread.extensions.md.as_ref().and_then(|md| md.mdx_jsx_flow),
+            Some(false)
+        );
+        assert_eq!(
+            Config(Arc::new(Mutex::new(reread)))
+                .to_toml_string()
+                .unwrap(),
+            text
+        );
+    }
+
+    #[test]
+    fn test_config_clone() {
+        let config = Config::default();
+        let shallow = config.clone();
+        let deep = config.deep_clone();
+
+        config.0.lock().unwrap().progress_threshold_ms = 1;
+
+        assert_eq!(shallow.0.lock().unwrap().progress_threshold_ms, 1);
+        assert_ne!(deep.0.lock().unwrap().progress_threshold_ms, 1);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(parse("partials_dirs = 1").is_err());
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let values = parse_json(
+            r#"{
+    "partials_dirs": ["partials"],
+    "progress_threshold_ms": 50,
+    "diagnostics_scope": "workspace",
+    "component_map": { "Button": "@/components/Button" }
+}"#,
+        )
+        .unwrap();
+
+        assert_eq!(values.partials_dirs, vec![PathBuf::from("partials")]);
+        assert_eq!(values.progress_threshold_ms, 50);
+        assert_eq!(values.diagnostics_scope, DiagnosticsScope::Workspace);
+        assert_eq!(
+            values.component_map.get("Button").map(String::as_str),
+            Some("@/components/Button")
+        );
+        assert_eq!(values.snippets, default_snippets());
+    }
+
+    #[test]
+    fn test_parse_json_empty() {
+        let values = parse_json("{}").unwrap();
+        assert!(values.partials_dirs.is_empty());
+        assert_eq!(values.progress_threshold_ms, 500);
+    }
+
+    #[test]
+    fn test_parse_json_invalid() {
+        assert!(matches!(
+            parse_json(r#"{ "partials_dirs": 1 }"#),
+            Err(ConfigError::Json(_))
+        ));
+        assert!(matches!(parse_json("{"), Err(ConfigError::Json(_))));
+    }
+
+    #[test]
+    fn test_initialization_options_override_json_file() {
+        let options = serde_json::json!({ "supermdx": { "partials_dirs": ["overridden"] } });
+        let values = ConfigValues::load(
+            Some((
+                r#"{ "partials_dirs": ["partials"], "diagnostics_scope": "workspace" }"#,
+                ConfigFormat::Json,
+            )),
+            Some(&options),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(values.partials_dirs, vec![PathBuf::from("overridden")]);
+        assert_eq!(values.diagnostics_scope, DiagnosticsScope::Workspace);
+    }
+
+    #[test]
+    fn test_expand_partials_dirs() {
+        let root = tempfile::tempdir().unwrap();
+        for dir in ["packages/a/components", "packages/b/components", "shared"] {
+            fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        fs::create_dir_all(root.path().join("packages/c")).unwrap();
+        fs::write(root.path().join("packages/components"), "").unwrap();
+
+        let mut values = parse(
+            r#"partials_dirs = ["packages/*/components", "shared", "missing", "apps/*/partials"]"#,
+        )
+        .unwrap();
+        values.resolve_paths(root.path());
+        let warnings = values.expand_partials_dirs();
+
+        assert_eq!(
+            values.partials_dirs,
+            vec![
+                root.path().join("packages/a/components"),
+                root.path().join("packages/b/components"),
+                root.path().join("shared"),
+                root.path().join("missing"),
+            ]
+        );
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("apps/*/partials"));
+    }
+
+    #[test]
+    fn test_read_config_file() {
+        let root = tempfile::tempdir().unwrap();
+        assert!(read_config_file(root.path()).unwrap().is_none());
+
+        fs::write(root.path().join(JSON_CONFIG_FILE_NAME), "{}").unwrap();
+        let (_, format) = read_config_file(root.path()).unwrap().unwrap();
+        assert_eq!(format, ConfigFormat::Json);
+
+        fs::write(root.path().join(CONFIG_FILE_NAME), "").unwrap();
+        let (_, format) = read_config_file(root.path()).unwrap().unwrap();
+        assert_eq!(format, ConfigFormat::Toml);
+    }
+}
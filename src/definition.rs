@@ -0,0 +1,344 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use markdown::mdast::Node;
+use serde_json::Value;
+use tower_lsp::lsp_types::{GotoDefinitionResponse, Location, Position, Range, Url};
+
+use crate::{
+    ast::{find_deepest_match, get_ancestor_chain},
+    config::ConfigValues,
+    encoding::offset_to_position,
+    nodes::{is_configured_partial, partials::find_matching_partial, NodeExt},
+    workspace::normalize_path,
+};
+
+/// Extensions tried in order for a relative import without one.
+const IMPORT_EXTENSIONS: [&str; 4] = ["js", "ts", "tsx", "mdx"];
+
+/// Resolves the partial under the cursor to its file. Otherwise, resolves the
+/// import of the ESM statement under the cursor: relative imports against the
+/// document, and packages to their entry file in `node_modules`, which is only
+/// enabled with `resolve_node_modules_imports`.
+pub fn get_definition(
+    ast: &Node,
+    text: &str,
+    uri: &Url,
+    position: &Position,
+    config: &ConfigValues,
+) -> Option<GotoDefinitionResponse> {
+    let ancestor_chain = get_ancestor_chain(ast, text, position);
+    if let Some(partial) =
+        find_deepest_match(&ancestor_chain, |node| is_configured_partial(node, config))
+    {
+        let path = find_matching_partial(partial.get_attribute("src")?, config)?;
+        return Some(GotoDefinitionResponse::Scalar(Location::new(
+            Url::from_file_path(path).ok()?,
+            Range::default(),
+        )));
+    }
+
+    let (source, _) = get_import_at(ast, text, position)?;
+    let path = resolve_import(&source, uri, config)?;
+    Some(GotoDefinitionResponse::Scalar(Location::new(
+        Url::from_file_path(path).ok()?,
+        Range::default(),
+    )))
+}
+
+/// Returns the module specifier of the import statement under the cursor,
+/// along with the range of the specifier without its quotes.
+pub fn get_import_at(ast: &Node, text: &str, position: &Position) -> Option<(String, Range)> {
+    let ancestor_chain = get_ancestor_chain(ast, text, position);
+    let Some(Node::MdxjsEsm(esm)) = ancestor_chain.last().copied() else {
+        return None;
+    };
+    let start = &esm.position.as_ref()?.start;
+    let line = (position.line as usize).checked_sub(start.line - 1)?;
+
+    let source = get_import_source(&esm.value, line)?;
+    // The specifier ends the statement, so it's on the cursor's line or below.
+    let mut line_offset = 0;
+    let offset = esm
+        .value
+        .split_inclusive('\n')
+        .enumerate()
+        .find_map(|(index, esm_line)| {
+            let found = (index >= line)
+                .then(|| {
+                    ['"', '\'']
+                        .iter()
+                        .find_map(|quote| esm_line.find(&format!("{}{}{}", quote, source, quote)))
+                })
+                .flatten()
+                .map(|column| start.offset + line_offset + column + 1);
+            line_offset += esm_line.len();
+            found
+        })?;
+
+    let range = Range::new(
+        offset_to_position(text, offset),
+        offset_to_position(text, offset + source.len()),
+    );
+    Some((source, range))
+}
+
+/// Resolves a module specifier to a file: relative ones against the document,
+/// and packages to their entry file in `node_modules` when
+/// `resolve_node_modules_imports` is on.
+pub fn resolve_import(source: &str, uri: &Url, config: &ConfigValues) -> Option<PathBuf> {
+    if source.starts_with('.') || source.starts_with('/') {
+        let document_dir = uri.to_file_path().ok()?.parent()?.to_path_buf();
+        return resolve_relative_import(&document_dir, source);
+    }
+
+    if !config.resolve_node_modules_imports {
+        return None;
+    }
+
+    resolve_package(&config.workspace_root.join("node_modules"), source)
+}
+
+/// Returns the module specifier of the import statement spanning `line` of an
+/// ESM block, e.g. `@acme/components` for `import { Button } from
+/// "@acme/components"`.
+fn get_import_source(esm: &str, line: usize) -> Option<String> {
+    let lines: Vec<&str> = esm.lines().collect();
+    let is_statement_start =
+        |line: &&str| line.starts_with("import ") || line.starts_with("export ");
+
+    let start = lines[..=line.min(lines.len().checked_sub(1)?)]
+        .iter()
+        .rposition(is_statement_start)?;
+    let end = lines[start + 1..]
+        .iter()
+        .position(is_statement_start)
+        .map_or(lines.len(), |offset| start + 1 + offset);
+    if line >= end || !lines[start].starts_with("import ") {
+        return None;
+    }
+
+    let statement = lines[start..end].join("\n");
+    let statement = statement.trim_end().trim_end_matches(';');
+    let quote = statement
+        .chars()
+        .last()
+        .filter(|ch| matches!(ch, '"' | '\''))?;
+    let open = statement[..statement.len() - 1].rfind(quote)?;
+    let before = statement[..open].trim_end();
+    if !before.ends_with(" from") && !before.ends_with("}from") && before != "import" {
+        return None;
+    }
+
+    Some(statement[open + 1..statement.len() - 1].to_string())
+}
+
+/// Finds the file a relative specifier points at from `document_dir`, trying
+/// `IMPORT_EXTENSIONS` in order when it has no extension.
+fn resolve_relative_import(document_dir: &Path, source: &str) -> Option<PathBuf> {
+    let path = normalize_path(&document_dir.join(source));
+    if path.extension().is_some() {
+        return path.is_file().then_some(path);
+    }
+
+    IMPORT_EXTENSIONS
+        .iter()
+        .map(|ext| path.with_extension(ext))
+        .find(|path| path.is_file())
+}
+
+/// Finds the file a bare specifier points at: a subpath inside the package, or
+/// its `module` or `main` entry from `package.json`.
+fn resolve_package(node_modules: &Path, source: &str) -> Option<PathBuf> {
+    let segments = if source.starts_with('@') { 2 } else { 1 };
+    let mut parts = source.splitn(segments + 1, '/');
+    let name: Vec<&str> = parts.by_ref().take(segments).collect();
+    let package_dir = node_modules.join(name.join("/"));
+    if !package_dir.is_dir() {
+        return None;
+    }
+
+    if let Some(subpath) = parts.next() {
+        let path = package_dir.join(subpath);
+        return path.is_file().then_some(path);
+    }
+
+    let manifest: Option<Value> = fs::read_to_string(package_dir.join("package.json"))
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok());
+    let entry = manifest
+        .as_ref()
+        .and_then(|manifest| {
+            ["module", "main"]
+                .iter()
+                .find_map(|field| manifest.get(field)?.as_str())
+        })
+        .unwrap_or("index.js");
+
+    let path = package_dir.join(entry);
+    path.is_file().then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use log::debug;
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    fn get_definition_path(
+        text: &str,
+        position: Position,
+        config: &ConfigValues,
+    ) -> Option<PathBuf> {
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let uri = Url::parse("file:///docs/index.mdx").unwrap();
+        match get_definition(&ast, text, &uri, &position, config)? {
+            GotoDefinitionResponse::Scalar(location) => location.uri.to_file_path().ok(),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_get_import_source() {
+        let esm = "import { Button } from \"@acme/components\";\nimport {\n  Card,\n} from 'ui/card'\nexport const meta = {}";
+
+        assert_eq!(
+            get_import_source(esm, 0),
+            Some("@acme/components".to_string())
+        );
+        assert_eq!(get_import_source(esm, 2), Some("ui/card".to_string()));
+        assert_eq!(get_import_source(esm, 4), None);
+    }
+
+    #[test]
+    fn test_definition_node_modules() {
+        let workspace = tempfile::tempdir().unwrap();
+        let package_dir = workspace.path().join("node_modules/@acme/components");
+        fs::create_dir_all(package_dir.join("dist")).unwrap();
+        fs::write(
+            package_dir.join("package.json"),
+            r#"{ "main": "dist/index.js", "module": "dist/index.mjs" }"#,
+        )
+        .unwrap();
+        fs::write(package_dir.join("dist/index.mjs"), "").unwrap();
+
+        let text = "import { Button } from \"@acme/components\"\nimport { Local } from \"./local\"\n\n<Button />";
+        let position = Position {
+            line: 0,
+            character: 10,
+        };
+        let mut config = ConfigValues {
+            workspace_root: workspace.path().to_path_buf(),
+            resolve_node_modules_imports: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            get_definition_path(text, position, &config),
+            Some(package_dir.join("dist/index.mjs"))
+        );
+        assert_eq!(
+            get_definition_path(
+                text,
+                Position {
+                    line: 1,
+                    character: 10,
+                },
+                &config
+            ),
+            None
+        );
+
+        config.resolve_node_modules_imports = false;
+        assert_eq!(get_definition_path(text, position, &config), None);
+    }
+
+    #[test]
+    fn test_definition_without_node_modules() {
+        let workspace = tempfile::tempdir().unwrap();
+        let config = ConfigValues {
+            workspace_root: workspace.path().to_path_buf(),
+            resolve_node_modules_imports: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            get_definition_path(
+                "import { Button } from \"@acme/components\"",
+                Position {
+                    line: 0,
+                    character: 10,
+                },
+                &config
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_definition_partial() {
+        let partials_dir = tempfile::tempdir().unwrap();
+        fs::write(partials_dir.path().join("hero.mdx"), "# Hero").unwrap();
+        let config = ConfigValues {
+            partials_dirs: vec![partials_dir.path().to_path_buf()],
+            partials_namespace: Some("Docs".to_string()),
+            ..Default::default()
+        };
+        let text = "<$Partial src=\"hero.mdx\" />\n\n<$Docs:Hero src=\"hero\" />\n\n<$Partial src=\"missing.mdx\" />";
+
+        for line in [0, 2] {
+            assert_eq!(
+                get_definition_path(text, Position { line, character: 3 }, &config),
+                Some(partials_dir.path().join("hero.mdx"))
+            );
+        }
+        assert_eq!(
+            get_definition_path(
+                text,
+                Position {
+                    line: 4,
+                    character: 3,
+                },
+                &config
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_definition_relative_import() {
+        let workspace = tempfile::tempdir().unwrap();
+        fs::create_dir_all(workspace.path().join("components")).unwrap();
+        fs::write(workspace.path().join("components/Card.tsx"), "").unwrap();
+        fs::write(workspace.path().join("components/Card.js"), "").unwrap();
+        fs::write(workspace.path().join("intro.mdx"), "").unwrap();
+
+        let text = "import Card from './components/Card'\nimport Intro from \"./intro.mdx\"\nimport Missing from './missing'";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+        let uri = Url::from_file_path(workspace.path().join("index.mdx")).unwrap();
+
+        let definition_path = |line| match get_definition(
+            &ast,
+            text,
+            &uri,
+            &Position { line, character: 8 },
+            &ConfigValues::default(),
+        ) {
+            Some(GotoDefinitionResponse::Scalar(location)) => location.uri.to_file_path().ok(),
+            _ => None,
+        };
+        assert_eq!(
+            definition_path(0),
+            Some(workspace.path().join("components/Card.js"))
+        );
+        assert_eq!(definition_path(1), Some(workspace.path().join("intro.mdx")));
+        assert_eq!(definition_path(2), None);
+    }
+}
@@ -0,0 +1,663 @@
+use std::{collections::HashSet, time::Duration};
+
+use dashmap::DashMap;
+use futures::future::join_all;
+use markdown::{mdast::Node, to_mdast};
+use tokio::fs;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, NumberOrString, Range, Url};
+
+use crate::{
+    ast::find_all_matches,
+    config::{ConfigValues, DiagnosticsScope},
+    encoding::get_node_range,
+    links::resolve_link,
+    nodes::{
+        esm::get_imported_names, is_configured_partial, partials::find_matching_partial, NodeExt,
+    },
+    parser::get_parser_options,
+    workspace::{find_files, has_extension},
+};
+
+pub const DIAGNOSTIC_SOURCE: &str = "supermdx";
+/// Code of the warning for components from `component_map` used without an
+/// import.
+pub const COMPONENT_NOT_IMPORTED: &str = "component-not-imported";
+/// Code of the hint for elements without text or an `aria-label`.
+pub const MISSING_ARIA_LABEL: &str = "missing-aria-label";
+/// Code of the error for partials without a `src` attribute.
+pub const MISSING_PARTIAL_SRC: &str = "missing-partial-src";
+/// Code of the error for partials whose `src` is empty.
+pub const EMPTY_PARTIAL_SRC: &str = "empty-partial-src";
+/// Source of the dead link warnings, separate so that editors can turn them
+/// off on their own.
+pub const LINK_DIAGNOSTIC_SOURCE: &str = "supermdx-links";
+/// How long a `HEAD` request for an external link may take.
+const EXTERNAL_LINK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Computes the diagnostics of a document, with `text` being what `ast` was
+/// parsed from.
+pub fn get_diagnostics(
+    ast: &Node,
+    text: &str,
+    uri: &Url,
+    config: &ConfigValues,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = get_partial_diagnostics(ast, text, config);
+    diagnostics.extend(get_component_import_diagnostics(ast, text, config));
+    diagnostics.extend(get_broken_link_diagnostics(ast, text, uri));
+    if config.lint_accessibility {
+        diagnostics.extend(get_aria_label_diagnostics(ast, text, config));
+    }
+    diagnostics
+}
+
+fn get_partial_diagnostics(ast: &Node, text: &str, config: &ConfigValues) -> Vec<Diagnostic> {
+    find_all_matches(ast, |node| is_configured_partial(node, config))
+        .into_iter()
+        .filter_map(|partial| {
+            let name = partial.get_name()?;
+            let (code, message) = if !partial.get_all_attributes().contains(&"src") {
+                (
+                    Some(MISSING_PARTIAL_SRC),
+                    format!("{} element is missing required 'src' attribute", name),
+                )
+            } else {
+                // A `src` expression can't be resolved statically.
+                let src = partial.get_attribute("src")?;
+                if src.is_empty() {
+                    (
+                        Some(EMPTY_PARTIAL_SRC),
+                        format!("{} element has an empty 'src' attribute", name),
+                    )
+                } else if find_matching_partial(src, config).is_none() {
+                    (
+                        None,
+                        format!("Partial '{}' not found in any partials_dir", src),
+                    )
+                } else {
+                    return None;
+                }
+            };
+
+            Some(Diagnostic {
+                range: get_node_range(text, partial)?,
+                severity: Some(DiagnosticSeverity::ERROR),
+                code: code.map(|code| NumberOrString::String(code.to_string())),
+                source: Some(DIAGNOSTIC_SOURCE.to_string()),
+                message,
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Returns the components used in the document that are in `component_map` but
+/// not imported by any of its ESM blocks.
+pub fn get_unimported_components<'a>(ast: &'a Node, config: &ConfigValues) -> Vec<&'a Node> {
+    if config.component_map.is_empty() {
+        return Vec::new();
+    }
+
+    let imported = get_imported_names(ast);
+    find_all_matches(ast, |node| {
+        node.get_name().is_some_and(|name| {
+            config.component_map.contains_key(name) && !imported.iter().any(|i| i == name)
+        })
+    })
+}
+
+fn get_component_import_diagnostics(
+    ast: &Node,
+    text: &str,
+    config: &ConfigValues,
+) -> Vec<Diagnostic> {
+    get_unimported_components(ast, config)
+        .into_iter()
+        .filter_map(|element| {
+            Some(Diagnostic {
+                range: get_node_range(text, element)?,
+                severity: Some(DiagnosticSeverity::WARNING),
+                code: Some(NumberOrString::String(COMPONENT_NOT_IMPORTED.to_string())),
+                source: Some(DIAGNOSTIC_SOURCE.to_string()),
+                message: format!("Component '{}' is not imported", element.get_name()?),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Returns the JSX elements, other than partials, that screen readers have
+/// nothing to announce for: no text content and no `aria-label`.
+pub fn get_unlabeled_elements<'a>(ast: &'a Node, config: &ConfigValues) -> Vec<&'a Node> {
+    find_all_matches(ast, |node| {
+        node.is_any_jsx_element()
+            && !is_configured_partial(node, config)
+            && !node.get_all_attributes().contains(&"aria-label")
+            && node.text_content().trim().is_empty()
+    })
+}
+
+fn get_aria_label_diagnostics(ast: &Node, text: &str, config: &ConfigValues) -> Vec<Diagnostic> {
+    get_unlabeled_elements(ast, config)
+        .into_iter()
+        .filter_map(|element| {
+            Some(Diagnostic {
+                range: get_node_range(text, element)?,
+                severity: Some(DiagnosticSeverity::HINT),
+                code: Some(NumberOrString::String(MISSING_ARIA_LABEL.to_string())),
+                source: Some(DIAGNOSTIC_SOURCE.to_string()),
+                message: format!(
+                    "'{}' has no text content or aria-label",
+                    element.get_name()?
+                ),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Warns about links and images relative to the document whose file doesn't
+/// exist. Links to the root of the site, starting with `/`, can't be resolved
+/// without knowing where it's served from, so they aren't checked.
+fn get_broken_link_diagnostics(ast: &Node, text: &str, uri: &Url) -> Vec<Diagnostic> {
+    find_all_matches(ast, |node| matches!(node, Node::Link(_) | Node::Image(_)))
+        .into_iter()
+        .filter_map(|node| {
+            let (url, kind) = match node {
+                Node::Link(link) => (&link.url, "Linked file"),
+                Node::Image(image) => (&image.url, "Image"),
+                _ => return None,
+            };
+            let path = url.split(['#', '?']).next()?;
+            if path.is_empty() || path.starts_with('/') || Url::parse(path).is_ok() {
+                return None;
+            }
+
+            let target = resolve_link(path, uri)?.to_file_path().ok()?;
+            if target.exists() {
+                return None;
+            }
+
+            Some(Diagnostic {
+                range: get_node_range(text, node)?,
+                severity: Some(DiagnosticSeverity::WARNING),
+                source: Some(LINK_DIAGNOSTIC_SOURCE.to_string()),
+                message: format!("{} '{}' not found", kind, path),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// The range and URL of a web link or image.
+pub type ExternalLink = (Range, String);
+
+/// Returns the web links and images of the document, with their range.
+pub fn get_external_links(ast: &Node, text: &str) -> Vec<ExternalLink> {
+    find_all_matches(ast, |node| matches!(node, Node::Link(_) | Node::Image(_)))
+        .into_iter()
+        .filter_map(|node| {
+            let url = match node {
+                Node::Link(link) => &link.url,
+                Node::Image(image) => &image.url,
+                _ => return None,
+            };
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return None;
+            }
+
+            Some((get_node_range(text, node)?, url.clone()))
+        })
+        .collect()
+}
+
+/// Warns about the external links that respond to a `HEAD` request with a
+/// client or server error. Links that can't be reached at all are left alone,
+/// since that's more often the network than the link. Statuses are kept in
+/// `statuses`, so each URL is requested once, and the URLs without one are
+/// all requested at the same time.
+pub async fn get_external_link_diagnostics(
+    links: Vec<ExternalLink>,
+    statuses: &DashMap<String, u16>,
+) -> Vec<Diagnostic> {
+    let uncached: HashSet<&str> = links
+        .iter()
+        .map(|(_, url)| url.as_str())
+        .filter(|url| !statuses.contains_key(*url))
+        .collect();
+    let requests = uncached.into_iter().map(|url| {
+        let url = url.to_string();
+        async move {
+            let request_url = url.clone();
+            let status = tokio::task::spawn_blocking(move || get_head_status(&request_url))
+                .await
+                .ok()
+                .flatten();
+            (url, status)
+        }
+    });
+    for (url, status) in join_all(requests).await {
+        if let Some(status) = status {
+            statuses.insert(url, status);
+        }
+    }
+
+    links
+        .into_iter()
+        .filter_map(|(range, url)| {
+            let status = *statuses.get(&url)?;
+            (status >= 400).then(|| Diagnostic {
+                range,
+                severity: Some(DiagnosticSeverity::WARNING),
+                source: Some(LINK_DIAGNOSTIC_SOURCE.to_string()),
+                message: format!("Link '{}' responded with status {}", url, status),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// The status of a `HEAD` request for `url`, or `None` when it fails before
+/// getting a response.
+fn get_head_status(url: &str) -> Option<u16> {
+    let agent = ureq::AgentBuilder::new()
+        .timeout(EXTERNAL_LINK_TIMEOUT)
+        .build();
+
+    match agent.head(url).call() {
+        Ok(response) => Some(response.status()),
+        Err(ureq::Error::Status(status, _)) => Some(status),
+        Err(ureq::Error::Transport(_)) => None,
+    }
+}
+
+/// Computes diagnostics for every document in scope. Open documents always use
+/// their in-memory AST; in `workspace` scope the remaining `.mdx` files under
+/// `workspace_root` are read and parsed on demand.
+pub async fn get_workspace_diagnostics(
+    ast_map: &DashMap<String, Node>,
+    text_map: &DashMap<String, String>,
+    config: &ConfigValues,
+) -> Vec<(Url, Vec<Diagnostic>)> {
+    let mut reports = Vec::new();
+    let mut open_paths = HashSet::new();
+
+    for entry in ast_map.iter() {
+        let (Ok(uri), Some(text)) = (Url::parse(entry.key()), text_map.get(entry.key())) else {
+            continue;
+        };
+        if let Ok(path) = uri.to_file_path() {
+            open_paths.insert(path);
+        }
+        let diagnostics = get_diagnostics(entry.value(), &text, &uri, config);
+        reports.push((uri, diagnostics));
+    }
+
+    if config.diagnostics_scope == DiagnosticsScope::Workspace {
+        let roots = [config.workspace_root.clone()];
+        let files = find_files(&roots, &config.exclude_dirs, |path| {
+            has_extension(path, &["mdx"])
+        })
+        .await;
+
+        for file in files {
+            if open_paths.contains(&file) {
+                continue;
+            }
+            let Ok(uri) = Url::from_file_path(&file) else {
+                continue;
+            };
+            let Ok(text) = fs::read_to_string(&file).await else {
+                continue;
+            };
+            let Ok(ast) = to_mdast(&text, &get_parser_options()) else {
+                continue;
+            };
+            let diagnostics = get_diagnostics(&ast, &text, &uri, config);
+            reports.push((uri, diagnostics));
+        }
+    }
+
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use log::debug;
+
+    use super::*;
+
+    fn parse(text: &str) -> (Node, &str) {
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+        (ast, text)
+    }
+
+    fn document_uri() -> Url {
+        Url::parse("file:///docs/index.mdx").unwrap()
+    }
+
+    #[test]
+    fn test_unresolved_partial() {
+        let (ast, text) = parse("# Hello\n\n<$Partial src=\"missing.mdx\" />");
+        let diagnostics = get_diagnostics(&ast, text, &document_uri(), &ConfigValues::default());
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::ERROR));
+        assert_eq!(
+            diagnostics[0].message,
+            "Partial 'missing.mdx' not found in any partials_dir"
+        );
+        assert_eq!(diagnostics[0].range.start.line, 2);
+    }
+
+    #[test]
+    fn test_partial_with_implicit_extension() {
+        let partials_dir = tempfile::tempdir().unwrap();
+        fs::write(partials_dir.path().join("notes.md"), "Notes").unwrap();
+        let (ast, text) = parse("<$Partial src=\"notes\" />\n\n<$Partial src=\"notes.mdx\" />");
+        let config = ConfigValues {
+            partials_dirs: vec![partials_dir.path().to_path_buf()],
+            ..Default::default()
+        };
+        let diagnostics = get_diagnostics(&ast, text, &document_uri(), &config);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Partial 'notes.mdx' not found in any partials_dir"
+        );
+    }
+
+    #[test]
+    fn test_unresolved_partial_custom_element_name() {
+        let (ast, text) =
+            parse("<Include src=\"missing.mdx\" />\n\n<$Partial src=\"other.mdx\" />");
+        let config = ConfigValues {
+            partial_element_name: Some("Include".to_string()),
+            ..Default::default()
+        };
+        let diagnostics = get_diagnostics(&ast, text, &document_uri(), &config);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Partial 'missing.mdx' not found in any partials_dir"
+        );
+    }
+
+    #[test]
+    fn test_partial_without_src() {
+        let (ast, text) = parse("<$Partial />\n\n<$Partial src=\"\" />\n\n<$Partial src={name} />");
+        let diagnostics = get_diagnostics(&ast, text, &document_uri(), &ConfigValues::default());
+
+        let errors: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.range.start.line,
+                    diagnostic.code.clone(),
+                    diagnostic.message.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    0,
+                    Some(NumberOrString::String(MISSING_PARTIAL_SRC.to_string())),
+                    "$Partial element is missing required 'src' attribute"
+                ),
+                (
+                    2,
+                    Some(NumberOrString::String(EMPTY_PARTIAL_SRC.to_string())),
+                    "$Partial element has an empty 'src' attribute"
+                ),
+            ]
+        );
+        assert!(diagnostics
+            .iter()
+            .all(|diagnostic| diagnostic.severity == Some(DiagnosticSeverity::ERROR)));
+    }
+
+    #[test]
+    fn test_component_not_imported() {
+        let (ast, text) = parse(
+            "import { Card } from \"@acme/card\"\n\n<Button />\n\n<Card>\n  <Badge />\n</Card>",
+        );
+        let config = ConfigValues {
+            component_map: [("Button", "@acme/button"), ("Card", "@acme/card")]
+                .into_iter()
+                .map(|(name, path)| (name.to_string(), path.to_string()))
+                .collect(),
+            ..Default::default()
+        };
+
+        let diagnostics = get_diagnostics(&ast, text, &document_uri(), &config);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(
+            diagnostics[0].code,
+            Some(NumberOrString::String(COMPONENT_NOT_IMPORTED.to_string()))
+        );
+        assert_eq!(diagnostics[0].message, "Component 'Button' is not imported");
+        assert_eq!(diagnostics[0].range.start.line, 2);
+    }
+
+    #[test]
+    fn test_missing_aria_label() {
+        let (ast, text) = parse(
+            "<IconButton><Icon name=\"close\" /></IconButton>\n\n<Button>Save</Button>\n\n<Icon aria-label=\"Close\" />",
+        );
+        assert!(get_diagnostics(&ast, text, &document_uri(), &ConfigValues::default()).is_empty());
+
+        let config = ConfigValues {
+            lint_accessibility: true,
+            ..Default::default()
+        };
+        let diagnostics = get_diagnostics(&ast, text, &document_uri(), &config);
+
+        let messages: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "'IconButton' has no text content or aria-label",
+                "'Icon' has no text content or aria-label"
+            ]
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::HINT));
+    }
+
+    #[test]
+    fn test_broken_links() {
+        let workspace = tempfile::tempdir().unwrap();
+        fs::create_dir_all(workspace.path().join("images")).unwrap();
+        fs::write(workspace.path().join("guide.mdx"), "# Guide").unwrap();
+        fs::write(workspace.path().join("images/logo.png"), "").unwrap();
+        let uri = Url::from_file_path(workspace.path().join("index.mdx")).unwrap();
+
+        let (ast, text) = parse(
+            r#"
+[Guide](./guide.mdx#setup) and [missing](./missing.mdx), [site](https://example.com),
+[anchor](#intro) and [root](/docs/guide).
+
+![Logo](images/logo.png) ![Banner](images/banner%20wide.png)
+"#
+            .trim(),
+        );
+        let diagnostics = get_diagnostics(&ast, text, &uri, &ConfigValues::default());
+
+        let messages: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.range.start.line, diagnostic.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                (0, "Linked file './missing.mdx' not found"),
+                (3, "Image 'images/banner%20wide.png' not found"),
+            ]
+        );
+        assert_eq!(
+            diagnostics[0].source.as_deref(),
+            Some(LINK_DIAGNOSTIC_SOURCE)
+        );
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+    }
+
+    #[test]
+    fn test_get_external_links() {
+        let (ast, text) = parse(
+            "[site](https://example.com) [local](./a.mdx)
+
+![](http://example.com/a.png)",
+        );
+
+        let links: Vec<_> = get_external_links(&ast, text)
+            .into_iter()
+            .map(|(range, url)| (range.start.line, url))
+            .collect();
+        assert_eq!(
+            links,
+            vec![
+                (0, "https://example.com".to_string()),
+                (2, "http://example.com/a.png".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_external_link_diagnostics_use_cached_statuses() {
+        let statuses = DashMap::new();
+        statuses.insert("https://example.com/gone".to_string(), 404);
+        statuses.insert("https://example.com/".to_string(), 200);
+        let range = Range::default();
+
+        let diagnostics = get_external_link_diagnostics(
+            vec![
+                (range, "https://example.com/gone".to_string()),
+                (range, "https://example.com/".to_string()),
+            ],
+            &statuses,
+        )
+        .await;
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Link 'https://example.com/gone' responded with status 404"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_external_links() {
+        let statuses = DashMap::new();
+        let url = "http://127.0.0.1:1/".to_string();
+
+        let diagnostics = get_external_link_diagnostics(
+            vec![(Range::default(), url.clone()), (Range::default(), url)],
+            &statuses,
+        )
+        .await;
+
+        assert!(diagnostics.is_empty());
+        assert!(statuses.is_empty());
+    }
+
+    fn create_workspace() -> (tempfile::TempDir, ConfigValues) {
+        let workspace = tempfile::tempdir().unwrap();
+        fs::create_dir_all(workspace.path().join("partials")).unwrap();
+        fs::create_dir_all(workspace.path().join("node_modules")).unwrap();
+        fs::write(workspace.path().join("partials/hero.mdx"), "# Hero").unwrap();
+        fs::write(
+            workspace.path().join("closed.mdx"),
+            "<$Partial src=\"missing.mdx\" />",
+        )
+        .unwrap();
+        fs::write(
+            workspace.path().join("node_modules/ignored.mdx"),
+            "<$Partial src=\"missing.mdx\" />",
+        )
+        .unwrap();
+
+        let config = ConfigValues {
+            workspace_root: workspace.path().to_path_buf(),
+            partials_dirs: vec![workspace.path().join("partials")],
+            exclude_dirs: vec![workspace.path().join("node_modules")],
+            ..Default::default()
+        };
+        (workspace, config)
+    }
+
+    fn open_document(
+        ast_map: &DashMap<String, Node>,
+        text_map: &DashMap<String, String>,
+        path: PathBuf,
+        text: &str,
+    ) -> Url {
+        let uri = Url::from_file_path(path).unwrap();
+        ast_map.insert(uri.to_string(), parse(text).0);
+        text_map.insert(uri.to_string(), text.to_string());
+        uri
+    }
+
+    #[tokio::test]
+    async fn test_open_documents_scope() {
+        let (workspace, config) = create_workspace();
+        let ast_map = DashMap::new();
+        let text_map = DashMap::new();
+        let open = open_document(
+            &ast_map,
+            &text_map,
+            workspace.path().join("open.mdx"),
+            "<$Partial src=\"hero.mdx\" />",
+        );
+
+        let reports = get_workspace_diagnostics(&ast_map, &text_map, &config).await;
+
+        assert_eq!(reports, vec![(open, vec![])]);
+    }
+
+    #[tokio::test]
+    async fn test_workspace_scope() {
+        let (workspace, config) = create_workspace();
+        let config = ConfigValues {
+            diagnostics_scope: DiagnosticsScope::Workspace,
+            ..config
+        };
+        let ast_map = DashMap::new();
+        let text_map = DashMap::new();
+        let open = open_document(
+            &ast_map,
+            &text_map,
+            workspace.path().join("partials/hero.mdx"),
+            "<$Partial src=\"missing.mdx\" />",
+        );
+
+        let reports = get_workspace_diagnostics(&ast_map, &text_map, &config).await;
+        let counts: Vec<_> = reports
+            .iter()
+            .map(|(uri, diagnostics)| (uri.clone(), diagnostics.len()))
+            .collect();
+
+        // The open version of `hero.mdx` takes precedence over the one on disk,
+        // and `node_modules` is excluded.
+        assert_eq!(
+            counts,
+            vec![
+                (open, 1),
+                (
+                    Url::from_file_path(workspace.path().join("closed.mdx")).unwrap(),
+                    1
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use tower_lsp::lsp_types::TextDocumentContentChangeEvent;
+
+use crate::encoding::position_to_offset;
+
+/// Applies the changes of a `textDocument/didChange` notification, in order.
+/// A change without a range replaces the whole text.
+pub fn apply_content_changes(text: &mut String, changes: &[TextDocumentContentChangeEvent]) {
+    for change in changes {
+        match change.range {
+            Some(range) => {
+                let start = position_to_offset(text, &range.start);
+                let end = position_to_offset(text, &range.end).max(start);
+                text.replace_range(start..end, &change.text);
+            }
+            None => *text = change.text.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tower_lsp::lsp_types::{Position, Range};
+
+    use super::*;
+
+    fn change(range: Option<Range>, text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range,
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_apply_content_changes() {
+        let mut text = "# Café\n\nSome text.".to_string();
+
+        apply_content_changes(
+            &mut text,
+            &[
+                change(
+                    Some(Range::new(Position::new(0, 2), Position::new(0, 6))),
+                    "Bar",
+                ),
+                change(
+                    Some(Range::new(Position::new(2, 5), Position::new(2, 5))),
+                    "more ",
+                ),
+                change(
+                    Some(Range::new(Position::new(2, 14), Position::new(2, 15))),
+                    "!\n\nDone.",
+                ),
+            ],
+        );
+        assert_eq!(text, "# Bar\n\nSome more text!\n\nDone.");
+
+        apply_content_changes(&mut text, &[change(None, "# New")]);
+        assert_eq!(text, "# New");
+    }
+}
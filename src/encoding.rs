@@ -0,0 +1,155 @@
+use markdown::mdast::Node;
+use tower_lsp::lsp_types::{Position, Range};
+
+/// The length of `text` in UTF-16 code units, which LSP columns count.
+pub fn utf16_len(text: &str) -> u32 {
+    text.encode_utf16().count() as u32
+}
+
+/// Converts a UTF-16 column of `line` to a byte offset in it. Columns past the
+/// end of the line, or inside a character, are clamped to the next character
+/// boundary.
+pub fn utf16_to_byte_offset(line: &str, utf16_col: u32) -> usize {
+    let mut units = 0;
+    for (index, ch) in line.char_indices() {
+        if units >= utf16_col as usize {
+            return index;
+        }
+        units += ch.len_utf16();
+    }
+
+    line.len()
+}
+
+/// Converts an LSP position to a byte offset in `text`. Positions past the end
+/// of a line or of the text are clamped, as the protocol requires.
+pub fn position_to_offset(text: &str, position: &Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(index) => line_start += index + 1,
+            None => return text.len(),
+        }
+    }
+
+    let line = &text[line_start..];
+    let line = &line[..line.find('\n').unwrap_or(line.len())];
+    line_start + utf16_to_byte_offset(line, position.character)
+}
+
+/// Converts a byte offset in `text` to an LSP position.
+pub fn offset_to_position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: utf16_len(&before[line_start..]),
+    }
+}
+
+/// Converts an LSP position to the position of the same point with the
+/// character counted in bytes, as the columns of mdast nodes are. Columns past
+/// the end of the line stay past it, so that they compare as being after
+/// everything on the line.
+pub fn to_byte_position(text: &str, position: &Position) -> Position {
+    let line = text
+        .split('\n')
+        .nth(position.line as usize)
+        .unwrap_or_default();
+    let overflow = position.character.saturating_sub(utf16_len(line));
+
+    Position {
+        line: position.line,
+        character: utf16_to_byte_offset(line, position.character) as u32 + overflow,
+    }
+}
+
+/// Returns the range of `node` as LSP positions, counting UTF-16 code units
+/// on lines with non-ASCII text. `text` is the text the node was parsed from;
+/// returns `None` when the node doesn't fit in it.
+pub fn get_node_range(text: &str, node: &Node) -> Option<Range> {
+    let position = node.position()?;
+    if !text.is_char_boundary(position.start.offset) || !text.is_char_boundary(position.end.offset)
+    {
+        return None;
+    }
+
+    Some(Range {
+        start: offset_to_position(text, position.start.offset),
+        end: offset_to_position(text, position.end.offset),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    #[test]
+    fn test_utf16_to_byte_offset() {
+        let line = "a😀漢b";
+
+        assert_eq!(utf16_to_byte_offset(line, 0), 0);
+        assert_eq!(utf16_to_byte_offset(line, 1), 1);
+        // The emoji is two UTF-16 code units and four bytes, and the CJK
+        // character one code unit and three bytes.
+        assert_eq!(utf16_to_byte_offset(line, 3), 5);
+        assert_eq!(utf16_to_byte_offset(line, 4), 8);
+        assert_eq!(utf16_to_byte_offset(line, 2), 5);
+        assert_eq!(utf16_to_byte_offset(line, 99), 9);
+    }
+
+    #[test]
+    fn test_position_to_offset() {
+        let text = "# Café 😀\n\nText";
+
+        assert_eq!(position_to_offset(text, &Position::new(0, 0)), 0);
+        assert_eq!(position_to_offset(text, &Position::new(0, 6)), 7);
+        assert_eq!(position_to_offset(text, &Position::new(0, 9)), 12);
+        assert_eq!(position_to_offset(text, &Position::new(0, 99)), 12);
+        assert_eq!(position_to_offset(text, &Position::new(2, 2)), 16);
+        assert_eq!(position_to_offset(text, &Position::new(9, 0)), text.len());
+    }
+
+    #[test]
+    fn test_offset_to_position() {
+        let text = "# Café 😀\n\nText";
+
+        assert_eq!(offset_to_position(text, 0), Position::new(0, 0));
+        assert_eq!(offset_to_position(text, 12), Position::new(0, 9));
+        assert_eq!(offset_to_position(text, 16), Position::new(2, 2));
+    }
+
+    #[test]
+    fn test_to_byte_position() {
+        let text = "# Café 😀\n\nText";
+
+        assert_eq!(
+            to_byte_position(text, &Position::new(0, 9)),
+            Position::new(0, 12)
+        );
+        assert_eq!(
+            to_byte_position(text, &Position::new(0, 11)),
+            Position::new(0, 14)
+        );
+        assert_eq!(
+            to_byte_position(text, &Position::new(2, 2)),
+            Position::new(2, 2)
+        );
+    }
+
+    #[test]
+    fn test_get_node_range() {
+        let text = "é😀 *x*";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+
+        let emphasis = &ast.children().unwrap()[0].children().unwrap()[1];
+        assert_eq!(
+            get_node_range(text, emphasis),
+            Some(Range::new(Position::new(0, 4), Position::new(0, 7)))
+        );
+    }
+}
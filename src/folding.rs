@@ -0,0 +1,104 @@
+use markdown::mdast::Node;
+use tower_lsp::lsp_types::{FoldingRange, FoldingRangeKind};
+
+use crate::{ast::find_all_matches, encoding::get_node_range, nodes::headings::get_section};
+
+/// Returns the folding ranges of a document: heading sections, then fenced
+/// code blocks, block JSX elements and tables that span several lines.
+pub fn get_folding_ranges(ast: &Node, text: &str) -> Vec<FoldingRange> {
+    let sections = ast
+        .children()
+        .into_iter()
+        .flatten()
+        .filter(|node| matches!(node, Node::Heading(_)))
+        .filter_map(|heading| {
+            let section = get_section(ast, heading);
+            let start = get_node_range(text, section.first()?)?.start.line;
+            let end = get_node_range(text, section.last()?)?.end.line;
+            folding_range(start, end, Some(FoldingRangeKind::Region))
+        });
+
+    let blocks = find_all_matches(ast, |node| {
+        matches!(
+            node,
+            Node::Code(_) | Node::MdxJsxFlowElement(_) | Node::Table(_)
+        )
+    })
+    .into_iter()
+    .filter_map(|node| {
+        let range = get_node_range(text, node)?;
+        folding_range(range.start.line, range.end.line, None)
+    });
+
+    sections.chain(blocks).collect()
+}
+
+/// A range that folds the lines after `start_line` up to `end_line`. Single
+/// lines have nothing to fold.
+fn folding_range(
+    start_line: u32,
+    end_line: u32,
+    kind: Option<FoldingRangeKind>,
+) -> Option<FoldingRange> {
+    (end_line > start_line).then(|| FoldingRange {
+        start_line,
+        end_line,
+        kind,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use log::debug;
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    #[test]
+    fn test_get_folding_ranges() {
+        let text = r#"
+# Guide
+
+Intro.
+
+## Setup
+
+```sh
+npm install
+```
+
+## Usage
+
+<Callout>
+  Careful.
+</Callout>
+
+| a | b |
+| - | - |
+| 1 | 2 |
+
+# Reference
+"#
+        .trim();
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let ranges: Vec<_> = get_folding_ranges(&ast, text)
+            .into_iter()
+            .map(|range| (range.start_line, range.end_line, range.kind))
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (0, 18, Some(FoldingRangeKind::Region)),
+                (4, 8, Some(FoldingRangeKind::Region)),
+                (10, 18, Some(FoldingRangeKind::Region)),
+                (6, 8, None),
+                (12, 14, None),
+                (16, 18, None),
+            ]
+        );
+    }
+}
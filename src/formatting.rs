@@ -0,0 +1,942 @@
+use std::{
+    io::{self, Read, Write},
+    path::Path,
+    process::{Command, Stdio},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use markdown::mdast::{AlignKind, Node};
+use tower_lsp::lsp_types::{Position, Range, TextEdit};
+
+use crate::{
+    ast::{find_all_matches, get_ancestor_chain, node_depth},
+    completion::get_line_prefix,
+    config::ConfigValues,
+    encoding::{get_node_range, offset_to_position},
+};
+
+pub const EXPRESSION_OPEN: &str = "{";
+pub const TAG_CLOSE: &str = ">";
+/// How long prettier may take to format a document before it's killed.
+pub const PRETTIER_TIMEOUT: Duration = Duration::from_secs(10);
+const PRETTIER_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Formats `text` with `prettier --parser mdx`, as the document at `path` so
+/// that the project's prettier config applies. Fails with
+/// [`io::ErrorKind::NotFound`] when prettier isn't installed, and with the
+/// output of prettier when it rejects the document. Prettier is killed when
+/// it takes longer than `timeout`, failing with [`io::ErrorKind::TimedOut`].
+pub fn run_prettier(
+    text: &str,
+    path: &Path,
+    config: &ConfigValues,
+    timeout: Duration,
+) -> io::Result<String> {
+    let prettier = config
+        .prettier_path
+        .as_deref()
+        .unwrap_or(Path::new("prettier"));
+    let mut command = Command::new(prettier);
+    command
+        .args(["--parser", "mdx", "--stdin-filepath"])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    if config.workspace_root.is_dir() {
+        command.current_dir(&config.workspace_root);
+    }
+    let mut child = command.spawn()?;
+    let (Some(mut stdin), Some(stdout), Some(stderr)) =
+        (child.stdin.take(), child.stdout.take(), child.stderr.take())
+    else {
+        return Err(io::Error::other("prettier's standard streams aren't piped"));
+    };
+
+    // The pipes are served from other threads so that a full one can't block
+    // prettier. They're left behind if prettier hangs, and end once it's
+    // killed.
+    let text = text.to_string();
+    let writer = thread::spawn(move || stdin.write_all(text.as_bytes()));
+    let stdout = thread::spawn(move || read_all(stdout));
+    let stderr = thread::spawn(move || read_all(stderr));
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            // Killing fails when prettier exited in the meantime, which is
+            // just as good.
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("prettier took longer than {} ms", timeout.as_millis()),
+            ));
+        }
+        thread::sleep(PRETTIER_POLL_INTERVAL);
+    };
+
+    let written = join_pipe(writer);
+    if !status.success() {
+        let stderr = join_pipe(stderr)?;
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&stderr).trim().to_string(),
+        ));
+    }
+    written?;
+    String::from_utf8(join_pipe(stdout)?).map_err(io::Error::other)
+}
+
+fn read_all(mut reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    reader.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+fn join_pipe<T>(handle: JoinHandle<io::Result<T>>) -> io::Result<T> {
+    handle
+        .join()
+        .map_err(|_| io::Error::other("serving prettier's pipes panicked"))?
+}
+
+/// Replaces the whole document with its formatted version, unless formatting
+/// changed nothing.
+pub fn get_replacement_edits(text: &str, formatted: &str) -> Vec<TextEdit> {
+    if text == formatted {
+        return Vec::new();
+    }
+
+    vec![TextEdit {
+        range: Range {
+            start: Position::new(0, 0),
+            end: offset_to_position(text, text.len()),
+        },
+        new_text: formatted.to_string(),
+    }]
+}
+
+/// Computes the edits for `textDocument/formatting`.
+pub fn get_formatting_edits(ast: &Node, text: &str, config: &ConfigValues) -> Vec<TextEdit> {
+    let mut edits = normalize_expression_spacing(ast, text);
+    edits.extend(align_tables(ast, text));
+
+    // Edits must not overlap, so lines already rewritten are left alone.
+    if config.trim_trailing_whitespace {
+        let trailing_whitespace: Vec<_> = trim_trailing_whitespace(ast, text)
+            .into_iter()
+            .filter(|trim| {
+                !edits.iter().any(|edit| {
+                    (edit.range.start.line..=edit.range.end.line).contains(&trim.range.start.line)
+                })
+            })
+            .collect();
+        edits.extend(trailing_whitespace);
+    }
+
+    edits
+}
+
+/// Deletes spaces and tabs at the end of lines, except inside fenced code
+/// and where they make a hard line break.
+fn trim_trailing_whitespace(ast: &Node, text: &str) -> Vec<TextEdit> {
+    let code_lines: Vec<(u32, u32)> = find_all_matches(ast, |node| matches!(node, Node::Code(_)))
+        .into_iter()
+        .filter_map(|node| get_node_range(text, node))
+        .map(|range| (range.start.line, range.end.line))
+        .collect();
+    let break_lines: Vec<u32> = find_all_matches(ast, |node| matches!(node, Node::Break(_)))
+        .into_iter()
+        .filter_map(|node| get_node_range(text, node))
+        .map(|range| range.start.line)
+        .collect();
+
+    text.split('\n')
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let index = index as u32;
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            let trimmed = line.trim_end_matches([' ', '\t']);
+            if trimmed.len() == line.len()
+                || break_lines.contains(&index)
+                || code_lines
+                    .iter()
+                    .any(|(start, end)| (*start..=*end).contains(&index))
+            {
+                return None;
+            }
+
+            Some(TextEdit {
+                range: Range {
+                    start: Position {
+                        line: index,
+                        character: trimmed.encode_utf16().count() as u32,
+                    },
+                    end: Position {
+                        line: index,
+                        character: line.encode_utf16().count() as u32,
+                    },
+                },
+                new_text: String::new(),
+            })
+        })
+        .collect()
+}
+
+/// Computes the edits for `textDocument/rangeFormatting`.
+pub fn get_range_formatting_edits(ast: &Node, text: &str, range: &Range) -> Vec<TextEdit> {
+    normalize_list_indentation(ast, text, range)
+}
+
+/// Indents the items of bullet lists by two spaces per nesting level. Only
+/// items starting inside `range` are touched, and items inside ordered lists
+/// or other containers (block quotes, JSX...) are left alone, since their
+/// content offset depends on the container.
+fn normalize_list_indentation(ast: &Node, text: &str, range: &Range) -> Vec<TextEdit> {
+    let lines: Vec<&str> = text.lines().collect();
+
+    find_all_matches(ast, |node| matches!(node, Node::ListItem(_)))
+        .into_iter()
+        .filter_map(|item| {
+            let start = get_node_range(text, item)?.start;
+            if start.line < range.start.line || start.line > range.end.line {
+                return None;
+            }
+
+            let chain = get_ancestor_chain(ast, text, &start);
+            let only_bullet_lists = chain.iter().all(|node| match node {
+                Node::Root(_) | Node::ListItem(_) => true,
+                Node::List(list) => !list.ordered,
+                _ => false,
+            });
+            if !only_bullet_lists {
+                return None;
+            }
+
+            let depth = node_depth(&chain, |node| matches!(node, Node::List(_)));
+            let expected = 2 * depth.saturating_sub(1);
+            let line = lines.get(start.line as usize)?;
+            let actual = line.len() - line.trim_start_matches([' ', '\t']).len();
+            if line[..actual] == " ".repeat(expected) {
+                return None;
+            }
+
+            Some(TextEdit {
+                range: Range {
+                    start: Position {
+                        line: start.line,
+                        character: 0,
+                    },
+                    end: Position {
+                        line: start.line,
+                        character: actual as u32,
+                    },
+                },
+                new_text: " ".repeat(expected),
+            })
+        })
+        .collect()
+}
+
+/// Rewrites single-line MDX expressions to `{ value }`. Multi-line expressions
+/// are left untouched.
+fn normalize_expression_spacing(ast: &Node, text: &str) -> Vec<TextEdit> {
+    find_all_matches(ast, |node| {
+        matches!(
+            node,
+            Node::MdxTextExpression(_) | Node::MdxFlowExpression(_)
+        )
+    })
+    .into_iter()
+    .filter_map(|node| {
+        let value = match node {
+            Node::MdxTextExpression(expression) => &expression.value,
+            Node::MdxFlowExpression(expression) => &expression.value,
+            _ => return None,
+        };
+        let trimmed = value.trim();
+        if trimmed.is_empty() || value.contains('\n') {
+            return None;
+        }
+
+        let pos = node.position()?;
+        let expected = format!("{{ {} }}", trimmed);
+        if text.get(pos.start.offset..pos.end.offset)? == expected {
+            return None;
+        }
+
+        Some(TextEdit {
+            range: get_node_range(text, node)?,
+            new_text: expected,
+        })
+    })
+    .collect()
+}
+
+/// Pads every column of GFM tables to the width of its widest cell.
+fn align_tables(ast: &Node, text: &str) -> Vec<TextEdit> {
+    find_all_matches(ast, |node| matches!(node, Node::Table(_)))
+        .into_iter()
+        .filter_map(|node| get_table_edit(node, text))
+        .collect()
+}
+
+/// Rewrites a table with every column padded to the width of its widest
+/// cell, replacing its whole range. Returns `None` when the table is already
+/// aligned, or doesn't start a line (e.g. inside a block quote).
+pub fn get_table_edit(node: &Node, text: &str) -> Option<TextEdit> {
+    let Node::Table(table) = node else {
+        return None;
+    };
+    let pos = node.position()?;
+    if pos.start.column != 1 {
+        return None;
+    }
+
+    let source = text.get(pos.start.offset..pos.end.offset)?;
+    let formatted = format_table(source, &table.align)?;
+    if formatted == source {
+        return None;
+    }
+
+    Some(TextEdit {
+        range: get_node_range(text, node)?,
+        new_text: formatted,
+    })
+}
+
+fn format_table(source: &str, align: &[AlignKind]) -> Option<String> {
+    let rows: Vec<Vec<String>> = source.lines().map(split_table_row).collect();
+    let columns = align.len();
+    if rows.len() < 2 || rows.iter().any(|row| row.len() > columns) {
+        return None;
+    }
+
+    // Delimiter cells are at least three dashes wide.
+    let widths: Vec<usize> = (0..columns)
+        .map(|column| {
+            rows.iter()
+                .enumerate()
+                .filter(|(index, _)| *index != 1)
+                .filter_map(|(_, row)| row.get(column))
+                .map(|cell| cell.chars().count())
+                .max()
+                .unwrap_or_default()
+                .max(3)
+        })
+        .collect();
+
+    let lines: Vec<String> = rows
+        .iter()
+        .enumerate()
+        .map(|(index, row)| {
+            let cells: Vec<String> = (0..columns)
+                .map(|column| {
+                    let width = widths[column];
+                    if index == 1 {
+                        return format_delimiter(&align[column], width);
+                    }
+                    let cell = row.get(column).map(String::as_str).unwrap_or_default();
+                    match align[column] {
+                        AlignKind::Right => format!("{:>width$}", cell),
+                        AlignKind::Center => format!("{:^width$}", cell),
+                        _ => format!("{:<width$}", cell),
+                    }
+                })
+                .collect();
+            format!("| {} |", cells.join(" | "))
+        })
+        .collect();
+
+    Some(lines.join("\n"))
+}
+
+fn format_delimiter(align: &AlignKind, width: usize) -> String {
+    match align {
+        AlignKind::Left => format!(":{}", "-".repeat(width - 1)),
+        AlignKind::Right => format!("{}:", "-".repeat(width - 1)),
+        AlignKind::Center => format!(":{}:", "-".repeat(width - 2)),
+        AlignKind::None => "-".repeat(width),
+    }
+}
+
+/// Splits a table row into trimmed cells. Pipes that are escaped or inside
+/// inline code don't separate cells.
+pub fn split_table_row(line: &str) -> Vec<String> {
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = match line.strip_suffix('|') {
+        Some(stripped) if !stripped.ends_with('\\') => stripped,
+        _ => line,
+    };
+
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut in_code = false;
+    let mut escaped = false;
+    for ch in line.chars() {
+        match ch {
+            '|' if !in_code && !escaped => {
+                cells.push(cell.trim().to_string());
+                cell.clear();
+                continue;
+            }
+            '`' if !escaped => in_code = !in_code,
+            _ => {}
+        }
+        escaped = ch == '\\' && !escaped;
+        cell.push(ch);
+    }
+    cells.push(cell.trim().to_string());
+
+    cells
+}
+
+/// Computes the edits for `textDocument/onTypeFormatting`. The AST may be the
+/// last successfully parsed version of the document, since the typed character
+/// often leaves the MDX temporarily invalid.
+pub fn get_on_type_edits(
+    ast: Option<&Node>,
+    text: &str,
+    position: &Position,
+    ch: &str,
+) -> Vec<TextEdit> {
+    match ch {
+        EXPRESSION_OPEN => close_expression(ast, text, position).into_iter().collect(),
+        TAG_CLOSE => close_tag(ast, text, position).into_iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn close_expression(ast: Option<&Node>, text: &str, position: &Position) -> Option<TextEdit> {
+    let prefix = get_line_prefix(text, position);
+    let before_brace = prefix.strip_suffix('{')?;
+    if before_brace.ends_with('\\') || is_in_inline_code(before_brace) || is_in_string(before_brace)
+    {
+        return None;
+    }
+
+    let line = text.lines().nth(position.line as usize).unwrap_or_default();
+    if line[prefix.len()..].starts_with('}') {
+        return None;
+    }
+
+    if ast.is_some_and(|ast| is_in_code(ast, text, position)) {
+        return None;
+    }
+
+    Some(TextEdit {
+        range: Range {
+            start: *position,
+            end: *position,
+        },
+        new_text: "}".to_string(),
+    })
+}
+
+/// Inserts the closing tag of the JSX element whose opening tag was just
+/// finished on this line, e.g. `</$Partial>` after `<$Partial src="hero">`.
+fn close_tag(ast: Option<&Node>, text: &str, position: &Position) -> Option<TextEdit> {
+    let prefix = get_line_prefix(text, position);
+    let before_bracket = prefix.strip_suffix('>')?;
+    if before_bracket.ends_with('/')
+        || is_in_inline_code(before_bracket)
+        || is_in_string(before_bracket)
+    {
+        return None;
+    }
+
+    let tag = &before_bracket[before_bracket.rfind('<')? + 1..];
+    if !is_open_tag(tag) {
+        return None;
+    }
+
+    let name = tag.split_whitespace().next()?;
+    if !name.starts_with(|ch: char| ch.is_alphabetic() || ch == '$' || ch == '_')
+        || !name
+            .chars()
+            .all(|ch| ch.is_alphanumeric() || matches!(ch, '$' | '_' | '-' | '.' | ':'))
+    {
+        return None;
+    }
+
+    let closing_tag = format!("</{}>", name);
+    let line = text.lines().nth(position.line as usize).unwrap_or_default();
+    if line[prefix.len()..].trim_start().starts_with(&closing_tag) {
+        return None;
+    }
+
+    if ast.is_some_and(|ast| is_in_code(ast, text, position)) {
+        return None;
+    }
+
+    Some(TextEdit {
+        range: Range {
+            start: *position,
+            end: *position,
+        },
+        new_text: closing_tag,
+    })
+}
+
+/// Checks that `tag`, the text after a `<`, is still inside that tag: every
+/// attribute expression is closed, and any `>` in it is part of one, like the
+/// arrow of `onClick={() => {}}`.
+fn is_open_tag(tag: &str) -> bool {
+    let mut depth = 0;
+    for ch in tag.chars() {
+        match ch {
+            '{' => depth += 1,
+            '}' if depth == 0 => return false,
+            '}' => depth -= 1,
+            '>' if depth == 0 => return false,
+            _ => {}
+        }
+    }
+
+    depth == 0
+}
+
+fn is_in_code(ast: &Node, text: &str, position: &Position) -> bool {
+    get_ancestor_chain(ast, text, position)
+        .iter()
+        .any(|node| matches!(node, Node::Code(_) | Node::InlineCode(_)))
+}
+
+fn is_in_inline_code(prefix: &str) -> bool {
+    prefix.matches('`').count() % 2 == 1
+}
+
+/// Checks whether the cursor is inside a quoted attribute value of the JSX
+/// tag that is currently open on this line.
+fn is_in_string(prefix: &str) -> bool {
+    let tag_start = match (prefix.rfind('<'), prefix.rfind('>')) {
+        (Some(open), Some(close)) if close > open => return false,
+        (Some(open), _) => open,
+        (None, _) => return false,
+    };
+
+    let mut quote = None;
+    for ch in prefix[tag_start..].chars() {
+        match (quote, ch) {
+            (None, '"' | '\'') => quote = Some(ch),
+            (Some(open), _) if open == ch => quote = None,
+            _ => {}
+        }
+    }
+
+    quote.is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use log::debug;
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    fn get_edits(text: &str, position: Position) -> Vec<TextEdit> {
+        let ast = to_mdast(text, &get_parser_options()).ok();
+        debug!("{:#?}", ast);
+
+        get_on_type_edits(ast.as_ref(), text, &position, EXPRESSION_OPEN)
+    }
+
+    fn closing_brace_at(position: Position) -> Vec<TextEdit> {
+        vec![TextEdit {
+            range: Range {
+                start: position,
+                end: position,
+            },
+            new_text: "}".to_string(),
+        }]
+    }
+
+    fn format(text: &str) -> Vec<TextEdit> {
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        get_formatting_edits(&ast, text, &ConfigValues::default())
+    }
+
+    fn delete(line: u32, start: u32, end: u32) -> TextEdit {
+        TextEdit {
+            range: Range {
+                start: Position {
+                    line,
+                    character: start,
+                },
+                end: Position {
+                    line,
+                    character: end,
+                },
+            },
+            new_text: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_trim_trailing_whitespace() {
+        let text = "# Title \t\n\nLine with break  \nnext line \n\n```js\nconst a = 1;   \n```\n";
+
+        assert_eq!(format(text), vec![delete(0, 7, 9), delete(3, 9, 10)]);
+    }
+
+    #[test]
+    fn test_trim_trailing_whitespace_disabled() {
+        let text = "# Title  \n";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        let config = ConfigValues {
+            trim_trailing_whitespace: false,
+            ..Default::default()
+        };
+
+        assert!(get_formatting_edits(&ast, text, &config).is_empty());
+    }
+
+    #[test]
+    fn test_normalize_single_line_expressions() {
+        let edits = format("{  foo  }\n\nSome {bar} text.");
+
+        assert_eq!(
+            edits,
+            vec![
+                TextEdit {
+                    range: Range {
+                        start: Position {
+                            line: 0,
+                            character: 0,
+                        },
+                        end: Position {
+                            line: 0,
+                            character: 9,
+                        },
+                    },
+                    new_text: "{ foo }".to_string(),
+                },
+                TextEdit {
+                    range: Range {
+                        start: Position {
+                            line: 2,
+                            character: 5,
+                        },
+                        end: Position {
+                            line: 2,
+                            character: 10,
+                        },
+                    },
+                    new_text: "{ bar }".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_preserve_multi_line_expressions() {
+        assert!(format("{\n  items.map((item) => item)\n}").is_empty());
+    }
+
+    #[test]
+    fn test_normalized_expressions_unchanged() {
+        assert!(format("{ foo }\n\nSome { bar } text.").is_empty());
+    }
+
+    #[test]
+    fn test_normalize_expression_after_non_ascii_text() {
+        let edits = format("Café 🎉 {bar}");
+
+        assert_eq!(
+            edits.iter().map(|edit| edit.range).collect::<Vec<_>>(),
+            vec![Range::new(Position::new(0, 8), Position::new(0, 13))]
+        );
+    }
+
+    #[test]
+    fn test_align_table() {
+        let text = "# Table\n\n| Name | Description |\n| :- | -: |\n| `a \\| b` | A much longer description |\n| b |\n";
+        let edits = format(text);
+
+        assert_eq!(
+            edits,
+            vec![TextEdit {
+                range: Range {
+                    start: Position {
+                        line: 2,
+                        character: 0,
+                    },
+                    end: Position {
+                        line: 5,
+                        character: 5,
+                    },
+                },
+                new_text: [
+                    "| Name     |               Description |",
+                    "| :------- | ------------------------: |",
+                    "| `a \\| b` | A much longer description |",
+                    "| b        |                           |",
+                ]
+                .join("\n"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_aligned_table_unchanged() {
+        let text = "| a   |  b  |\n| --- | :-: |\n| 1   |  2  |";
+
+        assert!(format(text).is_empty());
+    }
+
+    fn format_range(text: &str, start_line: u32, end_line: u32) -> Vec<TextEdit> {
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let range = Range {
+            start: Position {
+                line: start_line,
+                character: 0,
+            },
+            end: Position {
+                line: end_line,
+                character: 0,
+            },
+        };
+        get_range_formatting_edits(&ast, text, &range)
+    }
+
+    fn indent_edit(line: u32, from: u32, to: usize) -> TextEdit {
+        TextEdit {
+            range: Range {
+                start: Position { line, character: 0 },
+                end: Position {
+                    line,
+                    character: from,
+                },
+            },
+            new_text: " ".repeat(to),
+        }
+    }
+
+    #[test]
+    fn test_normalize_list_indentation() {
+        let text = "- a\n   - b\n       - c\n   - d\n- e";
+
+        assert_eq!(
+            format_range(text, 0, 4),
+            vec![
+                indent_edit(1, 3, 2),
+                indent_edit(2, 7, 4),
+                indent_edit(3, 3, 2)
+            ]
+        );
+        // Items outside the range are left alone.
+        assert_eq!(format_range(text, 2, 2), vec![indent_edit(2, 7, 4)]);
+    }
+
+    #[test]
+    fn test_list_indentation_ignores_ordered_lists() {
+        let text = "1. a\n   - b\n- c\n\n  - d";
+
+        assert!(format_range(text, 0, 1).is_empty());
+    }
+
+    #[test]
+    fn test_close_expression_in_attribute() {
+        let position = Position {
+            line: 0,
+            character: 12,
+        };
+
+        assert_eq!(
+            get_edits("<Comp prop={ />", position),
+            closing_brace_at(position)
+        );
+    }
+
+    #[test]
+    fn test_close_expression_at_flow_level() {
+        let position = Position {
+            line: 2,
+            character: 1,
+        };
+
+        assert_eq!(
+            get_edits("# Hello\n\n{\n", position),
+            closing_brace_at(position)
+        );
+    }
+
+    #[test]
+    fn test_no_close_expression_in_code() {
+        let text = "```js\nconst a = {\n```";
+        let position = Position {
+            line: 1,
+            character: 11,
+        };
+
+        assert!(get_edits(text, position).is_empty());
+    }
+
+    #[test]
+    fn test_no_close_expression_in_string() {
+        let position = Position {
+            line: 0,
+            character: 14,
+        };
+
+        assert!(get_edits("<Comp title=\"{\" />", position).is_empty());
+    }
+
+    #[test]
+    fn test_no_close_expression_when_already_closed() {
+        let position = Position {
+            line: 0,
+            character: 1,
+        };
+
+        assert!(get_edits("{}", position).is_empty());
+    }
+
+    #[test]
+    fn test_replacement_edits() {
+        assert!(get_replacement_edits("# Title\n", "# Title\n").is_empty());
+        assert_eq!(
+            get_replacement_edits("#  Café\n\n\n* a", "# Café\n\n- a\n"),
+            vec![TextEdit {
+                range: Range {
+                    start: Position::new(0, 0),
+                    end: Position::new(3, 3),
+                },
+                new_text: "# Café\n\n- a\n".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_run_missing_prettier() {
+        let config = ConfigValues {
+            prettier_path: Some("/nonexistent/prettier".into()),
+            ..Default::default()
+        };
+
+        let err = run_prettier(
+            "# Title",
+            Path::new("/docs/guide.mdx"),
+            &config,
+            PRETTIER_TIMEOUT,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_prettier() {
+        use std::{fs, os::unix::fs::PermissionsExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let prettier = dir.path().join("prettier");
+        fs::write(
+            &prettier,
+            "#!/bin/sh\n[ \"$2\" = mdx ] || exit 2\necho \"<!-- $4 -->\"\ncat\n",
+        )
+        .unwrap();
+        fs::set_permissions(&prettier, fs::Permissions::from_mode(0o755)).unwrap();
+        let config = ConfigValues {
+            prettier_path: Some(prettier),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            run_prettier(
+                "# Title\n",
+                Path::new("/docs/guide.mdx"),
+                &config,
+                PRETTIER_TIMEOUT
+            )
+            .unwrap(),
+            "<!-- /docs/guide.mdx -->\n# Title\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_prettier_timeout() {
+        use std::{fs, os::unix::fs::PermissionsExt};
+
+        let dir = tempfile::tempdir().unwrap();
+        let prettier = dir.path().join("prettier");
+        fs::write(&prettier, "#!/bin/sh\nexec sleep 10\n").unwrap();
+        fs::set_permissions(&prettier, fs::Permissions::from_mode(0o755)).unwrap();
+        let config = ConfigValues {
+            prettier_path: Some(prettier),
+            ..Default::default()
+        };
+
+        let started = Instant::now();
+        let err = run_prettier(
+            "# Title\n",
+            Path::new("/docs/guide.mdx"),
+            &config,
+            Duration::from_millis(100),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    fn get_tag_edits(text: &str, line: u32, character: u32) -> Vec<TextEdit> {
+        let ast = to_mdast(text, &get_parser_options()).ok();
+        debug!("{:#?}", ast);
+
+        get_on_type_edits(ast.as_ref(), text, &Position { line, character }, TAG_CLOSE)
+    }
+
+    #[test]
+    fn test_close_tag() {
+        let position = Position {
+            line: 2,
+            character: 25,
+        };
+
+        assert_eq!(
+            get_tag_edits("# Hello\n\n<$Partial src=\"hero.mdx\">", 2, 25),
+            vec![TextEdit {
+                range: Range {
+                    start: position,
+                    end: position,
+                },
+                new_text: "</$Partial>".to_string(),
+            }]
+        );
+        assert_eq!(
+            get_tag_edits("Say <Tooltip.Trigger>", 0, 21)[0].new_text,
+            "</Tooltip.Trigger>"
+        );
+        assert_eq!(
+            get_tag_edits("<Card title=\"Café\" onClick={() => {}}>", 0, 38)[0].new_text,
+            "</Card>"
+        );
+    }
+
+    #[test]
+    fn test_no_close_tag() {
+        // Self-closing and closing tags
+        assert!(get_tag_edits("<Card />", 0, 8).is_empty());
+        assert!(get_tag_edits("<Card></Card>", 0, 13).is_empty());
+        // Already closed
+        assert!(get_tag_edits("<Card></Card>", 0, 6).is_empty());
+        // Inside an attribute expression or string
+        assert!(get_tag_edits("<Card onClick={() =>", 0, 20).is_empty());
+        assert!(get_tag_edits("<Card title=\"a >", 0, 17).is_empty());
+        // Block quotes, autolinks and code
+        assert!(get_tag_edits(">", 0, 1).is_empty());
+        assert!(get_tag_edits("<https://example.com>", 0, 21).is_empty());
+        assert!(get_tag_edits("Use `<Card>`", 0, 11).is_empty());
+        assert!(get_tag_edits("```jsx\n<Card>\n```", 1, 6).is_empty());
+    }
+}
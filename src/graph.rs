@@ -0,0 +1,273 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use dashmap::DashMap;
+use markdown::{mdast::Node, to_mdast};
+use tokio::fs;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Url};
+
+use crate::{
+    ast::find_all_matches,
+    config::ConfigValues,
+    diagnostics::DIAGNOSTIC_SOURCE,
+    encoding::get_node_range,
+    nodes::{
+        is_configured_partial,
+        partials::{find_matching_partial, get_partial_src, list_partial_files},
+        NodeExt,
+    },
+    parser::get_parser_options_for,
+};
+
+/// Which files include which partials. Edges go from the including file to
+/// the resolved partial file.
+#[derive(Debug, Default)]
+pub struct PartialGraph {
+    edges: HashMap<PathBuf, Vec<PathBuf>>,
+}
+
+impl PartialGraph {
+    /// Builds the graph from the open documents, plus the partial files that
+    /// aren't open, read from disk.
+    pub async fn build(ast_map: &DashMap<String, Node>, config: &ConfigValues) -> Self {
+        let mut graph = Self::from_open_documents(ast_map, config);
+
+        for file in list_partial_files(&config.partials_dirs).await {
+            if graph.edges.contains_key(&file) {
+                continue;
+            }
+            let Ok(text) = fs::read_to_string(&file).await else {
+                continue;
+            };
+            if let Ok(ast) = to_mdast(&text, &get_parser_options_for(&file, config)) {
+                graph.add_document(file, &ast, config);
+            }
+        }
+
+        graph
+    }
+
+    fn from_open_documents(ast_map: &DashMap<String, Node>, config: &ConfigValues) -> Self {
+        let mut graph = Self::default();
+        for entry in ast_map.iter() {
+            let Some(path) = Url::parse(entry.key())
+                .ok()
+                .and_then(|uri| uri.to_file_path().ok())
+            else {
+                continue;
+            };
+            graph.add_document(path, entry.value(), config);
+        }
+        graph
+    }
+
+    /// Sets the edges of the document at `path` to the partials `ast`
+    /// includes, replacing the ones it had.
+    pub fn add_document(&mut self, path: PathBuf, ast: &Node, config: &ConfigValues) {
+        let targets = find_all_matches(ast, |node| is_configured_partial(node, config))
+            .into_iter()
+            .filter_map(|partial| find_matching_partial(partial.get_attribute("src")?, config))
+            .collect();
+        self.edges.insert(path, targets);
+    }
+
+    /// Finds the cycles with a depth-first search. Each cycle starts and ends
+    /// with the same file.
+    pub fn find_cycles(&self) -> Vec<Vec<PathBuf>> {
+        let mut cycles = Vec::new();
+        let mut visited = HashSet::new();
+        let mut stack = Vec::new();
+
+        let mut roots: Vec<_> = self.edges.keys().collect();
+        roots.sort();
+        for root in roots {
+            self.visit(root, &mut visited, &mut stack, &mut cycles);
+        }
+
+        cycles
+    }
+
+    fn visit<'a>(
+        &'a self,
+        path: &'a Path,
+        visited: &mut HashSet<&'a Path>,
+        stack: &mut Vec<&'a Path>,
+        cycles: &mut Vec<Vec<PathBuf>>,
+    ) {
+        if let Some(start) = stack.iter().position(|entry| *entry == path) {
+            let mut cycle: Vec<PathBuf> = stack[start..].iter().map(|p| p.to_path_buf()).collect();
+            cycle.push(path.to_path_buf());
+            cycles.push(cycle);
+            return;
+        }
+        if !visited.insert(path) {
+            return;
+        }
+
+        stack.push(path);
+        for target in self.edges.get(path).into_iter().flatten() {
+            self.visit(target, visited, stack, cycles);
+        }
+        stack.pop();
+    }
+}
+
+/// Warns on each `$Partial` of the document at `path` that continues a cycle,
+/// naming the whole cycle starting from this document.
+pub fn get_cycle_diagnostics(
+    ast: &Node,
+    text: &str,
+    path: &Path,
+    graph: &PartialGraph,
+    config: &ConfigValues,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    for cycle in graph.find_cycles() {
+        let Some(index) = cycle[..cycle.len() - 1]
+            .iter()
+            .position(|entry| entry == path)
+        else {
+            continue;
+        };
+
+        // Rotate the cycle so that it starts and ends with this document.
+        let rotated: Vec<_> = cycle[index..cycle.len() - 1]
+            .iter()
+            .chain(&cycle[..=index])
+            .collect();
+        let next = rotated[1];
+        let message = format!(
+            "Circular partial reference: {}",
+            rotated
+                .iter()
+                .map(|entry| get_partial_src(entry, config))
+                .collect::<Vec<_>>()
+                .join(" → ")
+        );
+
+        for partial in find_all_matches(ast, |node| is_configured_partial(node, config)) {
+            let resolved = partial
+                .get_attribute("src")
+                .and_then(|src| find_matching_partial(src, config));
+            if resolved.as_ref() != Some(next) {
+                continue;
+            }
+            let Some(range) = get_node_range(text, partial) else {
+                continue;
+            };
+
+            diagnostics.push(Diagnostic {
+                range,
+                severity: Some(DiagnosticSeverity::WARNING),
+                source: Some(DIAGNOSTIC_SOURCE.to_string()),
+                message: message.clone(),
+                ..Default::default()
+            });
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    fn create_partials(files: &[(&str, &str)]) -> (tempfile::TempDir, ConfigValues) {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, text) in files {
+            fs::write(dir.path().join(name), text).unwrap();
+        }
+        let config = ConfigValues {
+            partials_dirs: vec![dir.path().to_path_buf()],
+            ..Default::default()
+        };
+        (dir, config)
+    }
+
+    #[tokio::test]
+    async fn test_find_cycles() {
+        let (dir, config) = create_partials(&[
+            ("a.mdx", "<$Partial src=\"b.mdx\" />"),
+            (
+                "b.mdx",
+                "<$Partial src=\"a.mdx\" />\n\n<$Partial src=\"c.mdx\" />",
+            ),
+            ("c.mdx", "# Leaf"),
+        ]);
+
+        let graph = PartialGraph::build(&DashMap::new(), &config).await;
+
+        assert_eq!(
+            graph.find_cycles(),
+            vec![vec![
+                dir.path().join("a.mdx"),
+                dir.path().join("b.mdx"),
+                dir.path().join("a.mdx"),
+            ]]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cycle_diagnostics() {
+        let (dir, config) = create_partials(&[
+            ("a.mdx", "<$Partial src=\"b.mdx\" />"),
+            (
+                "b.mdx",
+                "# B\n\n<$Partial src=\"a.mdx\" />\n\n<$Partial src=\"c.mdx\" />",
+            ),
+            ("c.mdx", "# Leaf"),
+        ]);
+        let graph = PartialGraph::build(&DashMap::new(), &config).await;
+
+        let path = dir.path().join("b.mdx");
+        let text = fs::read_to_string(&path).unwrap();
+        let ast = to_mdast(&text, &get_parser_options()).unwrap();
+        let diagnostics = get_cycle_diagnostics(&ast, &text, &path, &graph, &config);
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Some(DiagnosticSeverity::WARNING));
+        assert_eq!(
+            diagnostics[0].message,
+            "Circular partial reference: b.mdx → a.mdx → b.mdx"
+        );
+        assert_eq!(diagnostics[0].range.start.line, 2);
+    }
+
+    #[tokio::test]
+    async fn test_open_document_overrides_disk() {
+        let (dir, config) = create_partials(&[("a.mdx", "<$Partial src=\"a.mdx\" />")]);
+        let ast_map = DashMap::new();
+        ast_map.insert(
+            Url::from_file_path(dir.path().join("a.mdx"))
+                .unwrap()
+                .to_string(),
+            to_mdast("# Fixed", &get_parser_options()).unwrap(),
+        );
+
+        let graph = PartialGraph::build(&ast_map, &config).await;
+
+        assert!(graph.find_cycles().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_add_document_replaces_edges() {
+        let (dir, config) = create_partials(&[
+            ("a.mdx", "<$Partial src=\"b.mdx\" />"),
+            ("b.mdx", "<$Partial src=\"a.mdx\" />"),
+        ]);
+        let mut graph = PartialGraph::build(&DashMap::new(), &config).await;
+        assert_eq!(graph.find_cycles().len(), 1);
+
+        let ast = to_mdast("# Fixed", &get_parser_options()).unwrap();
+        graph.add_document(dir.path().join("a.mdx"), &ast, &config);
+
+        assert!(graph.find_cycles().is_empty());
+    }
+}
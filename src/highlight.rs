@@ -0,0 +1,115 @@
+use markdown::mdast::Node;
+use tower_lsp::lsp_types::{DocumentHighlight, DocumentHighlightKind, Position};
+
+use crate::{
+    ast::{find_all_matches, find_deepest_match, get_ancestor_chain},
+    config::ConfigValues,
+    encoding::get_node_range,
+    nodes::{is_configured_partial, partials::find_matching_partial, NodeExt},
+    rename::{get_src_range, prepare_partial_rename},
+};
+
+/// Highlights the `$Partial` elements of the document that include the same
+/// file as the one under the cursor. With the cursor on the `src` value, the
+/// `src` values spelled the same way are highlighted too, resolved or not.
+pub fn get_document_highlights(
+    ast: &Node,
+    text: &str,
+    position: &Position,
+    config: &ConfigValues,
+) -> Option<Vec<DocumentHighlight>> {
+    let ancestor_chain = get_ancestor_chain(ast, text, position);
+    let partial = find_deepest_match(&ancestor_chain, |node| is_configured_partial(node, config))?;
+    let src = partial.get_attribute("src")?;
+    let path = find_matching_partial(src, config);
+    let on_src = prepare_partial_rename(ast, text, position, config).is_some();
+
+    let mut highlights = Vec::new();
+    for other in find_all_matches(ast, |node| is_configured_partial(node, config)) {
+        let Some(other_src) = other.get_attribute("src") else {
+            continue;
+        };
+
+        let ranges = [
+            path.as_ref()
+                .filter(|path| find_matching_partial(other_src, config).as_ref() == Some(path))
+                .and_then(|_| get_node_range(text, other)),
+            (on_src && other_src == src)
+                .then(|| get_src_range(other, text))
+                .flatten(),
+        ];
+        highlights.extend(ranges.into_iter().flatten().map(|range| DocumentHighlight {
+            range,
+            kind: Some(DocumentHighlightKind::READ),
+        }));
+    }
+
+    (!highlights.is_empty()).then_some(highlights)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use log::debug;
+    use markdown::to_mdast;
+    use tower_lsp::lsp_types::Range;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    const TEXT: &str = "<$Partial src=\"hero.mdx\" />\n\n<$Partial src=\"hero\" />\n\n<$Partial src=\"footer.mdx\" />\n\n<$Partial src=\"missing.mdx\" />\n\n<$Partial src=\"missing.mdx\" />";
+
+    fn get_highlights(position: Position) -> Option<Vec<Range>> {
+        let partials_dir = tempfile::tempdir().unwrap();
+        fs::write(partials_dir.path().join("hero.mdx"), "# Hero").unwrap();
+        fs::write(partials_dir.path().join("footer.mdx"), "Footer").unwrap();
+        let config = ConfigValues {
+            partials_dirs: vec![partials_dir.path().to_path_buf()],
+            ..Default::default()
+        };
+
+        let ast = to_mdast(TEXT, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        get_document_highlights(&ast, TEXT, &position, &config).map(|highlights| {
+            highlights
+                .into_iter()
+                .map(|highlight| highlight.range)
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_highlight_partials_of_same_file() {
+        assert_eq!(
+            get_highlights(Position::new(2, 3)),
+            Some(vec![
+                Range::new(Position::new(0, 0), Position::new(0, 27)),
+                Range::new(Position::new(2, 0), Position::new(2, 23)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_highlight_same_src() {
+        assert_eq!(
+            get_highlights(Position::new(0, 17)),
+            Some(vec![
+                Range::new(Position::new(0, 0), Position::new(0, 27)),
+                Range::new(Position::new(0, 15), Position::new(0, 23)),
+                Range::new(Position::new(2, 0), Position::new(2, 23)),
+            ])
+        );
+
+        // Unresolved partials are only highlighted by their `src`.
+        assert_eq!(get_highlights(Position::new(6, 3)), None);
+        assert_eq!(
+            get_highlights(Position::new(8, 17)),
+            Some(vec![
+                Range::new(Position::new(6, 15), Position::new(6, 26)),
+                Range::new(Position::new(8, 15), Position::new(8, 26)),
+            ])
+        );
+    }
+}
@@ -0,0 +1,837 @@
+use std::{fs, path::Path};
+
+use dashmap::DashMap;
+use markdown::{mdast::Node, to_mdast};
+use tower_lsp::lsp_types::{Hover, HoverContents, MarkupContent, MarkupKind, Position, Url};
+
+use crate::{
+    ast::{
+        extract_exports, find_all_matches, find_deepest_match, find_heading_chain,
+        get_ancestor_chain,
+    },
+    commands::format_size,
+    config::ConfigValues,
+    definition::{get_import_at, resolve_import},
+    encoding::get_node_range,
+    nodes::{
+        esm::get_import_source,
+        frontmatter::get_frontmatter_value,
+        headings::{get_heading_slugs, get_section},
+        is_configured_partial,
+        partials::find_matching_partial,
+        NodeExt,
+    },
+    parser::get_parser_options,
+    workspace::normalize_path,
+};
+
+pub fn get_hover(
+    ast: &Node,
+    text: &str,
+    uri: &Url,
+    position: &Position,
+    ast_map: &DashMap<String, Node>,
+    config: &ConfigValues,
+) -> Option<Hover> {
+    if let Some((source, range)) = get_import_at(ast, text, position) {
+        if range.start <= *position && *position <= range.end {
+            return Some(Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: get_import_hover(&source, uri, ast_map, config),
+                }),
+                range: Some(range),
+            });
+        }
+    }
+
+    let ancestor_chain = get_ancestor_chain(ast, text, position);
+    let element = find_deepest_match(&ancestor_chain, |node| {
+        node.is_any_jsx_element()
+            || matches!(
+                node,
+                Node::Link(_) | Node::Image(_) | Node::Heading(_) | Node::Html(_)
+            )
+            || node.is_comment_expression()
+    })?;
+
+    // Links describe their destination, images their size and alt text,
+    // headings their section, raw HTML gets a deprecation warning, comment
+    // expressions are flagged as not rendered, partials get their own hover,
+    // and other elements are described by whether they wrap any content.
+    let value = match element {
+        Node::Link(link) => hover_for_link(&link.url, ast, uri, ast_map)?,
+        Node::Image(image) => get_image_hover(&image.url, &image.alt, uri, config),
+        Node::Heading(_) => get_section_hover(ast, text, position)?,
+        Node::Html(html) => get_html_hover(ast, &html.value)?,
+        Node::MdxFlowExpression(_) | Node::MdxTextExpression(_) => {
+            "MDX comment expression — this content is not rendered".to_string()
+        }
+        _ if is_on_tag_name(element, text, position) => get_tag_name_hover(ast, element, config),
+        _ if is_configured_partial(element, config) => get_partial_hover(element, config),
+        _ => match element.children().map(|children| children.len()) {
+            Some(count) if count > 0 => format!("JSX element with {} child nodes", count),
+            _ => "Self-closing JSX element".to_string(),
+        },
+    };
+
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: get_node_range(text, element),
+    })
+}
+
+/// Checks whether the cursor is on the name in the opening tag of a JSX
+/// element.
+fn is_on_tag_name(element: &Node, text: &str, position: &Position) -> bool {
+    let (Some(name), Some(range)) = (element.get_name(), get_node_range(text, element)) else {
+        return false;
+    };
+    let start = range.start.character + 1;
+
+    position.line == range.start.line
+        && (start..=start + name.encode_utf16().count() as u32).contains(&position.character)
+}
+
+/// Tells where the component comes from: `$Partial` is built in, other
+/// components are looked up in the document's imports.
+fn get_tag_name_hover(ast: &Node, element: &Node, config: &ConfigValues) -> String {
+    if is_configured_partial(element, config) {
+        return format!(
+            "{} is a built-in supermdx construct\n\n{}",
+            element
+                .get_name()
+                .unwrap_or(config.get_partial_element_name()),
+            get_partial_hover(element, config)
+        );
+    }
+
+    match element
+        .get_name()
+        .and_then(|name| get_import_source(ast, name))
+    {
+        Some(source) => format!("Imported from '{}'", source),
+        None => "Not found in imports (may be a global)".to_string(),
+    }
+}
+
+const MDX_MIGRATION_URL: &str = "https://mdxjs.com/migrating/v2/";
+
+/// Warns about raw HTML, which MDX v2 doesn't support, unless the frontmatter
+/// opts into `mdx_v1_compat`.
+fn get_html_hover(ast: &Node, html: &str) -> Option<String> {
+    if get_frontmatter_value(ast, "mdx_v1_compat").as_deref() == Some("true") {
+        return None;
+    }
+
+    Some(format!(
+        "Raw HTML is disabled in MDX v2. Use JSX syntax instead.\n\n```mdx\n{}\n```\n\nSee the [MDX migration guide]({}).",
+        get_jsx_equivalent(html),
+        MDX_MIGRATION_URL
+    ))
+}
+
+/// Rewrites the most common HTML that isn't valid JSX: comments, unclosed void
+/// elements and `class`/`for` attributes.
+fn get_jsx_equivalent(html: &str) -> String {
+    const VOID_ELEMENTS: [&str; 6] = ["br", "hr", "img", "input", "meta", "link"];
+
+    let html = html.trim();
+    if let Some(comment) = html
+        .strip_prefix("<!--")
+        .and_then(|html| html.strip_suffix("-->"))
+    {
+        return format!("{{/* {} */}}", comment.trim());
+    }
+
+    let mut jsx = html
+        .replace(" class=", " className=")
+        .replace(" for=", " htmlFor=");
+    let name: String = jsx
+        .trim_start_matches('<')
+        .chars()
+        .take_while(|ch| ch.is_alphanumeric())
+        .collect();
+    if VOID_ELEMENTS.contains(&name.to_lowercase().as_str()) && !jsx.ends_with("/>") {
+        if let Some(open) = jsx.strip_suffix('>') {
+            jsx = format!("{} />", open.trim_end());
+        }
+    }
+    jsx
+}
+
+fn get_section_hover(ast: &Node, text: &str, position: &Position) -> Option<String> {
+    let heading = *find_heading_chain(ast, text, position).last()?;
+    let words: usize = get_section(ast, heading)
+        .iter()
+        .map(|node| node.text_content().split_whitespace().count())
+        .sum();
+
+    let unit = if words == 1 { "word" } else { "words" };
+    Some(format!("Section word count: {} {}", words, unit))
+}
+
+/// Describes a link destination: relative paths are resolved against the
+/// document, web URLs are shortened to their domain and path, and fragments
+/// are matched against the document's headings.
+fn hover_for_link(
+    url: &str,
+    ast: &Node,
+    uri: &Url,
+    ast_map: &DashMap<String, Node>,
+) -> Option<String> {
+    if let Some(fragment) = url.strip_prefix('#') {
+        return Some(
+            match get_heading_slugs(ast)
+                .into_iter()
+                .find(|(slug, _)| slug == fragment)
+            {
+                Some((_, heading)) => format!("Heading `{}`", heading.to_string()),
+                None => format!("No heading matches `#{}`", fragment),
+            },
+        );
+    }
+
+    if url.starts_with("http://") || url.starts_with("https://") {
+        let url = Url::parse(url).ok()?;
+        return Some(format!("{}{}", url.host_str()?, url.path()));
+    }
+
+    if url.starts_with("./") || url.starts_with("../") {
+        let document_dir = uri.to_file_path().ok()?.parent()?.to_path_buf();
+        let relative = url.split(['#', '?']).next().unwrap_or(url);
+        return Some(get_file_link_hover(
+            &normalize_path(&document_dir.join(relative)),
+            ast_map,
+        ));
+    }
+
+    None
+}
+
+/// Shows the file an import resolves to and, when it's an open document, the
+/// names it exports.
+fn get_import_hover(
+    source: &str,
+    uri: &Url,
+    ast_map: &DashMap<String, Node>,
+    config: &ConfigValues,
+) -> String {
+    let Some(path) = resolve_import(source, uri, config) else {
+        return format!("`{}`\n\nFile not found", source);
+    };
+
+    let mut value = format!("`{}`", path.display());
+    let exports = Url::from_file_path(&path)
+        .ok()
+        .and_then(|target| ast_map.get(&target.to_string()))
+        .map(|target| extract_exports(&target));
+    match exports {
+        Some(exports) if exports.is_empty() => value.push_str("\n\nNo exports"),
+        Some(exports) => {
+            value.push_str("\n\nExports:");
+            for name in exports {
+                value.push_str(&format!("\n- `{}`", name));
+            }
+        }
+        None => {}
+    }
+    value
+}
+
+/// Reads the dimensions of local images from their header and flags missing
+/// alt text.
+fn get_image_hover(url: &str, alt: &str, uri: &Url, config: &ConfigValues) -> String {
+    let path = if Url::parse(url).is_ok() {
+        None
+    } else if let Some(absolute) = url.strip_prefix('/') {
+        Some(config.workspace_root.join(absolute))
+    } else {
+        uri.to_file_path()
+            .ok()
+            .and_then(|path| Some(path.parent()?.join(url)))
+    };
+
+    let image = match path.map(|path| imagesize::size(normalize_path(&path))) {
+        Some(Ok(size)) => format!("Image: {}×{} px", size.width, size.height),
+        Some(Err(_)) => "Image: file not found".to_string(),
+        None => "Image".to_string(),
+    };
+
+    if alt.trim().is_empty() {
+        format!("{} | Missing alt text (accessibility issue)", image)
+    } else {
+        format!("{} | Alt: '{}'", image, alt)
+    }
+}
+
+fn get_file_link_hover(path: &Path, ast_map: &DashMap<String, Node>) -> String {
+    let Ok(metadata) = fs::metadata(path) else {
+        return format!("`{}`\n\nFile not found", path.display());
+    };
+
+    let mut value = format!(
+        "`{}`\n\nSize: {}",
+        path.display(),
+        format_size(metadata.len())
+    );
+    if let Some(heading) = get_first_heading(path, ast_map) {
+        value.push_str(&format!("\n\nFirst heading: {}", heading));
+    }
+    value
+}
+
+/// Reads the first heading from the open document when there is one, falling
+/// back to parsing the file on disk.
+fn get_first_heading(path: &Path, ast_map: &DashMap<String, Node>) -> Option<String> {
+    let first_heading = |ast: &Node| {
+        find_all_matches(ast, |node| matches!(node, Node::Heading(_)))
+            .first()
+            .map(|heading| heading.to_string())
+    };
+
+    let uri = Url::from_file_path(path).ok()?;
+    if let Some(ast) = ast_map.get(&uri.to_string()) {
+        return first_heading(&ast);
+    }
+
+    let text = fs::read_to_string(path).ok()?;
+    let ast = to_mdast(&text, &get_parser_options()).ok()?;
+    first_heading(&ast)
+}
+
+/// Shows where a partial resolves to and a preview of its first
+/// `partial_preview_lines` lines.
+fn get_partial_hover(element: &Node, config: &ConfigValues) -> String {
+    let Some(src) = element.get_attribute("src") else {
+        return "Partial without `src`".to_string();
+    };
+
+    let Some(path) = find_matching_partial(src, config) else {
+        return format!("Partial `{}`\n\nFile not found", src);
+    };
+
+    let mut value = format!("Partial `{}`\n\n{}", src, path.display());
+    if let Ok(text) = fs::read_to_string(&path) {
+        let preview: Vec<_> = text.lines().take(config.partial_preview_lines).collect();
+        value.push_str(&format!("\n\n```mdx\n{}\n```", preview.join("\n")));
+        if text.lines().count() > config.partial_preview_lines {
+            value.push_str("\n\n…");
+        }
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use log::debug;
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    fn get_hover_value(text: &str, position: Position) -> Option<String> {
+        get_hover_value_at(text, position, Path::new("/docs/index.mdx"))
+    }
+
+    fn get_hover_value_at(text: &str, position: Position, path: &Path) -> Option<String> {
+        let text = text.trim();
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let uri = Url::from_file_path(path).unwrap();
+        get_hover(
+            &ast,
+            text,
+            &uri,
+            &position,
+            &DashMap::new(),
+            &ConfigValues::default(),
+        )
+        .map(|hover| match hover.contents {
+            HoverContents::Markup(content) => content.value,
+            _ => unreachable!(),
+        })
+    }
+
+    #[test]
+    fn test_hover_paired_element() {
+        let value = get_hover_value(
+            r#"
+<Callout>
+  Some text.
+
+  More text.
+</Callout>
+"#,
+            Position {
+                line: 0,
+                character: 0,
+            },
+        );
+
+        assert_eq!(value.as_deref(), Some("JSX element with 2 child nodes"));
+    }
+
+    #[test]
+    fn test_hover_self_closing_element() {
+        let value = get_hover_value(
+            r#"
+<Divider />
+"#,
+            Position {
+                line: 0,
+                character: 0,
+            },
+        );
+
+        assert_eq!(value.as_deref(), Some("Self-closing JSX element"));
+    }
+
+    #[test]
+    fn test_hover_partial() {
+        let value = get_hover_value(
+            r#"
+<$Partial src="missing.mdx">
+  Fallback content.
+</$Partial>
+"#,
+            Position {
+                line: 0,
+                character: 0,
+            },
+        );
+
+        assert_eq!(
+            value.as_deref(),
+            Some("Partial `missing.mdx`\n\nFile not found")
+        );
+    }
+
+    #[test]
+    fn test_hover_partial_preview() {
+        let partials_dir = tempfile::tempdir().unwrap();
+        let path = partials_dir.path().join("hero.mdx");
+        fs::write(&path, "# Hero\n\nWelcome.\n\nMore.").unwrap();
+
+        let text = r#"<$Partial src="hero.mdx" />"#;
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        let config = ConfigValues {
+            partials_dirs: vec![partials_dir.path().to_path_buf()],
+            partial_preview_lines: 3,
+            ..Default::default()
+        };
+        let hover = get_hover(
+            &ast,
+            text,
+            &Url::from_file_path("/docs/index.mdx").unwrap(),
+            &Position {
+                line: 0,
+                character: 0,
+            },
+            &DashMap::new(),
+            &config,
+        )
+        .unwrap();
+
+        assert_eq!(
+            hover.contents,
+            HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value: format!(
+                    "Partial `hero.mdx`\n\n{}\n\n```mdx\n# Hero\n\nWelcome.\n```\n\n…",
+                    path.display()
+                ),
+            })
+        );
+    }
+
+    #[test]
+    fn test_hover_tag_name() {
+        let text = "import Button from './components/Button.mdx'\n\n<Button>Go</Button>\n\n<Badge />\n\n<$Partial src=\"missing.mdx\" />";
+
+        let value = get_hover_value(
+            text,
+            Position {
+                line: 2,
+                character: 3,
+            },
+        );
+        assert_eq!(
+            value.as_deref(),
+            Some("Imported from './components/Button.mdx'")
+        );
+
+        let value = get_hover_value(
+            text,
+            Position {
+                line: 4,
+                character: 2,
+            },
+        );
+        assert_eq!(
+            value.as_deref(),
+            Some("Not found in imports (may be a global)")
+        );
+
+        let value = get_hover_value(
+            text,
+            Position {
+                line: 6,
+                character: 4,
+            },
+        );
+        assert_eq!(
+            value.as_deref(),
+            Some("$Partial is a built-in supermdx construct\n\nPartial `missing.mdx`\n\nFile not found")
+        );
+    }
+
+    #[test]
+    fn test_hover_outside_jsx() {
+        let value = get_hover_value(
+            r#"
+Hello World
+"#,
+            Position {
+                line: 0,
+                character: 3,
+            },
+        );
+
+        assert_eq!(value, None);
+    }
+
+    #[test]
+    fn test_hover_section_word_count() {
+        let text = r#"
+# Guide
+
+Intro text.
+
+## Setup
+
+Install the *package* first.
+
+### Details
+
+Two words.
+
+## Usage
+"#;
+
+        let value = get_hover_value(
+            text,
+            Position {
+                line: 4,
+                character: 4,
+            },
+        );
+        assert_eq!(value.as_deref(), Some("Section word count: 8 words"));
+
+        let value = get_hover_value(
+            text,
+            Position {
+                line: 12,
+                character: 4,
+            },
+        );
+        assert_eq!(value.as_deref(), Some("Section word count: 1 word"));
+    }
+
+    fn get_html_hover_value(text: &str, position: Position) -> Option<String> {
+        // Raw HTML only reaches the AST when the HTML constructs are enabled.
+        let mut options = get_parser_options();
+        options.constructs.html_flow = true;
+        options.constructs.html_text = true;
+        options.constructs.mdx_jsx_flow = false;
+        options.constructs.mdx_jsx_text = false;
+        let text = text.trim();
+        let ast = to_mdast(text, &options).unwrap();
+        debug!("{:#?}", ast);
+
+        let uri = Url::from_file_path("/docs/index.mdx").unwrap();
+        get_hover(
+            &ast,
+            text,
+            &uri,
+            &position,
+            &DashMap::new(),
+            &ConfigValues::default(),
+        )
+        .map(|hover| match hover.contents {
+            HoverContents::Markup(content) => content.value,
+            _ => unreachable!(),
+        })
+    }
+
+    #[test]
+    fn test_hover_html() {
+        let value = get_html_hover_value(
+            r#"
+Some text.
+
+<img src="logo.png" class="logo">
+"#,
+            Position {
+                line: 2,
+                character: 3,
+            },
+        );
+
+        assert_eq!(
+            value.as_deref(),
+            Some(
+                "Raw HTML is disabled in MDX v2. Use JSX syntax instead.\n\n```mdx\n<img src=\"logo.png\" className=\"logo\" />\n```\n\nSee the [MDX migration guide](https://mdxjs.com/migrating/v2/)."
+            )
+        );
+    }
+
+    #[test]
+    fn test_hover_html_v1_compat() {
+        let value = get_html_hover_value(
+            r#"
+---
+mdx_v1_compat: true
+---
+
+<br>
+"#,
+            Position {
+                line: 4,
+                character: 2,
+            },
+        );
+
+        assert_eq!(value, None);
+    }
+
+    #[test]
+    fn test_hover_comment_expression() {
+        let value = get_hover_value(
+            "{/* This is a comment */}\n\nCount: {count}",
+            Position {
+                line: 0,
+                character: 5,
+            },
+        );
+        assert_eq!(
+            value.as_deref(),
+            Some("MDX comment expression — this content is not rendered")
+        );
+
+        let value = get_hover_value(
+            "{/* This is a comment */}\n\n<Badge>{count}</Badge>",
+            Position {
+                line: 2,
+                character: 9,
+            },
+        );
+        assert_eq!(value.as_deref(), Some("JSX element with 1 child nodes"));
+    }
+
+    #[test]
+    fn test_get_jsx_equivalent() {
+        assert_eq!(get_jsx_equivalent("<br>"), "<br />");
+        assert_eq!(get_jsx_equivalent("<!-- note -->"), "{/* note */}");
+        assert_eq!(
+            get_jsx_equivalent("<label for=\"a\">"),
+            "<label htmlFor=\"a\">"
+        );
+    }
+
+    #[test]
+    fn test_hover_fragment_link() {
+        let value = get_hover_value(
+            r#"
+# Getting Started
+
+See [above](#getting-started) or [nowhere](#missing).
+"#,
+            Position {
+                line: 2,
+                character: 6,
+            },
+        );
+        assert_eq!(value.as_deref(), Some("Heading `Getting Started`"));
+
+        let value = get_hover_value(
+            r#"
+# Getting Started
+
+See [above](#getting-started) or [nowhere](#missing).
+"#,
+            Position {
+                line: 2,
+                character: 35,
+            },
+        );
+        assert_eq!(value.as_deref(), Some("No heading matches `#missing`"));
+    }
+
+    #[test]
+    fn test_hover_web_link() {
+        let value = get_hover_value(
+            "[docs](https://example.com/docs/intro?tab=1)",
+            Position {
+                line: 0,
+                character: 2,
+            },
+        );
+
+        assert_eq!(value.as_deref(), Some("example.com/docs/intro"));
+    }
+
+    #[test]
+    fn test_hover_relative_link() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("guides")).unwrap();
+        fs::write(dir.path().join("setup.mdx"), "Intro\n\n## Setup\n").unwrap();
+
+        let text = "[setup](../setup.mdx#setup) and [missing](./missing.mdx)";
+        let document = dir.path().join("guides/index.mdx");
+
+        let value = get_hover_value_at(
+            text,
+            Position {
+                line: 0,
+                character: 2,
+            },
+            &document,
+        );
+        assert_eq!(
+            value,
+            Some(format!(
+                "`{}`\n\nSize: 16 B\n\nFirst heading: Setup",
+                dir.path().join("setup.mdx").display()
+            ))
+        );
+
+        let value = get_hover_value_at(
+            text,
+            Position {
+                line: 0,
+                character: 34,
+            },
+            &document,
+        );
+        assert_eq!(
+            value,
+            Some(format!(
+                "`{}`\n\nFile not found",
+                dir.path().join("guides/missing.mdx").display()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_hover_import() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("card.js"), "").unwrap();
+        fs::write(dir.path().join("shared.mdx"), "").unwrap();
+
+        let shared = Url::from_file_path(dir.path().join("shared.mdx")).unwrap();
+        let ast_map = DashMap::new();
+        ast_map.insert(
+            shared.to_string(),
+            to_mdast(
+                "export const meta = {}\nexport function Note() {}\n\n# Shared",
+                &get_parser_options(),
+            )
+            .unwrap(),
+        );
+
+        let text = "import Card from './card'\nimport { Note } from \"./shared.mdx\"";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+        let uri = Url::from_file_path(dir.path().join("index.mdx")).unwrap();
+        let hover_value = |line, character| {
+            get_hover(
+                &ast,
+                text,
+                &uri,
+                &Position { line, character },
+                &ast_map,
+                &ConfigValues::default(),
+            )
+            .map(|hover| match hover.contents {
+                HoverContents::Markup(content) => content.value,
+                _ => unreachable!(),
+            })
+        };
+
+        assert_eq!(
+            hover_value(0, 20),
+            Some(format!("`{}`", dir.path().join("card.js").display()))
+        );
+        assert_eq!(
+            hover_value(1, 25),
+            Some(format!(
+                "`{}`\n\nExports:\n- `meta`\n- `Note`",
+                dir.path().join("shared.mdx").display()
+            ))
+        );
+        assert_eq!(hover_value(0, 8), None);
+    }
+
+    /// The signature and `IHDR` chunk of a PNG, which is all that's needed to
+    /// read its size.
+    fn png_header(width: u32, height: u32) -> Vec<u8> {
+        let mut header = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+        header.extend(width.to_be_bytes());
+        header.extend(height.to_be_bytes());
+        header.extend([8, 6, 0, 0, 0, 0, 0, 0, 0]);
+        header
+    }
+
+    #[test]
+    fn test_hover_image() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("dashboard.png"), png_header(800, 600)).unwrap();
+
+        let text = "![A screenshot of the dashboard](./dashboard.png)\n\n![](./missing.png)";
+        let document = dir.path().join("index.mdx");
+
+        let value = get_hover_value_at(
+            text,
+            Position {
+                line: 0,
+                character: 3,
+            },
+            &document,
+        );
+        assert_eq!(
+            value.as_deref(),
+            Some("Image: 800×600 px | Alt: 'A screenshot of the dashboard'")
+        );
+
+        let value = get_hover_value_at(
+            text,
+            Position {
+                line: 2,
+                character: 3,
+            },
+            &document,
+        );
+        assert_eq!(
+            value.as_deref(),
+            Some("Image: file not found | Missing alt text (accessibility issue)")
+        );
+    }
+
+    #[test]
+    fn test_hover_remote_image() {
+        let value = get_hover_value(
+            "![Logo](https://example.com/logo.png)",
+            Position {
+                line: 0,
+                character: 3,
+            },
+        );
+
+        assert_eq!(value.as_deref(), Some("Image | Alt: 'Logo'"));
+    }
+}
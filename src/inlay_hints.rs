@@ -0,0 +1,111 @@
+use markdown::mdast::Node;
+use tower_lsp::lsp_types::{InlayHint, InlayHintLabel, InlayHintTooltip, Range};
+
+use crate::{
+    ast::find_all_matches,
+    config::ConfigValues,
+    encoding::get_node_range,
+    nodes::{is_configured_partial, partials::find_matching_partial, NodeExt},
+};
+
+/// Returns a hint after each partial in `range` with the file its `src`
+/// resolves to, relative to the workspace root, or a warning when it doesn't
+/// resolve.
+pub fn get_inlay_hints(
+    ast: &Node,
+    text: &str,
+    range: &Range,
+    config: &ConfigValues,
+) -> Vec<InlayHint> {
+    find_all_matches(ast, |node| is_configured_partial(node, config))
+        .into_iter()
+        .filter_map(|partial| {
+            let partial_range = get_node_range(text, partial)?;
+            if partial_range.end < range.start || partial_range.start > range.end {
+                return None;
+            }
+            let src = partial.get_attribute("src")?;
+
+            let (label, tooltip) = match find_matching_partial(src, config) {
+                Some(path) => {
+                    let path = path.strip_prefix(&config.workspace_root).unwrap_or(&path);
+                    (format!("→ {}", path.display()), None)
+                }
+                None => (
+                    "⚠ not found".to_string(),
+                    Some(InlayHintTooltip::String(format!(
+                        "No partial file matches \"{}\"",
+                        src
+                    ))),
+                ),
+            };
+
+            Some(InlayHint {
+                position: partial_range.end,
+                label: InlayHintLabel::String(label),
+                kind: None,
+                text_edits: None,
+                tooltip,
+                padding_left: Some(true),
+                padding_right: None,
+                data: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use log::debug;
+    use markdown::to_mdast;
+    use tower_lsp::lsp_types::Position;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    #[test]
+    fn test_get_inlay_hints() {
+        let workspace = tempfile::tempdir().unwrap();
+        let partials_dir = workspace.path().join("partials");
+        std::fs::create_dir(&partials_dir).unwrap();
+        std::fs::write(partials_dir.join("hero.mdx"), "# Hero").unwrap();
+        let config = ConfigValues {
+            workspace_root: workspace.path().to_path_buf(),
+            partials_dirs: vec![partials_dir],
+            ..Default::default()
+        };
+
+        let text = "<$Partial src=\"hero\" />\n\n<$Partial src=\"missing.mdx\" />\n\n<$Partial src=\"hero.mdx\" />";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let hints: Vec<_> = get_inlay_hints(
+            &ast,
+            text,
+            &Range::new(Position::new(0, 0), Position::new(2, 0)),
+            &config,
+        )
+        .into_iter()
+        .map(|hint| {
+            let InlayHintLabel::String(label) = hint.label else {
+                panic!("expected a string label");
+            };
+            (hint.position, label, hint.tooltip.is_some())
+        })
+        .collect();
+        assert_eq!(
+            hints,
+            vec![
+                (
+                    Position::new(0, 23),
+                    format!(
+                        "→ {}",
+                        std::path::Path::new("partials").join("hero.mdx").display()
+                    ),
+                    false
+                ),
+                (Position::new(2, 30), "⚠ not found".to_string(), true),
+            ]
+        );
+    }
+}
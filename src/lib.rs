@@ -0,0 +1,42 @@
+pub mod ast;
+pub mod call_hierarchy;
+pub mod code_actions;
+pub mod code_lens;
+pub mod commands;
+pub mod completion;
+pub mod config;
+pub mod definition;
+pub mod diagnostics;
+pub mod document;
+pub mod encoding;
+pub mod folding;
+pub mod formatting;
+pub mod graph;
+pub mod highlight;
+pub mod hover;
+pub mod inlay_hints;
+pub mod links;
+pub mod logging;
+pub mod nodes;
+pub mod parser;
+pub mod progress;
+pub mod references;
+pub mod rename;
+pub mod selection;
+pub mod semantic_tokens;
+pub mod signature_help;
+pub mod spelling;
+pub mod symbols;
+pub mod workspace;
+
+#[cfg(test)]
+use ctor::ctor;
+
+#[cfg(test)]
+#[ctor]
+fn init_test_logger() {
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+        .with_test_writer()
+        .init();
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use markdown::mdast::Node;
+use tower_lsp::lsp_types::{DocumentLink, Url};
+
+use crate::{ast::find_all_matches, encoding::get_node_range};
+
+/// Returns a link for each markdown link and image of the document, including
+/// reference-style ones, whose URL is on the web or relative to the document.
+pub fn get_document_links(ast: &Node, text: &str, uri: &Url) -> Vec<DocumentLink> {
+    let definitions: HashMap<&str, &str> =
+        find_all_matches(ast, |node| matches!(node, Node::Definition(_)))
+            .into_iter()
+            .filter_map(|node| match node {
+                Node::Definition(definition) => {
+                    Some((definition.identifier.as_str(), definition.url.as_str()))
+                }
+                _ => None,
+            })
+            .collect();
+
+    find_all_matches(ast, |node| {
+        matches!(
+            node,
+            Node::Link(_) | Node::LinkReference(_) | Node::Image(_) | Node::ImageReference(_)
+        )
+    })
+    .into_iter()
+    .filter_map(|node| {
+        let url = match node {
+            Node::Link(link) => link.url.as_str(),
+            Node::Image(image) => image.url.as_str(),
+            Node::LinkReference(reference) => definitions.get(reference.identifier.as_str())?,
+            Node::ImageReference(reference) => definitions.get(reference.identifier.as_str())?,
+            _ => return None,
+        };
+
+        Some(DocumentLink {
+            range: get_node_range(text, node)?,
+            target: Some(resolve_link(url, uri)?),
+            tooltip: None,
+            data: None,
+        })
+    })
+    .collect()
+}
+
+/// Resolves the URL of a link: web URLs as they are, and URLs without a
+/// scheme relative to the document. Other schemes, like `mailto:`, are left to
+/// the editor.
+pub fn resolve_link(url: &str, document_uri: &Url) -> Option<Url> {
+    if url.is_empty() {
+        return None;
+    }
+
+    match Url::parse(url) {
+        Ok(url) => matches!(url.scheme(), "http" | "https").then_some(url),
+        Err(_) => document_uri.join(url).ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::debug;
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    #[test]
+    fn test_get_document_links() {
+        let text = r#"
+See [the guide](./guide.mdx#setup), [the site](https://example.com) and
+[mail](mailto:team@example.com).
+
+![Logo](../images/logo.png)
+
+Read [the reference][ref] and ![the diagram][diagram].
+
+[ref]: reference.mdx
+[diagram]: https://example.com/diagram.png
+"#
+        .trim();
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let uri = Url::parse("file:///docs/start/index.mdx").unwrap();
+        let links: Vec<_> = get_document_links(&ast, text, &uri)
+            .into_iter()
+            .map(|link| (link.range.start.line, link.target.unwrap().to_string()))
+            .collect();
+        assert_eq!(
+            links,
+            vec![
+                (0, "file:///docs/start/guide.mdx#setup".to_string()),
+                (0, "https://example.com/".to_string()),
+                (3, "file:///docs/images/logo.png".to_string()),
+                (5, "file:///docs/start/reference.mdx".to_string()),
+                (5, "https://example.com/diagram.png".to_string()),
+            ]
+        );
+    }
+}
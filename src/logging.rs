@@ -0,0 +1,54 @@
+use std::sync::OnceLock;
+
+use tracing_log::LogTracer;
+use tracing_subscriber::{fmt, layer::SubscriberExt, reload, EnvFilter, Registry};
+
+/// Swaps the filter when the `log_level` config changes.
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Sends `tracing` events and spans, along with `log` records, to stderr,
+/// since stdout carries the protocol. Logs are filtered at `info` until the
+/// config sets a level with [`set_level`]. Debug builds write JSON lines for
+/// log analysis tools to consume.
+pub fn init() {
+    if let Err(err) = LogTracer::init() {
+        eprintln!("Failed to forward log records to tracing: {}", err);
+    }
+
+    let (filter, handle) = reload::Layer::new(get_filter(None));
+    let json = cfg!(debug_assertions);
+    let subscriber = Registry::default()
+        .with(filter)
+        .with(json.then(|| fmt::layer().json().with_writer(std::io::stderr)))
+        .with((!json).then(|| fmt::layer().with_writer(std::io::stderr).with_ansi(false)));
+    if let Err(err) = tracing::subscriber::set_global_default(subscriber) {
+        eprintln!("Failed to set up logging: {}", err);
+        return;
+    }
+    let _ = FILTER_HANDLE.set(handle);
+}
+
+/// Sets the minimum level of the logs, `info` when `None`. Does nothing
+/// before [`init`].
+pub fn set_level(level: Option<&str>) {
+    let Some(handle) = FILTER_HANDLE.get() else {
+        return;
+    };
+    if let Err(err) = handle.reload(get_filter(level)) {
+        eprintln!("Failed to set the log level: {}", err);
+    }
+}
+
+/// Filters logs below `level`, with the directives of `RUST_LOG` on top, so
+/// that it can still raise or lower the level of some modules.
+fn get_filter(level: Option<&str>) -> EnvFilter {
+    std::env::var(EnvFilter::DEFAULT_ENV)
+        .unwrap_or_default()
+        .split(',')
+        .filter(|directive| !directive.trim().is_empty())
+        .filter_map(|directive| directive.trim().parse().ok())
+        .fold(
+            EnvFilter::new(level.unwrap_or("info")),
+            EnvFilter::add_directive,
+        )
+}
@@ -1,31 +1,254 @@
-use ast::{find_deepest_match, get_ancestor_chain};
-use dashmap::DashMap;
-use markdown::{mdast::Node, to_mdast};
-use parser::get_parser_options;
-use tower_lsp::{jsonrpc, lsp_types::*, Client, LanguageServer};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant},
+};
 
-mod ast;
-mod nodes;
-mod parser;
+use dashmap::{
+    mapref::{entry::Entry, one::Ref},
+    DashMap, DashSet,
+};
+use markdown::{mdast::Node, to_mdast};
+use serde_json::Value;
+use supamdx::{
+    call_hierarchy::{get_incoming_calls, get_outgoing_calls, prepare_call_hierarchy},
+    code_actions::get_code_actions,
+    code_lens::{get_code_lenses, get_partial_usage_lens},
+    commands,
+    completion::{
+        get_anchor_completions, get_completions, get_expression_completions,
+        get_import_completions, get_link_destination_prefix, get_link_path_completions,
+        get_partial_src_completions, get_partial_src_prefix, get_pragma_completions,
+        get_prop_completions, get_table_delimiter_completions, get_word_completions,
+        resolve_completion, CompletionMode, LINK_TARGET_EXTENSIONS,
+    },
+    config::{
+        Config, ConfigFormat, ConfigValues, CONFIG_FILE_NAME, JSON_CONFIG_FILE_NAME,
+        SETTINGS_SECTION,
+    },
+    definition::get_definition,
+    diagnostics,
+    document::apply_content_changes,
+    folding::get_folding_ranges,
+    formatting::{
+        self, get_formatting_edits, get_on_type_edits, get_range_formatting_edits,
+        get_replacement_edits, run_prettier,
+    },
+    graph::{get_cycle_diagnostics, PartialGraph},
+    highlight::get_document_highlights,
+    hover::get_hover,
+    inlay_hints::get_inlay_hints,
+    links::get_document_links,
+    logging,
+    nodes::partials::{
+        get_partial_title, get_partial_titles, includes_partial_file, is_partial_file,
+        list_all_partials_in_dirs, scan_partial_files,
+    },
+    parser::get_parser_options_for,
+    progress::ProgressReporter,
+    references::{get_heading_references, get_partial_file_references},
+    rename::{prepare_partial_rename, rename_partial},
+    selection::get_selection_ranges,
+    semantic_tokens::{self, get_semantic_tokens},
+    signature_help::get_signature_help,
+    spelling,
+    symbols::{get_document_symbols, get_partial_symbols, get_workspace_symbols},
+    workspace::{find_files, has_extension, index_workspace_files, parse_document_file},
+};
+use tower_lsp::{jsonrpc, lsp_types::*, Client, LanguageServer, LspService, Server};
 
-use crate::nodes::NodeExt;
+/// Number of ASTs kept before the least recently used ones are evicted. The
+/// ASTs of open documents don't count towards it and are never evicted.
+const AST_CAPACITY: usize = 256;
 
 #[derive(Debug)]
 pub struct Backend {
     client: Client,
-    ast_map: DashMap<String, Node>,
+    config: Config,
+    /// ASTs of the open documents and of the most recently used other
+    /// documents of the workspace. Shared with the indexing task.
+    ast_map: Arc<DashMap<String, Node>>,
+    /// Text of every document in `ast_map`, which node ranges are computed
+    /// against. Shared with the indexing task.
+    text_map: Arc<DashMap<String, String>>,
+    /// Maximum number of entries in `ast_map` that may be evicted.
+    ast_capacity: usize,
+    /// When each AST was last inserted or read, to evict the least recently
+    /// used ones.
+    ast_access_times: Arc<DashMap<String, Instant>>,
+    /// Text of the open documents. Shared with the indexing task, whose
+    /// evictions keep their ASTs.
+    document_map: Arc<DashMap<String, String>>,
+    file_index: RwLock<Option<HashSet<PathBuf>>>,
+    partial_titles: DashMap<Url, String>,
+    partial_graph: RwLock<PartialGraph>,
+    /// Workspace symbols of the partial files, keyed by path. Built on the
+    /// first `workspace/symbol` request and cleared when partials may have
+    /// changed.
+    partial_symbols: DashMap<String, SymbolInformation>,
+    /// Response statuses of external links, kept for the whole session when
+    /// `check_external_links` is on.
+    external_link_statuses: Arc<DashMap<String, u16>>,
+    /// Words of the `spell_check_wordlist`, read again when the config or the
+    /// file changes.
+    wordlist: DashSet<String>,
+    /// Diagnostics of the last `supermdx/spellCheck` of each open document,
+    /// published along with the others until the document changes.
+    spelling_diagnostics: DashMap<String, Vec<Diagnostic>>,
+    completion_mode_map: DashMap<String, CompletionMode>,
+    supports_work_done_progress: AtomicBool,
+    /// Clients that pull diagnostics get none pushed, to avoid duplicates.
+    supports_pull_diagnostics: AtomicBool,
+    supports_watched_files_registration: AtomicBool,
+    /// Clients that answer `workspace/configuration` have their `supermdx`
+    /// settings pulled after initialization and on every settings change.
+    supports_configuration: AtomicBool,
+    /// Whether the user was told that prettier isn't installed, which is only
+    /// done once.
+    prettier_missing_reported: AtomicBool,
+    /// Kept to apply the client's overrides again when the config reloads.
+    initialization_options: RwLock<Option<Value>>,
+    /// The workspace root announced by the client, where the config file is
+    /// read from even when it overrides `workspace_root`.
+    root_uri: RwLock<Option<Url>>,
+    /// The client's `supermdx` settings, which override the config file and
+    /// the initialization options.
+    settings: RwLock<Option<Value>>,
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> jsonrpc::Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> jsonrpc::Result<InitializeResult> {
+        let update_result = self.config.0.lock().unwrap().update(&params, None);
+        match update_result {
+            Ok(warnings) => {
+                logging::set_level(self.config.0.lock().unwrap().log_level.as_deref());
+                for warning in warnings {
+                    self.client
+                        .show_message(MessageType::WARNING, warning)
+                        .await;
+                }
+            }
+            Err(err) => self.client.show_message(MessageType::WARNING, err).await,
+        }
+        self.scan_partial_files().await;
+        *self.initialization_options.write().unwrap() = params.initialization_options.clone();
+        *self.root_uri.write().unwrap() = params.root_uri.clone();
+
+        let supports_pull_diagnostics = params
+            .capabilities
+            .text_document
+            .as_ref()
+            .is_some_and(|text_document| text_document.diagnostic.is_some());
+        self.supports_pull_diagnostics
+            .store(supports_pull_diagnostics, Ordering::Relaxed);
+
+        let supports_watched_files_registration = params
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.did_change_watched_files)
+            .and_then(|watched_files| watched_files.dynamic_registration)
+            .unwrap_or(false);
+        self.supports_watched_files_registration
+            .store(supports_watched_files_registration, Ordering::Relaxed);
+
+        let supports_configuration = params
+            .capabilities
+            .workspace
+            .as_ref()
+            .and_then(|workspace| workspace.configuration)
+            .unwrap_or(false);
+        self.supports_configuration
+            .store(supports_configuration, Ordering::Relaxed);
+
+        let supports_work_done_progress = params
+            .capabilities
+            .window
+            .and_then(|window| window.work_done_progress)
+            .unwrap_or(false);
+        self.supports_work_done_progress
+            .store(supports_work_done_progress, Ordering::Relaxed);
+
         Ok(InitializeResult {
             server_info: None,
             capabilities: ServerCapabilities {
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::FULL,
+                    TextDocumentSyncKind::INCREMENTAL,
                 )),
                 definition_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: Default::default(),
+                })),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                diagnostic_provider: Some(DiagnosticServerCapabilities::Options(
+                    DiagnosticOptions {
+                        identifier: Some(diagnostics::DIAGNOSTIC_SOURCE.to_string()),
+                        inter_file_dependencies: true,
+                        workspace_diagnostics: true,
+                        ..Default::default()
+                    },
+                )),
+                document_formatting_provider: Some(OneOf::Left(true)),
+                document_range_formatting_provider: Some(OneOf::Left(true)),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec![" ".to_string(), "=".to_string()]),
+                    ..Default::default()
+                }),
+                document_on_type_formatting_provider: Some(DocumentOnTypeFormattingOptions {
+                    first_trigger_character: formatting::EXPRESSION_OPEN.to_string(),
+                    more_trigger_character: Some(vec![formatting::TAG_CLOSE.to_string()]),
+                }),
+                completion_provider: Some(CompletionOptions {
+                    // Word completion triggers on any alphanumeric character.
+                    trigger_characters: Some(
+                        ["<", "\"", "'", "/", "#", "@", "|", "("]
+                            .map(String::from)
+                            .into_iter()
+                            .chain(
+                                ('a'..='z')
+                                    .chain('A'..='Z')
+                                    .chain('0'..='9')
+                                    .map(String::from),
+                            )
+                            .collect(),
+                    ),
+                    ..Default::default()
+                }),
+                selection_range_provider: Some(SelectionRangeProviderCapability::Simple(true)),
+                folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                document_link_provider: Some(DocumentLinkOptions {
+                    resolve_provider: Some(false),
+                    work_done_progress_options: Default::default(),
+                }),
+                semantic_tokens_provider: Some(
+                    SemanticTokensServerCapabilities::SemanticTokensOptions(
+                        SemanticTokensOptions {
+                            legend: semantic_tokens::get_legend(),
+                            full: Some(SemanticTokensFullOptions::Bool(true)),
+                            ..Default::default()
+                        },
+                    ),
+                ),
+                code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+                code_lens_provider: Some(CodeLensOptions {
+                    resolve_provider: Some(false),
+                }),
+                call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+                execute_command_provider: Some(ExecuteCommandOptions {
+                    commands: commands::COMMANDS.iter().map(|c| c.to_string()).collect(),
+                    ..Default::default()
+                }),
                 ..Default::default()
             },
         })
@@ -34,7 +257,47 @@ impl LanguageServer for Backend {
     async fn initialized(&self, _: InitializedParams) {
         self.client
             .log_message(MessageType::INFO, "Server initialized!")
-            .await
+            .await;
+        if self.supports_configuration.load(Ordering::Relaxed) {
+            self.pull_settings().await;
+            if self.settings.read().unwrap().is_some() {
+                self.update_config().await;
+            }
+        }
+        let config = self.config.to_string();
+        self.client
+            .log_message(MessageType::INFO, format!("Config:\n{}", config))
+            .await;
+
+        if self
+            .supports_watched_files_registration
+            .load(Ordering::Relaxed)
+        {
+            self.register_file_watchers().await;
+        }
+
+        let config = self.config.0.lock().unwrap().clone();
+        let files = index_workspace_files(&config).await;
+        self.client
+            .log_message(
+                MessageType::INFO,
+                format!("Found {} workspace files", files.len()),
+            )
+            .await;
+        if config.index_workspace {
+            self.spawn_workspace_indexing(files.clone(), config.clone());
+        }
+        *self.file_index.write().unwrap() = Some(files.into_iter().collect());
+        self.load_wordlist().await;
+
+        for (path, title) in get_partial_titles(&config).await {
+            if let Ok(uri) = Url::from_file_path(path) {
+                self.partial_titles.insert(uri, title);
+            }
+        }
+
+        let graph = PartialGraph::build(&self.ast_map, &config).await;
+        *self.partial_graph.write().unwrap() = graph;
     }
 
     async fn shutdown(&self) -> jsonrpc::Result<()> {
@@ -45,15 +308,30 @@ impl LanguageServer for Backend {
         self.client
             .log_message(MessageType::INFO, "File opened!")
             .await;
-        self.on_change(&params.text_document.uri, &params.text_document.text)
-            .await;
+        // Opening a document replaces any text left over from a previous
+        // session.
+        let change = TextDocumentContentChangeEvent {
+            range: None,
+            range_length: None,
+            text: params.text_document.text,
+        };
+        self.on_change(&params.text_document.uri, &[change]).await;
+
+        // Without file watchers, partials created since the last scan are
+        // picked up when documents are opened or saved.
+        if !self
+            .supports_watched_files_registration
+            .load(Ordering::Relaxed)
+        {
+            self.scan_partial_files().await;
+        }
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         self.client
             .log_message(MessageType::INFO, "File changed!")
             .await;
-        self.on_change(&params.text_document.uri, &params.content_changes[0].text)
+        self.on_change(&params.text_document.uri, &params.content_changes)
             .await;
     }
 
@@ -61,14 +339,224 @@ impl LanguageServer for Backend {
         self.client
             .log_message(MessageType::INFO, "File saved!")
             .await;
+
+        if !self
+            .supports_watched_files_registration
+            .load(Ordering::Relaxed)
+        {
+            self.scan_partial_files().await;
+        }
+    }
+
+    /// Clients that support `workspace/configuration` are asked for the new
+    /// settings, since they may only notify of the change. Others are
+    /// expected to push them under the `supermdx` key.
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        if self.supports_configuration.load(Ordering::Relaxed) {
+            self.pull_settings().await;
+        } else {
+            *self.settings.write().unwrap() = params
+                .settings
+                .get(SETTINGS_SECTION)
+                .filter(|settings| !settings.is_null())
+                .cloned();
+        }
+        self.reload_config().await;
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        // Only the config file at the root is read, not those of nested
+        // packages or fixtures.
+        let root = self
+            .root_uri
+            .read()
+            .unwrap()
+            .as_ref()
+            .and_then(|uri| uri.to_file_path().ok());
+        let config_changed = root.is_some_and(|root| {
+            params.changes.iter().any(|event| {
+                event.uri.to_file_path().is_ok_and(|path| {
+                    ConfigFormat::ALL
+                        .iter()
+                        .any(|format| path == root.join(format.file_name()))
+                })
+            })
+        });
+        if config_changed {
+            self.reload_config().await;
+        }
+
+        let wordlist_changed = {
+            let config = self.config.0.lock().unwrap();
+            config
+                .spell_check_wordlist
+                .as_ref()
+                .is_some_and(|wordlist| {
+                    params
+                        .changes
+                        .iter()
+                        .any(|event| event.uri.to_file_path().is_ok_and(|path| path == *wordlist))
+                })
+        };
+        if wordlist_changed {
+            self.load_wordlist().await;
+        }
+
+        // Partials only resolve to the files of the last scan.
+        let partials_changed = {
+            let config = self.config.0.lock().unwrap();
+            params.changes.iter().any(|event| {
+                event.typ != FileChangeType::CHANGED
+                    && event.uri.to_file_path().is_ok_and(|path| {
+                        config
+                            .partials_dirs
+                            .iter()
+                            .chain(config.aliases.values())
+                            .any(|dir| path.starts_with(dir))
+                    })
+            })
+        };
+        if partials_changed {
+            self.scan_partial_files().await;
+        }
+
+        let config = self.config.0.lock().unwrap().clone();
+        self.partial_symbols.clear();
+
+        let mut changed_paths = Vec::new();
+        for event in params.changes {
+            let Ok(path) = event.uri.to_file_path() else {
+                continue;
+            };
+
+            // Indexed documents that aren't open follow the file on disk.
+            if config.index_workspace
+                && is_partial_file(&path)
+                && !self.document_map.contains_key(event.uri.as_str())
+            {
+                let ast = match event.typ {
+                    FileChangeType::DELETED => None,
+                    _ => parse_document_file(&path, &config).await,
+                };
+                match ast {
+                    Some((ast, text)) => {
+                        self.ast_map.insert(event.uri.to_string(), ast);
+                        self.text_map.insert(event.uri.to_string(), text);
+                        self.ast_access_times
+                            .insert(event.uri.to_string(), Instant::now());
+                        self.evict_asts();
+                    }
+                    None => {
+                        self.ast_map.remove(event.uri.as_str());
+                        self.text_map.remove(event.uri.as_str());
+                        self.ast_access_times.remove(event.uri.as_str());
+                    }
+                }
+            }
+
+            if event.typ == FileChangeType::DELETED {
+                self.partial_titles.remove(&event.uri);
+                if let Some(files) = &mut *self.file_index.write().unwrap() {
+                    files.remove(&path);
+                }
+            } else {
+                let is_partial = config.partials_dirs.iter().any(|dir| path.starts_with(dir));
+                let title = if is_partial {
+                    get_partial_title(&path).await
+                } else {
+                    None
+                };
+                if let Some(title) = title {
+                    self.partial_titles.insert(event.uri.clone(), title);
+                } else {
+                    self.partial_titles.remove(&event.uri);
+                }
+                if let Some(files) = &mut *self.file_index.write().unwrap() {
+                    files.insert(path.clone());
+                }
+            }
+            changed_paths.push(path);
+        }
+
+        let graph = PartialGraph::build(&self.ast_map, &config).await;
+        *self.partial_graph.write().unwrap() = graph;
+
+        let affected: Vec<Url> = self
+            .ast_map
+            .iter()
+            .filter(|entry| {
+                changed_paths
+                    .iter()
+                    .any(|path| includes_partial_file(entry.value(), path, &config))
+            })
+            .filter_map(|entry| Url::parse(entry.key()).ok())
+            .collect();
+        for uri in affected {
+            self.publish_diagnostics(&uri).await;
+        }
     }
 
-    async fn did_close(&self, _: DidCloseTextDocumentParams) {
+    async fn did_close(&self, params: DidCloseTextDocumentParams) {
         self.client
             .log_message(MessageType::INFO, "File closed!")
             .await;
+
+        let uri = params.text_document.uri.as_str();
+        self.document_map.remove(uri);
+        self.completion_mode_map.remove(uri);
+        self.spelling_diagnostics.remove(uri);
+
+        // Partials and indexed documents are reparsed for cross-file features,
+        // as they are on disk: unsaved changes were discarded.
+        let config = self.config.0.lock().unwrap().clone();
+        let kept_path = params.text_document.uri.to_file_path().ok().filter(|path| {
+            config.partials_dirs.iter().any(|dir| path.starts_with(dir))
+                || (config.index_workspace
+                    && self
+                        .file_index
+                        .read()
+                        .unwrap()
+                        .as_ref()
+                        .is_some_and(|files| files.contains(path)))
+        });
+        let ast = match &kept_path {
+            Some(path) => parse_document_file(path, &config).await,
+            None => None,
+        };
+        match (ast, kept_path) {
+            (Some((ast, text)), Some(path)) => {
+                self.partial_graph
+                    .write()
+                    .unwrap()
+                    .add_document(path, &ast, &config);
+                self.ast_map.insert(uri.to_string(), ast);
+                self.text_map.insert(uri.to_string(), text);
+                self.ast_access_times
+                    .insert(uri.to_string(), Instant::now());
+                self.evict_asts();
+            }
+            _ => {
+                self.ast_map.remove(uri);
+                self.text_map.remove(uri);
+                self.ast_access_times.remove(uri);
+            }
+        }
+
+        if !self.supports_pull_diagnostics.load(Ordering::Relaxed) {
+            self.client
+                .publish_diagnostics(params.text_document.uri, Vec::new(), None)
+                .await;
+        }
     }
 
+    #[tracing::instrument(
+        skip_all,
+        fields(
+            uri = %params.text_document_position_params.text_document.uri,
+            line = params.text_document_position_params.position.line,
+            character = params.text_document_position_params.position.character,
+        )
+    )]
     async fn goto_definition(
         &self,
         params: GotoDefinitionParams,
@@ -76,11 +564,780 @@ impl LanguageServer for Backend {
         let uri = params.text_document_position_params.text_document.uri;
         let position = params.text_document_position_params.position;
 
-        let ast = self.ast_map.get(&uri.to_string()).unwrap();
-        let ancestor_chain = get_ancestor_chain(&ast, &position);
-        let deepest_match = find_deepest_match(&ancestor_chain, |node| node.is_partial());
+        let (Some(ast), Some(text)) = (
+            self.get_ast(uri.as_str()),
+            self.text_map.get(&uri.to_string()),
+        ) else {
+            return Ok(None);
+        };
+        let config = self.config.0.lock().unwrap().clone();
+
+        Ok(get_definition(&ast, &text, &uri, &position, &config))
+    }
+
+    async fn references(&self, params: ReferenceParams) -> jsonrpc::Result<Option<Vec<Location>>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        let heading_references = self
+            .get_ast(uri.as_str())
+            .zip(self.text_map.get(uri.as_str()))
+            .and_then(|(ast, text)| {
+                get_heading_references(
+                    &ast,
+                    &text,
+                    &uri,
+                    &position,
+                    &self.ast_map,
+                    &self.text_map,
+                    params.context.include_declaration,
+                )
+            });
+        if heading_references.is_some() {
+            return Ok(heading_references);
+        }
+
+        // Outside of a heading, a partial file is referenced by the documents
+        // that include it.
+        let config = self.config.0.lock().unwrap().clone();
+        Ok(get_partial_file_references(
+            &uri,
+            &self.ast_map,
+            &self.text_map,
+            &config,
+        ))
+    }
+
+    async fn signature_help(
+        &self,
+        params: SignatureHelpParams,
+    ) -> jsonrpc::Result<Option<SignatureHelp>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let Some(text) = self.document_map.get(uri.as_str()) else {
+            return Ok(None);
+        };
+        let config = self.config.0.lock().unwrap().clone();
+
+        Ok(get_signature_help(&text, &position, &config))
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> jsonrpc::Result<Option<Vec<DocumentHighlight>>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let (Some(ast), Some(text)) = (self.get_ast(uri.as_str()), self.text_map.get(uri.as_str()))
+        else {
+            return Ok(None);
+        };
+        let config = self.config.0.lock().unwrap().clone();
+
+        Ok(get_document_highlights(&ast, &text, &position, &config))
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> jsonrpc::Result<Option<PrepareRenameResponse>> {
+        let uri = params.text_document.uri.to_string();
+        let (Some(ast), Some(text)) = (self.get_ast(&uri), self.text_map.get(&uri)) else {
+            return Ok(None);
+        };
+        let config = self.config.0.lock().unwrap().clone();
+
+        // Refusing here keeps the editor from asking for a new name at all.
+        match prepare_partial_rename(&ast, &text, &params.position, &config) {
+            Some((range, _)) => Ok(Some(PrepareRenameResponse::Range(range))),
+            None => Err(jsonrpc::Error {
+                code: jsonrpc::ErrorCode::InvalidRequest,
+                message: "Only the src of a partial can be renamed".into(),
+                data: None,
+            }),
+        }
+    }
+
+    async fn rename(&self, params: RenameParams) -> jsonrpc::Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri.to_string();
+        let position = params.text_document_position.position;
+        let (Some(ast), Some(text)) = (self.get_ast(&uri), self.text_map.get(&uri)) else {
+            return Ok(None);
+        };
+        let config = self.config.0.lock().unwrap().clone();
+
+        rename_partial(
+            &ast,
+            &text,
+            &position,
+            &params.new_name,
+            &self.ast_map,
+            &self.text_map,
+            &config,
+        )
+        .map_err(|err| jsonrpc::Error::invalid_params(err.to_string()))
+    }
+
+    async fn folding_range(
+        &self,
+        params: FoldingRangeParams,
+    ) -> jsonrpc::Result<Option<Vec<FoldingRange>>> {
+        let uri = params.text_document.uri;
+
+        let (Some(ast), Some(text)) = (self.get_ast(uri.as_str()), self.text_map.get(uri.as_str()))
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(get_folding_ranges(&ast, &text)))
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> jsonrpc::Result<Option<DocumentSymbolResponse>> {
+        let uri = params.text_document.uri;
+
+        let (Some(ast), Some(text)) = (self.get_ast(uri.as_str()), self.text_map.get(uri.as_str()))
+        else {
+            return Ok(None);
+        };
+        let config = self.config.0.lock().unwrap().clone();
+        let symbols = get_document_symbols(&ast, &text, &config);
+
+        Ok(Some(DocumentSymbolResponse::Nested(symbols)))
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> jsonrpc::Result<Option<Vec<SymbolInformation>>> {
+        if self.partial_symbols.is_empty() {
+            let config = self.config.0.lock().unwrap().clone();
+            let partials = list_all_partials_in_dirs(&config).await;
+            for (path, symbol) in get_partial_symbols(&partials) {
+                self.partial_symbols.insert(path, symbol);
+            }
+        }
+
+        Ok(Some(get_workspace_symbols(
+            &self.ast_map,
+            &self.text_map,
+            &self.partial_symbols,
+            &params.query,
+        )))
+    }
+
+    async fn hover(&self, params: HoverParams) -> jsonrpc::Result<Option<Hover>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let (Some(ast), Some(text)) = (
+            self.get_ast(uri.as_str()).map(|ast| ast.clone()),
+            self.text_map.get(uri.as_str()).map(|text| text.clone()),
+        ) else {
+            return Ok(None);
+        };
+        let config = self.config.0.lock().unwrap().clone();
+
+        // Hovers over images and file links read the disk.
+        let ast_map = Arc::clone(&self.ast_map);
+        let task = tokio::task::spawn_blocking(move || {
+            get_hover(&ast, &text, &uri, &position, &ast_map, &config)
+        });
+
+        Ok(task.await.ok().flatten())
+    }
+
+    async fn completion(
+        &self,
+        params: CompletionParams,
+    ) -> jsonrpc::Result<Option<CompletionResponse>> {
+        let uri = params.text_document_position.text_document.uri;
+        let position = params.text_document_position.position;
+
+        // Copied so that no map entry is borrowed across an await.
+        let Some(text) = self
+            .document_map
+            .get(&uri.to_string())
+            .map(|text| text.clone())
+        else {
+            return Ok(None);
+        };
+        let config = self.config.0.lock().unwrap().clone();
+
+        let mode = self
+            .completion_mode_map
+            .get(&uri.to_string())
+            .map(|mode| *mode)
+            .unwrap_or_default();
+        match mode {
+            CompletionMode::Off => return Ok(None),
+            CompletionMode::SnippetsOnly => {
+                let completions = get_completions(&text, &position, &config);
+                return Ok(Some(CompletionResponse::Array(completions)));
+            }
+            CompletionMode::All => {}
+        }
+
+        if let Some(completions) = get_pragma_completions(&text, &position) {
+            return Ok(Some(CompletionResponse::Array(completions)));
+        }
+
+        if let Some(completions) = get_table_delimiter_completions(&text, &position) {
+            return Ok(Some(CompletionResponse::Array(completions)));
+        }
+
+        if let Some(completions) = self.get_ast(uri.as_str()).and_then(|ast| {
+            get_anchor_completions(&ast, &text, &position)
+                .or_else(|| get_expression_completions(&ast, &text, &position))
+        }) {
+            return Ok(Some(CompletionResponse::Array(completions)));
+        }
+
+        if let Some(typed) =
+            get_partial_src_prefix(&text, &position, config.get_partial_element_name())
+        {
+            let partials = list_all_partials_in_dirs(&config).await;
+            let completions = get_partial_src_completions(&position, &typed, &partials);
+            return Ok(Some(CompletionResponse::Array(completions)));
+        }
+
+        if let Some(completions) = get_prop_completions(&text, &position, &config) {
+            return Ok(Some(CompletionResponse::Array(completions)));
+        }
+
+        let link_destination = self
+            .get_ast(uri.as_str())
+            .and_then(|ast| get_link_destination_prefix(&ast, &text, &position));
+        if let (Some((start, typed)), Ok(path)) = (link_destination, uri.to_file_path()) {
+            let roots = [config.workspace_root.clone()];
+            let files = find_files(&roots, &config.exclude_dirs, |file| {
+                has_extension(file, &LINK_TARGET_EXTENSIONS)
+            })
+            .await;
+            let completions = get_link_path_completions(start, &position, &typed, &path, &files);
+            return Ok(Some(CompletionResponse::Array(completions)));
+        }
+
+        // Import specifiers can only be completed once the partial index is
+        // built.
+        if let (Ok(path), Some(files)) = (uri.to_file_path(), &*self.file_index.read().unwrap()) {
+            if let Some(completions) = get_import_completions(
+                &text,
+                &position,
+                &path,
+                files,
+                &self.partial_titles,
+                &config,
+            ) {
+                return Ok(Some(CompletionResponse::Array(completions)));
+            }
+        }
+
+        let completions = get_completions(&text, &position, &config);
+        if completions.is_empty() {
+            let wordlist = config
+                .spell_check_wordlist
+                .is_some()
+                .then_some(&self.wordlist);
+
+            if let Some(completions) = self
+                .get_ast(uri.as_str())
+                .and_then(|ast| get_word_completions(&ast, &text, &position, wordlist))
+            {
+                return Ok(Some(CompletionResponse::Array(completions)));
+            }
+        }
+
+        Ok(Some(CompletionResponse::Array(completions)))
+    }
+
+    async fn selection_range(
+        &self,
+        params: SelectionRangeParams,
+    ) -> jsonrpc::Result<Option<Vec<SelectionRange>>> {
+        let uri = params.text_document.uri.to_string();
+
+        let (Some(ast), Some(text)) = (self.get_ast(&uri), self.text_map.get(&uri)) else {
+            return Ok(None);
+        };
+
+        Ok(Some(get_selection_ranges(&ast, &text, &params.positions)))
+    }
+
+    async fn code_action(
+        &self,
+        params: CodeActionParams,
+    ) -> jsonrpc::Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+
+        let (Some(ast), Some(text)) = (
+            self.get_ast(uri.as_str()),
+            self.text_map.get(&uri.to_string()),
+        ) else {
+            return Ok(None);
+        };
+        let config = self.config.0.lock().unwrap().clone();
+
+        Ok(Some(get_code_actions(
+            &ast,
+            &text,
+            &uri,
+            &params.range,
+            params.context.only.as_deref(),
+            &config,
+        )))
+    }
+
+    async fn semantic_tokens_full(
+        &self,
+        params: SemanticTokensParams,
+    ) -> jsonrpc::Result<Option<SemanticTokensResult>> {
+        let uri = params.text_document.uri.to_string();
+        let (Some(ast), Some(text)) = (self.get_ast(&uri), self.text_map.get(&uri)) else {
+            return Ok(None);
+        };
+
+        Ok(Some(SemanticTokensResult::Tokens(SemanticTokens {
+            result_id: None,
+            data: get_semantic_tokens(&ast, &text),
+        })))
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> jsonrpc::Result<Option<Vec<InlayHint>>> {
+        let uri = params.text_document.uri;
+
+        let (Some(ast), Some(text)) = (self.get_ast(uri.as_str()), self.text_map.get(uri.as_str()))
+        else {
+            return Ok(None);
+        };
+
+        let config = self.config.0.lock().unwrap().clone();
+
+        Ok(Some(get_inlay_hints(&ast, &text, &params.range, &config)))
+    }
+
+    async fn document_link(
+        &self,
+        params: DocumentLinkParams,
+    ) -> jsonrpc::Result<Option<Vec<DocumentLink>>> {
+        let uri = params.text_document.uri;
+
+        let (Some(ast), Some(text)) = (self.get_ast(uri.as_str()), self.text_map.get(uri.as_str()))
+        else {
+            return Ok(None);
+        };
+
+        Ok(Some(get_document_links(&ast, &text, &uri)))
+    }
+
+    async fn code_lens(&self, params: CodeLensParams) -> jsonrpc::Result<Option<Vec<CodeLens>>> {
+        let uri = params.text_document.uri;
+
+        let config = self.config.0.lock().unwrap().clone();
+        // Counted before taking the document's AST, which the count reads too.
+        let usage_lens = get_partial_usage_lens(&uri, &self.ast_map, &self.text_map, &config);
+
+        let (Some(ast), Some(text)) = (self.get_ast(uri.as_str()), self.text_map.get(uri.as_str()))
+        else {
+            return Ok(None);
+        };
+
+        let mut lenses: Vec<CodeLens> = usage_lens.into_iter().collect();
+        lenses.extend(get_code_lenses(&ast, &text, &uri, &config));
+        Ok(Some(lenses))
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> jsonrpc::Result<Option<Vec<CallHierarchyItem>>> {
+        let uri = params.text_document_position_params.text_document.uri;
+        let position = params.text_document_position_params.position;
+
+        let (Some(ast), Some(text)) = (self.get_ast(uri.as_str()), self.text_map.get(uri.as_str()))
+        else {
+            return Ok(None);
+        };
+        let config = self.config.0.lock().unwrap().clone();
+
+        Ok(prepare_call_hierarchy(&ast, &text, &position, &config))
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> jsonrpc::Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        let config = self.config.0.lock().unwrap().clone();
+
+        Ok(Some(get_incoming_calls(
+            &params.item,
+            &self.ast_map,
+            &self.text_map,
+            &config,
+        )))
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> jsonrpc::Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        let uri = params.item.uri;
+        let config = self.config.0.lock().unwrap().clone();
+
+        // Partial files whose AST isn't cached are read from disk.
+        if let (Some(ast), Some(text)) =
+            (self.get_ast(uri.as_str()), self.text_map.get(uri.as_str()))
+        {
+            return Ok(Some(get_outgoing_calls(&ast, &text, &config)));
+        }
+        let Ok(path) = uri.to_file_path() else {
+            return Ok(None);
+        };
+        let document = parse_document_file(&path, &config).await;
+
+        Ok(document.map(|(ast, text)| get_outgoing_calls(&ast, &text, &config)))
+    }
+
+    async fn completion_resolve(&self, item: CompletionItem) -> jsonrpc::Result<CompletionItem> {
+        Ok(resolve_completion(item, &self.partial_titles))
+    }
+
+    async fn formatting(
+        &self,
+        params: DocumentFormattingParams,
+    ) -> jsonrpc::Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document.uri.to_string();
+        let (Some(text), Ok(path)) = (
+            self.document_map.get(&uri).map(|text| text.clone()),
+            params.text_document.uri.to_file_path(),
+        ) else {
+            return Ok(None);
+        };
+        let config = self.config.0.lock().unwrap().clone();
+
+        let prettier_result = {
+            let text = text.clone();
+            let config = config.clone();
+            let task = tokio::task::spawn_blocking(move || {
+                run_prettier(&text, &path, &config, formatting::PRETTIER_TIMEOUT)
+            });
+            // Prettier itself is killed at the timeout, but a process it
+            // started may keep its output open.
+            tokio::time::timeout(formatting::PRETTIER_TIMEOUT + Duration::from_secs(1), task)
+                .await
+                .unwrap_or_else(|_| {
+                    Ok(Err(std::io::Error::new(
+                        std::io::ErrorKind::TimedOut,
+                        "prettier's output didn't end",
+                    )))
+                })
+        };
+        match prettier_result {
+            Ok(Ok(formatted)) => return Ok(Some(get_replacement_edits(&text, &formatted))),
+            // Without prettier, the document is still formatted by the
+            // server itself.
+            Ok(Err(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                if !self.prettier_missing_reported.swap(true, Ordering::Relaxed) {
+                    let prettier = config
+                        .prettier_path
+                        .as_deref()
+                        .unwrap_or(Path::new("prettier"));
+                    self.client
+                        .show_message(
+                            MessageType::WARNING,
+                            format!(
+                                "`{}` wasn't found, so documents are formatted without prettier",
+                                prettier.display()
+                            ),
+                        )
+                        .await;
+                }
+            }
+            Ok(Err(err)) => {
+                self.client
+                    .show_message(
+                        MessageType::ERROR,
+                        format!("Prettier failed to format {}: {}", uri, err),
+                    )
+                    .await;
+                return Ok(None);
+            }
+            Err(err) => {
+                self.client
+                    .log_message(MessageType::ERROR, format!("Prettier failed: {}", err))
+                    .await;
+                return Ok(None);
+            }
+        }
+
+        let (Some(ast), Some(text)) = (self.get_ast(&uri), self.text_map.get(&uri)) else {
+            return Ok(None);
+        };
 
-        Ok(None)
+        Ok(Some(get_formatting_edits(&ast, &text, &config)))
+    }
+
+    async fn range_formatting(
+        &self,
+        params: DocumentRangeFormattingParams,
+    ) -> jsonrpc::Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document.uri.to_string();
+
+        let (Some(ast), Some(text)) = (self.get_ast(&uri), self.text_map.get(&uri)) else {
+            return Ok(None);
+        };
+
+        Ok(Some(get_range_formatting_edits(&ast, &text, &params.range)))
+    }
+
+    async fn on_type_formatting(
+        &self,
+        params: DocumentOnTypeFormattingParams,
+    ) -> jsonrpc::Result<Option<Vec<TextEdit>>> {
+        let uri = params.text_document_position.text_document.uri.to_string();
+        let position = params.text_document_position.position;
+
+        let Some(text) = self.document_map.get(&uri) else {
+            return Ok(None);
+        };
+        let ast = self.get_ast(&uri);
+        let edits = get_on_type_edits(ast.as_deref(), &text, &position, &params.ch);
+
+        Ok(Some(edits))
+    }
+
+    async fn diagnostic(
+        &self,
+        params: DocumentDiagnosticParams,
+    ) -> jsonrpc::Result<DocumentDiagnosticReportResult> {
+        let uri = params.text_document.uri;
+
+        let items = match self.get_document_diagnostics(&uri) {
+            Some((mut items, external_links)) => {
+                items.extend(
+                    diagnostics::get_external_link_diagnostics(
+                        external_links,
+                        &self.external_link_statuses,
+                    )
+                    .await,
+                );
+                items
+            }
+            None => Vec::new(),
+        };
+
+        Ok(DocumentDiagnosticReportResult::Report(
+            DocumentDiagnosticReport::Full(RelatedFullDocumentDiagnosticReport {
+                related_documents: None,
+                full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                    result_id: None,
+                    items,
+                },
+            }),
+        ))
+    }
+
+    async fn workspace_diagnostic(
+        &self,
+        _: WorkspaceDiagnosticParams,
+    ) -> jsonrpc::Result<WorkspaceDiagnosticReportResult> {
+        let config = self.config.0.lock().unwrap().clone();
+        let reports =
+            diagnostics::get_workspace_diagnostics(&self.ast_map, &self.text_map, &config).await;
+
+        let items = reports
+            .into_iter()
+            .map(|(uri, items)| {
+                WorkspaceDocumentDiagnosticReport::Full(WorkspaceFullDocumentDiagnosticReport {
+                    uri,
+                    version: None,
+                    full_document_diagnostic_report: FullDocumentDiagnosticReport {
+                        result_id: None,
+                        items,
+                    },
+                })
+            })
+            .collect();
+
+        Ok(WorkspaceDiagnosticReportResult::Report(
+            WorkspaceDiagnosticReport { items },
+        ))
+    }
+
+    async fn execute_command(
+        &self,
+        params: ExecuteCommandParams,
+    ) -> jsonrpc::Result<Option<Value>> {
+        match params.command.as_str() {
+            commands::LIST_PARTIALS => {
+                let config = self.config.0.lock().unwrap().clone();
+                let content = commands::list_partials(&config, &self.ast_map).await;
+                self.show_report("partials.md", &content).await;
+
+                Ok(Some(Value::String(content)))
+            }
+            commands::CHECK_ALL_PARTIALS => {
+                let config = self.config.0.lock().unwrap().clone();
+                let content = commands::check_all_partials(&config).await;
+                self.show_report("partial-check.md", &content).await;
+
+                Ok(Some(Value::String(content)))
+            }
+            commands::COPY_TO_CLIPBOARD => {
+                let Some(Value::String(text)) = params.arguments.into_iter().next() else {
+                    return Err(jsonrpc::Error::invalid_params(
+                        "Expected the text to copy as the first argument",
+                    ));
+                };
+                self.client
+                    .send_notification::<commands::CopyToClipboard>(
+                        commands::CopyToClipboardParams { text },
+                    )
+                    .await;
+
+                Ok(None)
+            }
+            commands::SHOW_DOCUMENT => {
+                let Some(uri) = params
+                    .arguments
+                    .first()
+                    .and_then(Value::as_str)
+                    .and_then(|uri| Url::parse(uri).ok())
+                else {
+                    return Err(jsonrpc::Error::invalid_params(
+                        "Expected a URI as the first argument",
+                    ));
+                };
+                let params = ShowDocumentParams {
+                    uri,
+                    external: Some(false),
+                    take_focus: Some(true),
+                    selection: None,
+                };
+                if let Err(err) = self.client.show_document(params).await {
+                    self.client
+                        .log_message(
+                            MessageType::WARNING,
+                            format!("Failed to show document: {}", err),
+                        )
+                        .await;
+                }
+
+                Ok(None)
+            }
+            commands::SPELL_CHECK => {
+                let (Some(uri), Some(line)) = (
+                    params
+                        .arguments
+                        .first()
+                        .and_then(Value::as_str)
+                        .and_then(|uri| Url::parse(uri).ok()),
+                    params.arguments.get(1).and_then(Value::as_u64),
+                ) else {
+                    return Err(jsonrpc::Error::invalid_params(
+                        "Expected a URI and a heading line as arguments",
+                    ));
+                };
+                if self.config.0.lock().unwrap().spell_check_wordlist.is_none() {
+                    return Ok(None);
+                }
+
+                let diagnostics = match (
+                    self.get_ast(uri.as_str()),
+                    self.text_map.get(&uri.to_string()),
+                ) {
+                    (Some(ast), Some(text)) => {
+                        spelling::check_section_spelling(&ast, &text, line as u32, &self.wordlist)
+                    }
+                    _ => Vec::new(),
+                };
+                let count = diagnostics.len();
+                self.spelling_diagnostics
+                    .insert(uri.to_string(), diagnostics);
+                // Clients that pull diagnostics are asked to pull them again.
+                if self.supports_pull_diagnostics.load(Ordering::Relaxed) {
+                    if let Err(err) = self.client.workspace_diagnostic_refresh().await {
+                        self.client
+                            .log_message(
+                                MessageType::WARNING,
+                                format!("Failed to refresh diagnostics: {}", err),
+                            )
+                            .await;
+                    }
+                } else {
+                    self.publish_diagnostics(&uri).await;
+                }
+
+                Ok(Some(Value::from(count)))
+            }
+            commands::GENERATE_TOC => {
+                let Some(uri) = params
+                    .arguments
+                    .first()
+                    .and_then(Value::as_str)
+                    .and_then(|uri| Url::parse(uri).ok())
+                else {
+                    return Err(jsonrpc::Error::invalid_params(
+                        "Expected a URI as the first argument",
+                    ));
+                };
+                let toc = match (
+                    self.get_ast(uri.as_str()),
+                    self.text_map.get(&uri.to_string()),
+                ) {
+                    (Some(ast), Some(text)) => commands::generate_toc(&ast, &text),
+                    _ => None,
+                };
+                let Some((toc, edit)) = toc else {
+                    return Ok(None);
+                };
+
+                let edit = WorkspaceEdit {
+                    changes: Some([(uri, vec![edit])].into_iter().collect()),
+                    ..Default::default()
+                };
+                if let Err(err) = self.client.apply_edit(edit).await {
+                    self.client
+                        .log_message(
+                            MessageType::WARNING,
+                            format!("Failed to insert table of contents: {}", err),
+                        )
+                        .await;
+                }
+
+                Ok(Some(Value::String(toc)))
+            }
+            commands::DUMP_AST => {
+                let Some(uri) = params
+                    .arguments
+                    .first()
+                    .and_then(Value::as_str)
+                    .and_then(|uri| Url::parse(uri).ok())
+                else {
+                    return Err(jsonrpc::Error::invalid_params(
+                        "Expected a URI as the first argument",
+                    ));
+                };
+                let config = self.config.0.lock().unwrap().clone();
+                let Some(content) = self
+                    .get_ast(uri.as_str())
+                    .map(|ast| commands::dump_ast(&uri, &ast, &config))
+                else {
+                    return Ok(None);
+                };
+                self.show_report("ast.md", &content).await;
+
+                Ok(Some(Value::String(content)))
+            }
+            _ => Err(jsonrpc::Error::invalid_params(format!(
+                "Unknown command: {}",
+                params.command
+            ))),
+        }
     }
 }
 
@@ -88,28 +1345,496 @@ impl Backend {
     fn new(client: Client) -> Self {
         Self {
             client,
-            ast_map: DashMap::new(),
+            // `Config` clones share their values, so hand out clones only where
+            // updates should be seen everywhere; use `deep_clone` for a copy.
+            config: Config::default(),
+            ast_map: Arc::new(DashMap::new()),
+            text_map: Arc::new(DashMap::new()),
+            ast_capacity: AST_CAPACITY,
+            ast_access_times: Arc::new(DashMap::new()),
+            document_map: Arc::new(DashMap::new()),
+            file_index: RwLock::new(None),
+            partial_titles: DashMap::new(),
+            partial_graph: RwLock::new(PartialGraph::default()),
+            partial_symbols: DashMap::new(),
+            external_link_statuses: Arc::new(DashMap::new()),
+            wordlist: DashSet::new(),
+            spelling_diagnostics: DashMap::new(),
+            completion_mode_map: DashMap::new(),
+            supports_work_done_progress: AtomicBool::new(false),
+            supports_pull_diagnostics: AtomicBool::new(false),
+            supports_watched_files_registration: AtomicBool::new(false),
+            supports_configuration: AtomicBool::new(false),
+            prettier_missing_reported: AtomicBool::new(false),
+            initialization_options: RwLock::new(None),
+            root_uri: RwLock::new(None),
+            settings: RwLock::new(None),
         }
     }
 
-    async fn on_change(&self, uri: &Url, text: &str) {
-        let ast = to_mdast(text, &get_parser_options());
-        if ast.is_ok() {
-            let ast = ast.unwrap();
+    async fn show_report(&self, file_name: &str, content: &str) {
+        let uri = match commands::write_report(file_name, content).await {
+            Ok(uri) => uri,
+            Err(err) => {
+                self.client
+                    .show_message(
+                        MessageType::ERROR,
+                        format!("Failed to write report: {}", err),
+                    )
+                    .await;
+                return;
+            }
+        };
+
+        let params = ShowDocumentParams {
+            uri,
+            external: Some(false),
+            take_focus: Some(true),
+            selection: None,
+        };
+        if let Err(err) = self.client.show_document(params).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Failed to show report: {}", err),
+                )
+                .await;
+        }
+    }
+
+    #[tracing::instrument(skip_all, fields(%uri, len = tracing::field::Empty))]
+    async fn on_change(&self, uri: &Url, changes: &[TextDocumentContentChangeEvent]) {
+        let text = {
+            let mut text = self.document_map.entry(uri.to_string()).or_default();
+            apply_content_changes(&mut text, changes);
+            text.clone()
+        };
+        let text = text.as_str();
+        tracing::Span::current().record("len", text.len());
+        self.completion_mode_map
+            .insert(uri.to_string(), CompletionMode::from_pragma(text));
+        // Their ranges are stale once the text changes.
+        self.spelling_diagnostics.remove(uri.as_str());
+
+        let config = self.config.0.lock().unwrap().clone();
+        let started = Instant::now();
+        let ast = to_mdast(
+            text,
+            &get_parser_options_for(Path::new(uri.path()), &config),
+        );
+        let elapsed = started.elapsed();
+
+        if let Ok(ast) = ast {
+            // Any change can create or break a cycle of partials. Only this
+            // document's edges change, so the rest of the graph is kept.
+            if let Ok(path) = uri.to_file_path() {
+                self.partial_graph
+                    .write()
+                    .unwrap()
+                    .add_document(path, &ast, &config);
+            }
             self.ast_map.insert(uri.to_string(), ast);
+            self.text_map.insert(uri.to_string(), text.to_string());
+            self.ast_access_times
+                .insert(uri.to_string(), Instant::now());
+            self.evict_asts();
+        }
+
+        let threshold = config.progress_threshold_ms;
+        if elapsed > Duration::from_millis(threshold) {
+            self.report_slow_parse(uri, elapsed).await;
+        }
+
+        self.publish_diagnostics(uri).await;
+    }
+
+    /// Returns the AST of a document, marking it as recently used.
+    fn get_ast(&self, uri: &str) -> Option<Ref<'_, String, Node>> {
+        let ast = self.ast_map.get(uri)?;
+        self.ast_access_times
+            .insert(uri.to_string(), Instant::now());
+        Some(ast)
+    }
+
+    /// Drops the least recently used ASTs while there are more than
+    /// `ast_capacity`. Open documents are kept, since handlers read their AST.
+    fn evict_asts(&self) {
+        evict_least_recently_used(
+            &self.ast_map,
+            &self.text_map,
+            &self.ast_access_times,
+            self.ast_capacity,
+            |uri| self.document_map.contains_key(uri),
+        );
+    }
+
+    /// Parses the workspace files that aren't open in a background task, so
+    /// that cross-file features see them too. Documents opened in the
+    /// meantime are never replaced. Indexed ASTs are evicted like any other
+    /// once there are more than `ast_capacity`.
+    fn spawn_workspace_indexing(&self, files: Vec<PathBuf>, config: ConfigValues) {
+        let client = self.client.clone();
+        let ast_map = Arc::clone(&self.ast_map);
+        let text_map = Arc::clone(&self.text_map);
+        let ast_access_times = Arc::clone(&self.ast_access_times);
+        let document_map = Arc::clone(&self.document_map);
+        let ast_capacity = self.ast_capacity;
+        let supports_work_done_progress = self.supports_work_done_progress.load(Ordering::Relaxed);
+
+        tokio::spawn(async move {
+            let progress = ProgressReporter::begin(
+                client,
+                "supermdx/index",
+                supports_work_done_progress,
+                "Indexing workspace",
+                None,
+            )
+            .await;
+
+            // Indexed documents count as used before anything opened since.
+            let started = Instant::now();
+            let interval = config.index_progress_interval.max(1);
+            let mut indexed = 0;
+            // Evicted along the way, so that the cache never holds much more
+            // than `ast_capacity` ASTs.
+            let evict = || {
+                evict_least_recently_used(
+                    &ast_map,
+                    &text_map,
+                    &ast_access_times,
+                    ast_capacity,
+                    |uri| document_map.contains_key(uri),
+                )
+            };
+            for (index, file) in files.iter().enumerate() {
+                if index > 0 && index % interval == 0 {
+                    evict();
+                    progress.report(index, files.len()).await;
+                }
+                let Ok(uri) = Url::from_file_path(file) else {
+                    continue;
+                };
+                if ast_map.contains_key(uri.as_str()) {
+                    continue;
+                }
+                let Some((ast, text)) = parse_document_file(file, &config).await else {
+                    continue;
+                };
+                if let Entry::Vacant(entry) = ast_map.entry(uri.to_string()) {
+                    entry.insert(ast);
+                    text_map.insert(uri.to_string(), text);
+                    ast_access_times.insert(uri.to_string(), started);
+                    indexed += 1;
+                }
+            }
+            evict();
+            progress.end(format!("Indexed {} documents", indexed)).await;
+        });
+    }
+
+    /// Pushes the document's diagnostics to clients that don't pull them.
+    /// Checking external links takes network round trips, so their
+    /// diagnostics are pushed along with the others once the links are
+    /// checked, in the background.
+    async fn publish_diagnostics(&self, uri: &Url) {
+        if self.supports_pull_diagnostics.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let Some((mut diagnostics, external_links)) = self.get_document_diagnostics(uri) else {
+            return;
+        };
+        let text = self.text_map.get(uri.as_str()).map(|text| text.clone());
+        self.client
+            .publish_diagnostics(uri.clone(), diagnostics.clone(), None)
+            .await;
+        if external_links.is_empty() {
+            return;
+        }
+
+        let client = self.client.clone();
+        let statuses = Arc::clone(&self.external_link_statuses);
+        let text_map = Arc::clone(&self.text_map);
+        let uri = uri.clone();
+        tokio::spawn(async move {
+            let link_diagnostics =
+                diagnostics::get_external_link_diagnostics(external_links, &statuses).await;
+            // A document changed in the meantime has newer diagnostics, and
+            // its own links checked.
+            let unchanged = text_map
+                .get(uri.as_str())
+                .is_some_and(|current| Some(&*current) == text.as_ref());
+            if link_diagnostics.is_empty() || !unchanged {
+                return;
+            }
+
+            diagnostics.extend(link_diagnostics);
+            client.publish_diagnostics(uri, diagnostics, None).await;
+        });
+    }
+
+    /// Asks the client for its `supermdx` settings. A client that fails to
+    /// answer keeps the settings it last sent.
+    async fn pull_settings(&self) {
+        let item = ConfigurationItem {
+            scope_uri: None,
+            section: Some(SETTINGS_SECTION.to_string()),
+        };
+        match self.client.configuration(vec![item]).await {
+            Ok(values) => {
+                *self.settings.write().unwrap() = values
+                    .into_iter()
+                    .next()
+                    .filter(|settings| !settings.is_null());
+            }
+            Err(err) => {
+                self.client
+                    .log_message(
+                        MessageType::WARNING,
+                        format!("Couldn't get the {} settings: {}", SETTINGS_SECTION, err),
+                    )
+                    .await;
+            }
         }
     }
+
+    /// Reads the config file again and applies the client's overrides on
+    /// top. An invalid config leaves the current one in place, in which case
+    /// this returns false.
+    async fn update_config(&self) -> bool {
+        let params = InitializeParams {
+            root_uri: self.root_uri.read().unwrap().clone(),
+            initialization_options: self.initialization_options.read().unwrap().clone(),
+            ..Default::default()
+        };
+        let settings = self.settings.read().unwrap().clone();
+
+        let update_result = self
+            .config
+            .0
+            .lock()
+            .unwrap()
+            .update(&params, settings.as_ref());
+        match update_result {
+            Ok(warnings) => {
+                logging::set_level(self.config.0.lock().unwrap().log_level.as_deref());
+                for warning in warnings {
+                    self.client
+                        .show_message(MessageType::WARNING, warning)
+                        .await;
+                }
+                self.scan_partial_files().await;
+                true
+            }
+            Err(err) => {
+                self.client.show_message(MessageType::WARNING, err).await;
+                false
+            }
+        }
+    }
+
+    /// Reads the `spell_check_wordlist` into `wordlist`, which is left empty
+    /// when there's none. A file that can't be read keeps the previous words.
+    async fn load_wordlist(&self) {
+        let Some(path) = self.config.0.lock().unwrap().spell_check_wordlist.clone() else {
+            self.wordlist.clear();
+            return;
+        };
+
+        match spelling::load_wordlist(&path).await {
+            Ok(words) => {
+                self.wordlist.clear();
+                for word in words {
+                    self.wordlist.insert(word);
+                }
+            }
+            Err(err) => {
+                self.client
+                    .show_message(
+                        MessageType::ERROR,
+                        format!("Failed to read {}: {}", path.display(), err),
+                    )
+                    .await;
+            }
+        }
+    }
+
+    /// Lists the partial files for `find_matching_partial`, so that handlers
+    /// resolve partials without blocking on the disk. A scan that times out
+    /// keeps the previous one, if any.
+    async fn scan_partial_files(&self) {
+        let config = self.config.0.lock().unwrap().clone();
+        if let Some(files) = scan_partial_files(&config).await {
+            self.config.0.lock().unwrap().partial_files = Some(Arc::new(files));
+        }
+    }
+
+    /// Reads the config file again, keeping the client's overrides, then
+    /// refreshes everything that depends on the partials directories. An
+    /// invalid config leaves the current one in place.
+    async fn reload_config(&self) {
+        if !self.update_config().await {
+            return;
+        }
+        self.client
+            .log_message(MessageType::INFO, "Reloaded the config")
+            .await;
+
+        let config = self.config.0.lock().unwrap().clone();
+        let files = index_workspace_files(&config).await;
+        if config.index_workspace {
+            self.spawn_workspace_indexing(files.clone(), config.clone());
+        }
+        *self.file_index.write().unwrap() = Some(files.into_iter().collect());
+        self.load_wordlist().await;
+        self.partial_symbols.clear();
+        self.partial_titles.clear();
+        for (path, title) in get_partial_titles(&config).await {
+            if let Ok(uri) = Url::from_file_path(path) {
+                self.partial_titles.insert(uri, title);
+            }
+        }
+
+        let graph = PartialGraph::build(&self.ast_map, &config).await;
+        *self.partial_graph.write().unwrap() = graph;
+
+        let uris: Vec<Url> = self
+            .ast_map
+            .iter()
+            .filter_map(|entry| Url::parse(entry.key()).ok())
+            .collect();
+        for uri in uris {
+            self.publish_diagnostics(&uri).await;
+        }
+    }
+
+    /// Asks the client to report changes to the config file, to the spell
+    /// check wordlist and to MDX and Markdown files, which may be partials.
+    async fn register_file_watchers(&self) {
+        let mut patterns = vec![
+            "**/*.mdx".to_string(),
+            "**/*.md".to_string(),
+            format!("**/{}", CONFIG_FILE_NAME),
+            format!("**/{}", JSON_CONFIG_FILE_NAME),
+        ];
+        let wordlist = self.config.0.lock().unwrap().spell_check_wordlist.clone();
+        if let Some(file_name) = wordlist.as_deref().and_then(Path::file_name) {
+            patterns.push(format!("**/{}", file_name.to_string_lossy()));
+        }
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: patterns
+                .into_iter()
+                .map(|pattern| FileSystemWatcher {
+                    glob_pattern: GlobPattern::String(pattern),
+                    kind: None,
+                })
+                .collect(),
+        };
+        let registration = Registration {
+            id: "supermdx/watchFiles".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(options).ok(),
+        };
+
+        if let Err(err) = self.client.register_capability(vec![registration]).await {
+            self.client
+                .log_message(
+                    MessageType::WARNING,
+                    format!("Couldn't watch files: {}", err),
+                )
+                .await;
+        }
+    }
+
+    /// The diagnostics of an open document, including partial cycles it's
+    /// part of, along with its external links to check when
+    /// `check_external_links` is on.
+    fn get_document_diagnostics(
+        &self,
+        uri: &Url,
+    ) -> Option<(Vec<Diagnostic>, Vec<diagnostics::ExternalLink>)> {
+        let config = self.config.0.lock().unwrap().clone();
+
+        let ast = self.get_ast(uri.as_str())?;
+        let text = self.text_map.get(uri.as_str())?;
+        let mut diagnostics = diagnostics::get_diagnostics(&ast, &text, uri, &config);
+        if let Ok(path) = uri.to_file_path() {
+            let graph = self.partial_graph.read().unwrap();
+            diagnostics.extend(get_cycle_diagnostics(&ast, &text, &path, &graph, &config));
+        }
+        if let Some(spelling_diagnostics) = self.spelling_diagnostics.get(uri.as_str()) {
+            diagnostics.extend(spelling_diagnostics.iter().cloned());
+        }
+        let external_links = if config.check_external_links {
+            diagnostics::get_external_links(&ast, &text)
+        } else {
+            Vec::new()
+        };
+
+        Some((diagnostics, external_links))
+    }
+
+    /// Leaves a record of slow parses in the client's progress UI, or in the
+    /// log when the client doesn't support work done progress.
+    async fn report_slow_parse(&self, uri: &Url, elapsed: Duration) {
+        let progress = ProgressReporter::begin(
+            self.client.clone(),
+            &format!("supermdx/parse/{}", uri),
+            self.supports_work_done_progress.load(Ordering::Relaxed),
+            "Parsing",
+            Some(uri.to_string()),
+        )
+        .await;
+        progress
+            .end(format!("Parsed {} in {} ms", uri, elapsed.as_millis()))
+            .await;
+    }
 }
 
-fn main() {
-    println!("Hello, world!");
+/// Drops the least recently used ASTs, with their text, while more than
+/// `capacity` of them aren't pinned. Only the timestamps are scanned. An AST
+/// read since the scan is kept, and the next oldest is tried instead.
+fn evict_least_recently_used(
+    ast_map: &DashMap<String, Node>,
+    text_map: &DashMap<String, String>,
+    access_times: &DashMap<String, Instant>,
+    capacity: usize,
+    is_pinned: impl Fn(&str) -> bool,
+) {
+    if ast_map.len() <= capacity {
+        return;
+    }
+
+    let mut candidates: Vec<(String, Instant)> = access_times
+        .iter()
+        .filter(|entry| !is_pinned(entry.key()))
+        .map(|entry| (entry.key().clone(), *entry.value()))
+        .collect();
+    candidates.sort_by_key(|(_, accessed)| *accessed);
+
+    let mut excess = candidates.len().saturating_sub(capacity);
+    for (uri, accessed) in candidates {
+        if excess == 0 {
+            break;
+        }
+        if access_times
+            .remove_if(&uri, |_, time| *time == accessed)
+            .is_some()
+        {
+            ast_map.remove(&uri);
+            text_map.remove(&uri);
+            excess -= 1;
+        }
+    }
 }
 
-#[cfg(test)]
-use ctor::ctor;
+#[tokio::main]
+async fn main() {
+    logging::init();
+
+    let stdin = tokio::io::stdin();
+    let stdout = tokio::io::stdout();
 
-#[cfg(test)]
-#[ctor]
-fn init_test_logger() {
-    env_logger::init();
+    let (service, socket) = LspService::new(Backend::new);
+    Server::new(stdin, stdout, socket).serve(service).await;
 }
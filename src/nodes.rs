@@ -1,33 +1,163 @@
-use markdown::mdast::Node;
+use markdown::mdast::{AttributeContent, AttributeValue, Node};
 use tower_lsp::lsp_types::Position;
 
-const PARTIAL: &str = "$Partial";
+use crate::{ast::find_all_matches, config::ConfigValues, encoding::to_byte_position};
+
+pub mod esm;
+pub mod frontmatter;
+pub mod headings;
+pub mod partials;
+
+/// Default name of the element that includes a partial.
+pub const PARTIAL: &str = "$Partial";
 
 pub trait NodeExt {
-    fn contains_position(&self, position: &Position) -> bool;
+    fn contains_position(&self, text: &str, position: &Position) -> bool;
     fn is_partial(&self) -> bool;
+    fn is_any_jsx_element(&self) -> bool;
+    fn is_link(&self) -> bool;
+    fn get_name(&self) -> Option<&str>;
+    fn get_attribute(&self, name: &str) -> Option<&str>;
+    fn get_all_attributes(&self) -> Vec<&str>;
+    fn is_namespaced_partial(&self, namespace: &str) -> bool;
+    fn is_comment_expression(&self) -> bool;
+    fn text_content(&self) -> String;
 }
 
 impl NodeExt for Node {
-    fn contains_position(&self, position: &Position) -> bool {
+    /// Checks whether the node spans `position`, an LSP position in `text`,
+    /// the document the node was parsed from. Node columns count bytes, so
+    /// the position's UTF-16 column is converted first.
+    fn contains_position(&self, text: &str, position: &Position) -> bool {
+        let position = to_byte_position(text, position);
+        let target = (
+            (position.line + 1) as usize,
+            (position.character + 1) as usize,
+        );
+
         self.position()
             .map(|pos| {
-                pos.start.line <= (position.line + 1) as usize
-                    && pos.end.line >= (position.line + 1) as usize
-                    && pos.start.column <= (position.character + 1) as usize
-                    && pos.end.column >= (position.character + 1) as usize
+                (pos.start.line, pos.start.column) <= target
+                    && (pos.end.line, pos.end.column) >= target
             })
             .unwrap_or(false)
     }
 
     fn is_partial(&self) -> bool {
+        is_partial_element(self, PARTIAL)
+    }
+
+    fn is_any_jsx_element(&self) -> bool {
+        matches!(
+            self,
+            Node::MdxJsxFlowElement(_) | Node::MdxJsxTextElement(_)
+        )
+    }
+
+    fn is_link(&self) -> bool {
+        matches!(self, Node::Link(_))
+    }
+
+    fn get_name(&self) -> Option<&str> {
         match self {
-            Node::MdxJsxFlowElement(element) => {
-                element.name.as_ref().map_or(false, |name| name == PARTIAL)
-            }
-            _ => false,
+            Node::MdxJsxFlowElement(element) => element.name.as_deref(),
+            Node::MdxJsxTextElement(element) => element.name.as_deref(),
+            _ => None,
         }
     }
+
+    fn get_attribute(&self, name: &str) -> Option<&str> {
+        let attributes = match self {
+            Node::MdxJsxFlowElement(element) => &element.attributes,
+            Node::MdxJsxTextElement(element) => &element.attributes,
+            _ => return None,
+        };
+
+        attributes.iter().find_map(|attribute| match attribute {
+            AttributeContent::Property(property) if property.name == name => {
+                match &property.value {
+                    Some(AttributeValue::Literal(value)) => Some(value.as_str()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+    }
+
+    /// The names of the element's attributes, without spread expressions.
+    fn get_all_attributes(&self) -> Vec<&str> {
+        let attributes = match self {
+            Node::MdxJsxFlowElement(element) => &element.attributes,
+            Node::MdxJsxTextElement(element) => &element.attributes,
+            _ => return Vec::new(),
+        };
+
+        attributes
+            .iter()
+            .filter_map(|attribute| match attribute {
+                AttributeContent::Property(property) => Some(property.name.as_str()),
+                AttributeContent::Expression(_) => None,
+            })
+            .collect()
+    }
+
+    /// Checks whether the element is named `$<namespace>:<name>`, e.g.
+    /// `<$Docs:Hero>` for the `Docs` namespace.
+    fn is_namespaced_partial(&self, namespace: &str) -> bool {
+        let Node::MdxJsxFlowElement(element) = self else {
+            return false;
+        };
+
+        element
+            .name
+            .as_deref()
+            .and_then(|name| name.strip_prefix('$'))
+            .and_then(|name| name.strip_prefix(namespace))
+            .and_then(|name| name.strip_prefix(':'))
+            .is_some_and(|name| !name.is_empty())
+    }
+
+    /// Expressions holding only a comment are how MDX v2 writes comments.
+    fn is_comment_expression(&self) -> bool {
+        let value = match self {
+            Node::MdxFlowExpression(expression) => &expression.value,
+            Node::MdxTextExpression(expression) => &expression.value,
+            _ => return false,
+        };
+        let value = value.trim();
+        value.starts_with("/*") && value.ends_with("*/")
+    }
+
+    /// The text of the node, with a space between the values of separate text
+    /// nodes so that words from different blocks don't run together.
+    fn text_content(&self) -> String {
+        find_all_matches(self, |node| {
+            matches!(node, Node::Text(_) | Node::InlineCode(_))
+        })
+        .into_iter()
+        .map(|node| node.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+}
+
+/// Checks whether `node` includes a partial, given the configured name of the
+/// partial element (see `ConfigValues::get_partial_element_name`).
+pub fn is_partial_element(node: &Node, name: &str) -> bool {
+    match node {
+        Node::MdxJsxFlowElement(element) => element.name.as_deref() == Some(name),
+        _ => false,
+    }
+}
+
+/// Checks whether `node` includes a partial under the configured element
+/// name, or under the configured `partials_namespace`.
+pub fn is_configured_partial(node: &Node, config: &ConfigValues) -> bool {
+    is_partial_element(node, config.get_partial_element_name())
+        || config
+            .partials_namespace
+            .as_deref()
+            .is_some_and(|namespace| node.is_namespaced_partial(namespace))
 }
 
 #[cfg(test)]
@@ -40,16 +170,13 @@ mod tests {
 
     #[test]
     fn test_contains_position() {
-        let ast = to_mdast(
-            r#"
+        let text = r#"
 # Hello World
 
 This is a test.
 "#
-            .trim(),
-            &get_parser_options(),
-        )
-        .unwrap();
+        .trim();
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
         debug!("{:#?}", ast);
 
         let position = Position {
@@ -59,11 +186,39 @@ This is a test.
 
         let heading_node = ast.children().unwrap().get(0).unwrap();
         debug!("heading node: {:#?}", heading_node);
-        assert!(heading_node.contains_position(&position));
+        assert!(heading_node.contains_position(text, &position));
 
         let paragraph_node = ast.children().unwrap().get(1).unwrap();
         debug!("paragraph node: {:#?}", paragraph_node);
-        assert!(!paragraph_node.contains_position(&position));
+        assert!(!paragraph_node.contains_position(text, &position));
+    }
+
+    #[test]
+    fn test_contains_position_multiline() {
+        let text = r#"
+<Section>
+  A line that is longer than the closing tag.
+</Section>
+"#
+        .trim();
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let section = ast.children().unwrap().first().unwrap();
+        assert!(section.contains_position(
+            text,
+            &Position {
+                line: 1,
+                character: 30,
+            }
+        ));
+        assert!(!section.contains_position(
+            text,
+            &Position {
+                line: 2,
+                character: 30,
+            }
+        ));
     }
 
     #[test]
@@ -101,4 +256,135 @@ This is a test.
         let partial = ast.children().unwrap().get(1).unwrap();
         assert!(partial.is_partial());
     }
+
+    #[test]
+    fn test_is_partial_element() {
+        let ast = to_mdast(
+            "<Include src=\"hero.mdx\" />\n\n<$Partial />",
+            &get_parser_options(),
+        )
+        .unwrap();
+        debug!("{:#?}", ast);
+
+        let children = ast.children().unwrap();
+        assert!(is_partial_element(&children[0], "Include"));
+        assert!(!is_partial_element(&children[1], "Include"));
+        assert!(!children[0].is_partial());
+    }
+
+    #[test]
+    fn test_is_namespaced_partial() {
+        let ast = to_mdast(
+            "<$Docs:Hero src=\"hero.mdx\" />\n\n<$Docs />\n\n<$DocsX:Hero />\n\n<Docs:Hero />",
+            &get_parser_options(),
+        )
+        .unwrap();
+        debug!("{:#?}", ast);
+
+        let matches: Vec<_> = ast
+            .children()
+            .unwrap()
+            .iter()
+            .map(|node| node.is_namespaced_partial("Docs"))
+            .collect();
+        assert_eq!(matches, vec![true, false, false, false]);
+
+        let config = ConfigValues {
+            partials_namespace: Some("Docs".to_string()),
+            ..Default::default()
+        };
+        assert!(is_configured_partial(&ast.children().unwrap()[0], &config));
+        assert!(!is_configured_partial(
+            &ast.children().unwrap()[0],
+            &ConfigValues::default()
+        ));
+    }
+
+    #[test]
+    fn test_get_name() {
+        let ast = to_mdast(
+            r#"
+<Section>
+  Some <Badge /> text.
+</Section>
+"#
+            .trim(),
+            &get_parser_options(),
+        )
+        .unwrap();
+        debug!("{:#?}", ast);
+
+        let section = ast.children().unwrap().first().unwrap();
+        assert!(section.is_any_jsx_element());
+        assert_eq!(section.get_name(), Some("Section"));
+
+        let paragraph = section.children().unwrap().first().unwrap();
+        assert!(!paragraph.is_any_jsx_element());
+        assert_eq!(paragraph.get_name(), None);
+
+        let badge = paragraph.children().unwrap().get(1).unwrap();
+        assert!(badge.is_any_jsx_element());
+        assert_eq!(badge.get_name(), Some("Badge"));
+    }
+
+    #[test]
+    fn test_get_attribute() {
+        let ast = to_mdast(
+            r#"
+<$Partial src="hero.mdx" title={title} />
+"#
+            .trim(),
+            &get_parser_options(),
+        )
+        .unwrap();
+        debug!("{:#?}", ast);
+
+        let partial = ast.children().unwrap().first().unwrap();
+        assert_eq!(partial.get_attribute("src"), Some("hero.mdx"));
+        assert_eq!(partial.get_attribute("title"), None);
+        assert_eq!(partial.get_attribute("missing"), None);
+    }
+
+    #[test]
+    fn test_get_all_attributes() {
+        let ast = to_mdast(
+            r#"<Icon name="close" aria-hidden {...props} size={16} />"#,
+            &get_parser_options(),
+        )
+        .unwrap();
+        debug!("{:#?}", ast);
+
+        let icon = ast.children().unwrap().first().unwrap();
+        assert_eq!(
+            icon.get_all_attributes(),
+            vec!["name", "aria-hidden", "size"]
+        );
+    }
+
+    #[test]
+    fn test_is_link() {
+        let ast = to_mdast("[docs](./docs.mdx)", &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let paragraph = ast.children().unwrap().first().unwrap();
+        assert!(!paragraph.is_link());
+        assert!(paragraph.children().unwrap().first().unwrap().is_link());
+    }
+
+    #[test]
+    fn test_text_content() {
+        let ast = to_mdast(
+            "# Title\n\nSome *emphasized* `code`.",
+            &get_parser_options(),
+        )
+        .unwrap();
+        debug!("{:#?}", ast);
+
+        let words: Vec<_> = ast
+            .text_content()
+            .split_whitespace()
+            .map(String::from)
+            .collect();
+        assert_eq!(words, vec!["Title", "Some", "emphasized", "code", "."]);
+    }
 }
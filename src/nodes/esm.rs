@@ -0,0 +1,167 @@
+use markdown::mdast::Node;
+
+use crate::ast::find_all_matches;
+
+/// Returns the local names bound by the `import` statements of the document's
+/// ESM blocks: default imports, named imports (after any `as` rename) and
+/// namespace imports.
+pub fn get_imported_names(ast: &Node) -> Vec<String> {
+    get_imports(ast).into_iter().map(|(name, _)| name).collect()
+}
+
+/// Returns the names the document's ESM blocks declare: imported names, then
+/// exported or top-level `const`, `let`, `var`, `function` and `class`
+/// declarations.
+pub fn get_declared_names(ast: &Node) -> Vec<String> {
+    let mut names = get_imported_names(ast);
+
+    for esm in find_all_matches(ast, |node| matches!(node, Node::MdxjsEsm(_))) {
+        for line in esm.to_string().lines() {
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let line = line.strip_prefix("default ").unwrap_or(line);
+            let line = line.strip_prefix("async ").unwrap_or(line);
+            let Some(rest) = ["const ", "let ", "var ", "function ", "class "]
+                .iter()
+                .find_map(|keyword| line.strip_prefix(keyword))
+            else {
+                continue;
+            };
+
+            let name: String = rest
+                .trim_start()
+                .chars()
+                .take_while(|ch| ch.is_alphanumeric() || matches!(ch, '_' | '$'))
+                .collect();
+            if !name.is_empty() && !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+
+    names
+}
+
+/// Returns the module specifier of the import that binds `name`.
+pub fn get_import_source(ast: &Node, name: &str) -> Option<String> {
+    get_imports(ast)
+        .into_iter()
+        .find(|(imported, _)| imported == name)
+        .map(|(_, source)| source)
+}
+
+/// Returns each imported name with the module it's imported from.
+fn get_imports(ast: &Node) -> Vec<(String, String)> {
+    find_all_matches(ast, |node| matches!(node, Node::MdxjsEsm(_)))
+        .into_iter()
+        .flat_map(|esm| parse_imports(&esm.to_string()))
+        .collect()
+}
+
+fn parse_imports(esm: &str) -> Vec<(String, String)> {
+    let mut imports = Vec::new();
+
+    for statement in esm.split("import ").skip(1) {
+        let Some(end) = statement.find(" from ") else {
+            continue;
+        };
+        let clause = statement[..end].trim();
+        let source = statement[end + " from ".len()..]
+            .trim_start()
+            .split(['"', '\''])
+            .nth(1)
+            .unwrap_or_default();
+
+        let (default, named) = match clause.find('{') {
+            Some(open) => (
+                &clause[..open],
+                Some(&clause[open + 1..clause.rfind('}').unwrap_or(clause.len())]),
+            ),
+            None => (clause, None),
+        };
+
+        for binding in default
+            .split(',')
+            .chain(named.into_iter().flat_map(|named| named.split(',')))
+        {
+            let name = binding
+                .rsplit(" as ")
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .trim_start_matches("type ");
+            if !name.is_empty() && name != "*" {
+                imports.push((name.to_string(), source.to_string()));
+            }
+        }
+    }
+
+    imports
+}
+
+#[cfg(test)]
+mod tests {
+    use log::debug;
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    #[test]
+    fn test_get_imported_names() {
+        let ast = to_mdast(
+            r#"
+import Button from "./components/Button.mdx"
+import Card, { CardHeader, CardBody as Body } from "@acme/card"
+import * as Icons from "@acme/icons"
+import {
+  Tabs,
+  Tab,
+} from "@acme/tabs"
+
+export const meta = { title: "Hello" }
+
+# Hello
+"#
+            .trim(),
+            &get_parser_options(),
+        )
+        .unwrap();
+        debug!("{:#?}", ast);
+
+        assert_eq!(
+            get_imported_names(&ast),
+            vec![
+                "Button",
+                "Card",
+                "CardHeader",
+                "Body",
+                "Icons",
+                "Tabs",
+                "Tab"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_declared_names() {
+        let ast = to_mdast(
+            r#"
+import { Chart } from "./chart"
+export const data = [1, 2, 3]
+export function format(value) { return value }
+const local = 1
+
+<Chart data={data} />
+"#
+            .trim(),
+            &get_parser_options(),
+        )
+        .unwrap();
+        debug!("{:#?}", ast);
+
+        assert_eq!(
+            get_declared_names(&ast),
+            vec!["Chart", "data", "format", "local"]
+        );
+    }
+}
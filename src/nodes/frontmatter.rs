@@ -0,0 +1,69 @@
+use markdown::mdast::Node;
+
+/// Reads a top-level scalar from the YAML or TOML frontmatter, without the
+/// quotes around strings.
+pub fn get_frontmatter_value(ast: &Node, key: &str) -> Option<String> {
+    let frontmatter = ast
+        .children()?
+        .iter()
+        .find(|node| matches!(node, Node::Yaml(_) | Node::Toml(_)))?
+        .to_string();
+
+    frontmatter.lines().find_map(|line| {
+        if line.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let (name, value) = line.split_once([':', '='])?;
+        if name.trim() != key {
+            return None;
+        }
+        Some(value.trim().trim_matches(['"', '\'']).to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use log::debug;
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    #[test]
+    fn test_get_frontmatter_value() {
+        let ast = to_mdast(
+            r#"
+---
+title: "Hero section"
+draft: true
+nested:
+  title: Ignored
+---
+"#
+            .trim(),
+            &get_parser_options(),
+        )
+        .unwrap();
+        debug!("{:#?}", ast);
+
+        assert_eq!(
+            get_frontmatter_value(&ast, "title").as_deref(),
+            Some("Hero section")
+        );
+        assert_eq!(
+            get_frontmatter_value(&ast, "draft").as_deref(),
+            Some("true")
+        );
+        assert_eq!(get_frontmatter_value(&ast, "missing"), None);
+    }
+
+    #[test]
+    fn test_get_toml_frontmatter_value() {
+        let ast = to_mdast("+++\ntitle = 'Hero'\n+++", &get_parser_options()).unwrap();
+
+        assert_eq!(
+            get_frontmatter_value(&ast, "title").as_deref(),
+            Some("Hero")
+        );
+    }
+}
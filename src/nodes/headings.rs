@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use markdown::mdast::Node;
+
+use crate::ast::find_all_matches;
+
+/// Computes the anchor ID of a heading the way GitHub does: lowercase, with
+/// punctuation dropped and spaces turned into dashes.
+pub fn slug_from_heading(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|ch| match ch {
+            ' ' => Some('-'),
+            ch if ch.is_alphanumeric() || ch == '-' || ch == '_' => Some(ch),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns every heading of the document with its slug. Repeated slugs get a
+/// `-1`, `-2`... suffix, matching the anchors generated on render.
+pub fn get_heading_slugs(ast: &Node) -> Vec<(String, &Node)> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+
+    find_all_matches(ast, |node| matches!(node, Node::Heading(_)))
+        .into_iter()
+        .map(|heading| {
+            let slug = slug_from_heading(&heading.to_string());
+            let count = seen.entry(slug.clone()).or_default();
+            let slug = match *count {
+                0 => slug,
+                n => format!("{}-{}", slug, n),
+            };
+            *count += 1;
+
+            (slug, heading)
+        })
+        .collect()
+}
+
+/// Returns the top-level nodes of the section started by `heading`: the
+/// heading itself and everything up to the next heading of the same or a
+/// higher level.
+pub fn get_section<'a>(ast: &'a Node, heading: &Node) -> Vec<&'a Node> {
+    let Node::Heading(start) = heading else {
+        return Vec::new();
+    };
+    let Some(children) = ast.children() else {
+        return Vec::new();
+    };
+    let Some(index) = children.iter().position(|node| std::ptr::eq(node, heading)) else {
+        return Vec::new();
+    };
+
+    let mut section = vec![&children[index]];
+    section.extend(
+        children[index + 1..]
+            .iter()
+            .take_while(|node| !matches!(node, Node::Heading(next) if next.depth <= start.depth)),
+    );
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use log::debug;
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    #[test]
+    fn test_slug_from_heading() {
+        assert_eq!(slug_from_heading("Getting Started"), "getting-started");
+        assert_eq!(slug_from_heading("What's new in v2.0?"), "whats-new-in-v20");
+        assert_eq!(
+            slug_from_heading("snake_case & kebab-case"),
+            "snake_case--kebab-case"
+        );
+    }
+
+    #[test]
+    fn test_get_heading_slugs() {
+        let ast = to_mdast(
+            r#"
+# Install
+
+## Usage
+
+## Usage
+
+### *Advanced* usage
+"#
+            .trim(),
+            &get_parser_options(),
+        )
+        .unwrap();
+        debug!("{:#?}", ast);
+
+        let slugs: Vec<_> = get_heading_slugs(&ast)
+            .into_iter()
+            .map(|(slug, _)| slug)
+            .collect();
+        assert_eq!(slugs, vec!["install", "usage", "usage-1", "advanced-usage"]);
+    }
+
+    #[test]
+    fn test_get_section() {
+        let ast = to_mdast(
+            r#"
+# Guide
+
+## Setup
+
+Install it.
+
+### Details
+
+## Usage
+"#
+            .trim(),
+            &get_parser_options(),
+        )
+        .unwrap();
+        debug!("{:#?}", ast);
+
+        let setup = &ast.children().unwrap()[1];
+        let section: Vec<_> = get_section(&ast, setup)
+            .into_iter()
+            .map(|node| node.to_string())
+            .collect();
+        assert_eq!(section, vec!["Setup", "Install it.", "Details"]);
+    }
+}
@@ -0,0 +1,719 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    future::Future,
+    ops::Range,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use dashmap::DashMap;
+use futures::future::join_all;
+use log::warn;
+use markdown::{mdast::Node, to_mdast};
+use tokio::{fs, time::timeout};
+
+use crate::{
+    ast::find_all_matches,
+    config::ConfigValues,
+    nodes::{frontmatter::get_frontmatter_value, is_configured_partial, NodeExt},
+    parser::get_parser_options,
+    workspace::{find_files, has_extension, normalize_path},
+};
+
+const PARTIAL_EXTENSIONS: [&str; 2] = ["mdx", "md"];
+
+/// Resolves a `$Partial` `src` value against the configured partials
+/// directories, returning the first existing file. A `src` starting with one
+/// of the `aliases`, like `@partials/hero.mdx`, is only looked up in the
+/// aliased directory. A `src` without an extension is tried with each
+/// extension of `extension_fallback_chain`. Files are looked up in the
+/// scanned `partial_files` first, so that handlers don't block on the disk,
+/// then checked on disk, for files created since the scan.
+#[tracing::instrument(
+    level = "debug",
+    skip(config),
+    fields(element = config.get_partial_element_name())
+)]
+pub fn find_matching_partial(src: &str, config: &ConfigValues) -> Option<PathBuf> {
+    let candidates = get_candidate_paths(src, config);
+    let scanned = config.partial_files.as_ref().and_then(|files| {
+        candidates
+            .iter()
+            .find(|path| files.contains(&normalize_path(path)))
+    });
+
+    scanned
+        .or_else(|| candidates.iter().find(|path| path.is_file()))
+        .cloned()
+}
+
+/// Lists the files of the partials directories and the aliased directories,
+/// for `find_matching_partial` to resolve against. Gives up with `None` after
+/// `partial_scan_timeout_ms`.
+pub async fn scan_partial_files(config: &ConfigValues) -> Option<BTreeSet<PathBuf>> {
+    let dirs: Vec<PathBuf> = config
+        .partials_dirs
+        .iter()
+        .chain(config.aliases.values())
+        .cloned()
+        .collect();
+    let files = with_scan_timeout(config, find_files(&dirs, &[], |_| true)).await?;
+
+    Some(files.iter().map(|file| normalize_path(file)).collect())
+}
+
+/// Resolves a `src` like `find_matching_partial`, without blocking the
+/// executor: the partials directories are checked concurrently. Gives up
+/// with `None` after `partial_scan_timeout_ms`.
+pub async fn resolve_partial(src: &str, config: &ConfigValues) -> Option<PathBuf> {
+    let (dirs, src) = get_search_dirs(src, config);
+    let candidates = get_candidate_files(src, config);
+    let scans = dirs.into_iter().map(|dir| {
+        let candidates = &candidates;
+        async move {
+            for candidate in candidates {
+                let path = dir.join(candidate);
+                if fs::metadata(&path)
+                    .await
+                    .is_ok_and(|metadata| metadata.is_file())
+                {
+                    return Some(path);
+                }
+            }
+            None
+        }
+    });
+
+    // The first directory in `partials_dirs` order wins, as when resolving
+    // synchronously.
+    with_scan_timeout(config, join_all(scans))
+        .await?
+        .into_iter()
+        .flatten()
+        .next()
+}
+
+/// Runs a scan of the partials directories, logging a warning and returning
+/// `None` when it takes longer than `partial_scan_timeout_ms`.
+async fn with_scan_timeout<T>(config: &ConfigValues, scan: impl Future<Output = T>) -> Option<T> {
+    let duration = Duration::from_millis(config.partial_scan_timeout_ms);
+    match timeout(duration, scan).await {
+        Ok(result) => Some(result),
+        Err(_) => {
+            warn!(
+                "Scanning the partials directories took longer than {} ms",
+                config.partial_scan_timeout_ms
+            );
+            None
+        }
+    }
+}
+
+/// The file names, relative to a partials directory, that a `src` may
+/// resolve to, in order of preference.
+fn get_candidate_files(src: &str, config: &ConfigValues) -> Vec<String> {
+    if src.is_empty() {
+        Vec::new()
+    } else if Path::new(src).extension().is_some() {
+        vec![src.to_string()]
+    } else {
+        config
+            .extension_fallback_chain
+            .iter()
+            .map(|ext| format!("{}.{}", src, ext.trim_start_matches('.')))
+            .collect()
+    }
+}
+
+/// The paths a `src` may resolve to, in order of preference, whether or not
+/// they exist.
+fn get_candidate_paths(src: &str, config: &ConfigValues) -> Vec<PathBuf> {
+    let (dirs, src) = get_search_dirs(src, config);
+    let candidates = get_candidate_files(src, config);
+
+    dirs.into_iter()
+        .flat_map(|dir| candidates.iter().map(move |candidate| dir.join(candidate)))
+        .collect()
+}
+
+/// The directories to look a `src` up in, along with the path to look up in
+/// them: the aliased directory and the rest of the path when `src` starts
+/// with one of the `aliases`, or else the partials directories and `src`
+/// itself. The longest matching alias wins.
+pub fn get_search_dirs<'a>(src: &'a str, config: &'a ConfigValues) -> (Vec<&'a Path>, &'a str) {
+    let aliased = config
+        .aliases
+        .iter()
+        .filter_map(|(alias, dir)| {
+            let rest = src.strip_prefix(alias.as_str())?.strip_prefix('/')?;
+            Some((dir.as_path(), rest))
+        })
+        .min_by_key(|(_, rest)| rest.len());
+
+    match aliased {
+        Some((dir, rest)) => (vec![dir], rest),
+        None => (
+            config.partials_dirs.iter().map(PathBuf::as_path).collect(),
+            src,
+        ),
+    }
+}
+
+/// Checks whether a `$Partial` of the document may resolve to `path`. Unlike
+/// `find_matching_partial`, this works for files that were just deleted.
+pub fn includes_partial_file(ast: &Node, path: &Path, config: &ConfigValues) -> bool {
+    find_all_matches(ast, |node| is_configured_partial(node, config))
+        .into_iter()
+        .filter_map(|partial| partial.get_attribute("src"))
+        .any(|src| {
+            get_candidate_paths(src, config)
+                .iter()
+                .any(|candidate| candidate == path)
+        })
+}
+
+/// The `src` that resolves to `path`: relative to the partials directory
+/// containing it, or just the file name otherwise.
+pub fn get_partial_src(path: &Path, config: &ConfigValues) -> String {
+    config
+        .partials_dirs
+        .iter()
+        .find_map(|dir| path.strip_prefix(dir).ok())
+        .or_else(|| path.file_name().map(Path::new))
+        .map(|src| src.to_string_lossy().replace('\\', "/"))
+        .unwrap_or_default()
+}
+
+/// Maps each resolved partial file to the URIs of the documents in `ast_map`
+/// that include it.
+pub fn get_partial_references(
+    ast_map: &DashMap<String, Node>,
+    config: &ConfigValues,
+) -> HashMap<PathBuf, Vec<String>> {
+    let mut references: HashMap<PathBuf, Vec<String>> = HashMap::new();
+
+    for entry in ast_map.iter() {
+        for partial in find_all_matches(entry.value(), |node| is_configured_partial(node, config)) {
+            let Some(path) = partial
+                .get_attribute("src")
+                .and_then(|src| find_matching_partial(src, config))
+            else {
+                continue;
+            };
+
+            let uris = references.entry(path).or_default();
+            if !uris.contains(entry.key()) {
+                uris.push(entry.key().clone());
+            }
+        }
+    }
+
+    references
+}
+
+/// The content of a partial file as it should appear once inlined: without
+/// its frontmatter, and with the partials it includes inlined too, down to
+/// `depth` levels. Also tells whether partials deeper than that were left as
+/// references.
+pub fn read_inlined_partial(
+    path: &Path,
+    depth: usize,
+    config: &ConfigValues,
+) -> Option<(String, bool)> {
+    let text = std::fs::read_to_string(path).ok()?;
+    let ast = to_mdast(&text, &get_parser_options()).ok()?;
+
+    let body_start = ast
+        .children()
+        .and_then(|children| children.first())
+        .filter(|node| matches!(node, Node::Yaml(_) | Node::Toml(_)))
+        .and_then(|node| node.position())
+        .map_or(0, |position| position.end.offset);
+
+    let mut limit_hit = false;
+    let mut replacements: Vec<(Range<usize>, String)> = Vec::new();
+    for partial in find_all_matches(&ast, |node| is_configured_partial(node, config)) {
+        let (Some(position), Some(nested_path)) = (
+            partial.position(),
+            partial
+                .get_attribute("src")
+                .and_then(|src| find_matching_partial(src, config)),
+        ) else {
+            continue;
+        };
+        // A partial nested in one being inlined is replaced along with it.
+        let is_nested = replacements
+            .last()
+            .is_some_and(|(range, _)| range.end > position.start.offset);
+        if is_nested {
+            continue;
+        }
+        if depth == 0 {
+            limit_hit = true;
+            continue;
+        }
+
+        let Some((content, nested_limit_hit)) =
+            read_inlined_partial(&nested_path, depth - 1, config)
+        else {
+            continue;
+        };
+        limit_hit |= nested_limit_hit;
+        replacements.push((position.start.offset..position.end.offset, content));
+    }
+
+    let mut inlined = text;
+    for (range, content) in replacements.into_iter().rev() {
+        inlined.replace_range(range, &content);
+    }
+
+    Some((inlined[body_start..].trim().to_string(), limit_hit))
+}
+
+pub fn is_partial_file(path: &Path) -> bool {
+    has_extension(path, &PARTIAL_EXTENSIONS)
+}
+
+/// Recursively lists every partial file found under the given directories.
+pub async fn list_partial_files(partials_dirs: &[PathBuf]) -> Vec<PathBuf> {
+    find_files(partials_dirs, &[], is_partial_file).await
+}
+
+/// The title of a document: its `title` frontmatter field, or the text of its
+/// first heading.
+pub fn get_document_title(ast: &Node) -> Option<String> {
+    get_frontmatter_value(ast, "title")
+        .filter(|title| !title.is_empty())
+        .or_else(|| {
+            find_all_matches(ast, |node| matches!(node, Node::Heading(_)))
+                .first()
+                .map(|heading| {
+                    let text = heading.text_content();
+                    text.split_whitespace().collect::<Vec<_>>().join(" ")
+                })
+        })
+}
+
+/// Reads and parses every partial file to collect its title.
+pub async fn get_partial_titles(config: &ConfigValues) -> Vec<(PathBuf, String)> {
+    let mut titles = Vec::new();
+
+    for file in list_partial_files(&config.partials_dirs).await {
+        if let Some(title) = get_partial_title(&file).await {
+            titles.push((file, title));
+        }
+    }
+
+    titles
+}
+
+/// Reads and parses a partial file to get its title.
+pub async fn get_partial_title(path: &Path) -> Option<String> {
+    let text = fs::read_to_string(path).await.ok()?;
+    let ast = to_mdast(&text, &get_parser_options()).ok()?;
+    get_document_title(&ast)
+}
+
+/// Lists the partial files of every partials directory, in the order of
+/// `partials_dirs`, with the `src` value that references each one (e.g.
+/// `buttons/primary.mdx`). Within a directory, shallower files come first,
+/// then files are sorted alphabetically. The aliased directories follow, with
+/// `src` values starting with their alias. The directories are scanned
+/// concurrently, and none are listed when that takes longer than
+/// `partial_scan_timeout_ms`.
+pub async fn list_all_partials_in_dirs(config: &ConfigValues) -> Vec<(PathBuf, String)> {
+    let roots: Vec<(&PathBuf, String)> = config
+        .partials_dirs
+        .iter()
+        .map(|dir| (dir, String::new()))
+        .chain(
+            config
+                .aliases
+                .iter()
+                .map(|(alias, dir)| (dir, format!("{}/", alias))),
+        )
+        .collect();
+    let scans = roots
+        .iter()
+        .map(|(dir, _)| list_partial_files(std::slice::from_ref(*dir)));
+    let Some(listings) = with_scan_timeout(config, join_all(scans)).await else {
+        return Vec::new();
+    };
+
+    let mut partials = Vec::new();
+    for ((dir, prefix), files) in roots.iter().zip(listings) {
+        let mut files: Vec<(PathBuf, String)> = files
+            .into_iter()
+            .filter_map(|file| {
+                let src = file
+                    .strip_prefix(dir)
+                    .ok()?
+                    .to_string_lossy()
+                    .replace('\\', "/");
+                Some((file, format!("{}{}", prefix, src)))
+            })
+            .collect();
+        files.sort_by(|(_, a), (_, b)| {
+            a.matches('/')
+                .count()
+                .cmp(&b.matches('/').count())
+                .then_with(|| a.cmp(b))
+        });
+        // Nested aliases list the same files twice.
+        for (file, src) in files {
+            if !partials.iter().any(|(_, existing)| *existing == src) {
+                partials.push((file, src));
+            }
+        }
+    }
+
+    partials
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, sync::Arc};
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn create_partials_dir() -> TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("buttons")).unwrap();
+        fs::write(dir.path().join("hero.mdx"), "# Hero").unwrap();
+        fs::write(dir.path().join("notes.md"), "Notes").unwrap();
+        fs::write(dir.path().join("image.png"), "").unwrap();
+        fs::write(dir.path().join("buttons/primary.mdx"), "Primary").unwrap();
+        dir
+    }
+
+    fn create_config(dir: &TempDir) -> ConfigValues {
+        ConfigValues {
+            partials_dirs: vec![PathBuf::from("/does/not/exist"), dir.path().to_path_buf()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_find_matching_partial() {
+        let dir = create_partials_dir();
+        let config = create_config(&dir);
+
+        assert_eq!(
+            find_matching_partial("hero.mdx", &config),
+            Some(dir.path().join("hero.mdx"))
+        );
+        assert_eq!(
+            find_matching_partial("buttons/primary.mdx", &config),
+            Some(dir.path().join("buttons/primary.mdx"))
+        );
+        assert_eq!(find_matching_partial("missing.mdx", &config), None);
+        assert_eq!(find_matching_partial("buttons", &config), None);
+        assert_eq!(find_matching_partial("", &config), None);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_partial() {
+        let dir = create_partials_dir();
+        let other = tempfile::tempdir().unwrap();
+        fs::write(other.path().join("hero.mdx"), "# Other hero").unwrap();
+        fs::write(other.path().join("only.md"), "").unwrap();
+        let config = ConfigValues {
+            partials_dirs: vec![
+                PathBuf::from("/does/not/exist"),
+                dir.path().to_path_buf(),
+                other.path().to_path_buf(),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            resolve_partial("hero", &config).await,
+            Some(dir.path().join("hero.mdx"))
+        );
+        assert_eq!(
+            resolve_partial("only", &config).await,
+            Some(other.path().join("only.md"))
+        );
+        assert_eq!(resolve_partial("buttons", &config).await, None);
+        assert_eq!(resolve_partial("", &config).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_scan_partial_files() {
+        let dir = create_partials_dir();
+        let aliased = tempfile::tempdir().unwrap();
+        fs::write(aliased.path().join("banner.mdx"), "Banner").unwrap();
+        let mut config = create_config(&dir);
+        config
+            .aliases
+            .insert("@shared".to_string(), aliased.path().to_path_buf());
+
+        let files = scan_partial_files(&config).await.unwrap();
+        assert!(files.contains(&dir.path().join("buttons/primary.mdx")));
+        assert!(files.contains(&dir.path().join("image.png")));
+        assert!(files.contains(&aliased.path().join("banner.mdx")));
+        config.partial_files = Some(Arc::new(files));
+
+        // Scanned files resolve without checking the disk, and files created
+        // since are still found on it.
+        fs::remove_file(dir.path().join("hero.mdx")).unwrap();
+        fs::write(dir.path().join("new.mdx"), "New").unwrap();
+        assert_eq!(
+            find_matching_partial("./hero", &config),
+            Some(dir.path().join("./hero.mdx"))
+        );
+        assert_eq!(
+            find_matching_partial("@shared/banner", &config),
+            Some(aliased.path().join("banner.mdx"))
+        );
+        assert_eq!(
+            find_matching_partial("new.mdx", &config),
+            Some(dir.path().join("new.mdx"))
+        );
+        assert_eq!(find_matching_partial("missing.mdx", &config), None);
+    }
+
+    #[tokio::test]
+    async fn test_partial_scan_timeout() {
+        let config = ConfigValues {
+            partial_scan_timeout_ms: 10,
+            ..Default::default()
+        };
+
+        assert_eq!(with_scan_timeout(&config, async { 1 }).await, Some(1));
+        assert_eq!(
+            with_scan_timeout(&config, std::future::pending::<()>()).await,
+            None
+        );
+    }
+
+    #[test]
+    fn test_extension_fallback_first_extension() {
+        let dir = create_partials_dir();
+        fs::write(dir.path().join("hero.md"), "# Hero").unwrap();
+        let config = create_config(&dir);
+
+        assert_eq!(
+            find_matching_partial("hero", &config),
+            Some(dir.path().join("hero.mdx"))
+        );
+    }
+
+    #[test]
+    fn test_extension_fallback_second_extension() {
+        let dir = create_partials_dir();
+        let config = create_config(&dir);
+
+        assert_eq!(
+            find_matching_partial("notes", &config),
+            Some(dir.path().join("notes.md"))
+        );
+        assert_eq!(
+            find_matching_partial("buttons/primary", &config),
+            Some(dir.path().join("buttons/primary.mdx"))
+        );
+    }
+
+    #[test]
+    fn test_extension_fallback_exhausted() {
+        let dir = create_partials_dir();
+        let config = ConfigValues {
+            extension_fallback_chain: vec![".md".to_string()],
+            ..create_config(&dir)
+        };
+
+        assert_eq!(find_matching_partial("hero", &config), None);
+        assert_eq!(find_matching_partial("image", &config), None);
+    }
+
+    #[test]
+    fn test_extension_fallback_skipped_with_extension() {
+        let dir = create_partials_dir();
+        fs::write(dir.path().join("hero.txt.mdx"), "").unwrap();
+        let config = create_config(&dir);
+
+        assert_eq!(find_matching_partial("hero.txt", &config), None);
+    }
+
+    #[test]
+    fn test_includes_partial_file() {
+        let dir = create_partials_dir();
+        let config = create_config(&dir);
+        let ast = to_mdast(
+            "<$Partial src=\"hero\" />\n\n<$Partial src=\"gone.mdx\" />",
+            &get_parser_options(),
+        )
+        .unwrap();
+
+        assert!(includes_partial_file(
+            &ast,
+            &dir.path().join("hero.md"),
+            &config
+        ));
+        assert!(includes_partial_file(
+            &ast,
+            &dir.path().join("gone.mdx"),
+            &config
+        ));
+        assert!(!includes_partial_file(
+            &ast,
+            &dir.path().join("notes.md"),
+            &config
+        ));
+    }
+
+    #[test]
+    fn test_read_inlined_partial() {
+        let dir = create_partials_dir();
+        fs::write(
+            dir.path().join("page.mdx"),
+            "---\ntitle: Page\n---\n\n# Page\n\n<$Partial src=\"section.mdx\" />\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("section.mdx"),
+            "## Section\n\n<$Partial src=\"hero\" />\n\n<$Partial src=\"missing.mdx\" />",
+        )
+        .unwrap();
+        let config = create_config(&dir);
+        let page = dir.path().join("page.mdx");
+
+        assert_eq!(
+            read_inlined_partial(&page, 2, &config),
+            Some((
+                "# Page\n\n## Section\n\n# Hero\n\n<$Partial src=\"missing.mdx\" />".to_string(),
+                false
+            ))
+        );
+        assert_eq!(
+            read_inlined_partial(&page, 1, &config),
+            Some((
+                "# Page\n\n## Section\n\n<$Partial src=\"hero\" />\n\n<$Partial src=\"missing.mdx\" />"
+                    .to_string(),
+                true
+            ))
+        );
+        assert_eq!(
+            read_inlined_partial(&page, 0, &config),
+            Some((
+                "# Page\n\n<$Partial src=\"section.mdx\" />".to_string(),
+                true
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_partial_files() {
+        let dir = create_partials_dir();
+        let files = list_partial_files(&[dir.path().to_path_buf()]).await;
+
+        assert_eq!(
+            files,
+            vec![
+                dir.path().join("buttons/primary.mdx"),
+                dir.path().join("hero.mdx"),
+                dir.path().join("notes.md"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_all_partials_in_dirs() {
+        let dir = create_partials_dir();
+        fs::create_dir_all(dir.path().join("a")).unwrap();
+        fs::write(dir.path().join("a/alert.mdx"), "").unwrap();
+        let config = create_config(&dir);
+
+        let partials: Vec<_> = list_all_partials_in_dirs(&config)
+            .await
+            .into_iter()
+            .map(|(path, src)| (path.strip_prefix(dir.path()).unwrap().to_path_buf(), src))
+            .collect();
+
+        assert_eq!(
+            partials,
+            vec![
+                (PathBuf::from("hero.mdx"), "hero.mdx".to_string()),
+                (PathBuf::from("notes.md"), "notes.md".to_string()),
+                (PathBuf::from("a/alert.mdx"), "a/alert.mdx".to_string()),
+                (
+                    PathBuf::from("buttons/primary.mdx"),
+                    "buttons/primary.mdx".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_aliases() {
+        let dir = create_partials_dir();
+        let aliased = tempfile::tempdir().unwrap();
+        fs::create_dir_all(aliased.path().join("cards")).unwrap();
+        fs::write(aliased.path().join("banner.mdx"), "").unwrap();
+        fs::write(aliased.path().join("cards/card.md"), "").unwrap();
+        let config = ConfigValues {
+            aliases: [
+                ("@shared".to_string(), aliased.path().to_path_buf()),
+                ("@shared/cards".to_string(), aliased.path().join("cards")),
+            ]
+            .into_iter()
+            .collect(),
+            ..create_config(&dir)
+        };
+
+        assert_eq!(
+            find_matching_partial("@shared/banner", &config),
+            Some(aliased.path().join("banner.mdx"))
+        );
+        assert_eq!(
+            find_matching_partial("@shared/cards/card", &config),
+            Some(aliased.path().join("cards/card.md"))
+        );
+        // Aliased paths aren't looked up in the partials directories.
+        assert_eq!(find_matching_partial("@shared/hero.mdx", &config), None);
+        assert_eq!(find_matching_partial("@sharedbanner.mdx", &config), None);
+        assert_eq!(
+            resolve_partial("@shared/banner.mdx", &config).await,
+            Some(aliased.path().join("banner.mdx"))
+        );
+
+        let srcs: Vec<_> = list_all_partials_in_dirs(&config)
+            .await
+            .into_iter()
+            .map(|(_, src)| src)
+            .filter(|src| src.starts_with('@'))
+            .collect();
+        assert_eq!(srcs, vec!["@shared/banner.mdx", "@shared/cards/card.md"]);
+    }
+
+    #[test]
+    fn test_get_document_title() {
+        let title = |text: &str| {
+            let ast = to_mdast(text, &get_parser_options()).unwrap();
+            get_document_title(&ast)
+        };
+
+        assert_eq!(
+            title("---\ntitle: Hero Section\n---\n\n# Hero").as_deref(),
+            Some("Hero Section")
+        );
+        assert_eq!(
+            title("Intro.\n\n## The *hero*").as_deref(),
+            Some("The hero")
+        );
+        assert_eq!(title("No headings."), None);
+    }
+
+    #[tokio::test]
+    async fn test_get_partial_titles() {
+        let dir = create_partials_dir();
+        let config = create_config(&dir);
+
+        assert_eq!(
+            get_partial_titles(&config).await,
+            vec![(dir.path().join("hero.mdx"), "Hero".to_string())]
+        );
+    }
+}
@@ -1,10 +1,15 @@
-use markdown::{Constructs, ParseOptions};
+use std::path::Path;
+
+use markdown::{Constructs, MdxSignal, ParseOptions};
+
+use crate::config::{ConfigValues, ParserConfig};
 
 pub fn get_parser_options() -> ParseOptions {
     ParseOptions {
         constructs: Constructs {
             autolink: false,
             code_indented: false,
+            frontmatter: true,
             gfm_footnote_definition: true,
             gfm_label_start_footnote: true,
             gfm_table: true,
@@ -17,6 +22,86 @@ pub fn get_parser_options() -> ParseOptions {
             mdx_jsx_text: true,
             ..Default::default()
         },
+        // ESM is only recognized with a parser. It isn't validated here, so
+        // every `import`/`export` block becomes an `MdxjsEsm` node.
+        mdx_esm_parse: Some(Box::new(|_| MdxSignal::Ok)),
         ..Default::default()
     }
 }
+
+/// Returns the parser options for a file, applying the `[extensions.md]` or
+/// `[extensions.mdx]` overrides that match its extension.
+pub fn get_parser_options_for(path: &Path, config: &ConfigValues) -> ParseOptions {
+    let mut options = get_parser_options();
+    let overrides = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("md") => config.extensions.md.as_ref(),
+        Some("mdx") => config.extensions.mdx.as_ref(),
+        _ => None,
+    };
+    if let Some(overrides) = overrides {
+        apply_parser_config(&mut options.constructs, overrides);
+    }
+
+    options
+}
+
+fn apply_parser_config(constructs: &mut Constructs, config: &ParserConfig) {
+    let overrides = [
+        (&mut constructs.frontmatter, config.frontmatter),
+        (&mut constructs.gfm_table, config.gfm_table),
+        (&mut constructs.html_flow, config.html_flow),
+        (&mut constructs.html_text, config.html_text),
+        (&mut constructs.mdx_esm, config.mdx_esm),
+        (
+            &mut constructs.mdx_expression_flow,
+            config.mdx_expression_flow,
+        ),
+        (
+            &mut constructs.mdx_expression_text,
+            config.mdx_expression_text,
+        ),
+        (&mut constructs.mdx_jsx_flow, config.mdx_jsx_flow),
+        (&mut constructs.mdx_jsx_text, config.mdx_jsx_text),
+    ];
+    for (construct, value) in overrides {
+        if let Some(value) = value {
+            *construct = value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ExtensionsConfig;
+
+    #[test]
+    fn test_parser_options_for_extension() {
+        let config = ConfigValues {
+            extensions: ExtensionsConfig {
+                md: Some(ParserConfig {
+                    mdx_jsx_flow: Some(false),
+                    ..Default::default()
+                }),
+                mdx: Some(ParserConfig {
+                    html_flow: Some(true),
+                    ..Default::default()
+                }),
+            },
+            ..Default::default()
+        };
+
+        let md = get_parser_options_for(Path::new("/docs/readme.md"), &config);
+        assert!(!md.constructs.mdx_jsx_flow);
+        assert!(md.constructs.mdx_jsx_text);
+        assert!(!md.constructs.html_flow);
+
+        let mdx = get_parser_options_for(Path::new("/docs/index.mdx"), &config);
+        assert!(mdx.constructs.mdx_jsx_flow);
+        assert!(mdx.constructs.html_flow);
+
+        let other = get_parser_options_for(Path::new("/docs/notes.txt"), &config);
+        assert!(other.constructs.mdx_jsx_flow);
+        assert!(!other.constructs.html_flow);
+    }
+}
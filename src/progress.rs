@@ -0,0 +1,129 @@
+use tower_lsp::{
+    lsp_types::{
+        notification, request, MessageType, NumberOrString, ProgressParams, ProgressParamsValue,
+        WorkDoneProgress, WorkDoneProgressBegin, WorkDoneProgressCreateParams, WorkDoneProgressEnd,
+        WorkDoneProgressReport,
+    },
+    Client,
+};
+
+/// Reports a long-running task with `$/progress` notifications under a token
+/// of its own. Clients that don't support work done progress, or refuse to
+/// create the token, get a log message when the task starts and ends instead.
+#[derive(Debug)]
+pub struct ProgressReporter {
+    client: Client,
+    /// `None` when falling back to log messages.
+    token: Option<NumberOrString>,
+}
+
+impl ProgressReporter {
+    /// Starts reporting a task titled `title`. `supported` tells whether the
+    /// client advertised `window.workDoneProgress`.
+    pub async fn begin(
+        client: Client,
+        token: &str,
+        supported: bool,
+        title: &str,
+        message: Option<String>,
+    ) -> Self {
+        let token = NumberOrString::String(token.to_string());
+        let created = supported
+            && client
+                .send_request::<request::WorkDoneProgressCreate>(WorkDoneProgressCreateParams {
+                    token: token.clone(),
+                })
+                .await
+                .is_ok();
+
+        let reporter = Self {
+            client,
+            token: created.then_some(token),
+        };
+        if reporter.token.is_none() {
+            let message = match &message {
+                Some(message) => format!("{}: {}", title, message),
+                None => title.to_string(),
+            };
+            reporter
+                .client
+                .log_message(MessageType::INFO, message)
+                .await;
+            return reporter;
+        }
+
+        reporter
+            .send(WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                title: title.to_string(),
+                message,
+                percentage: Some(0),
+                ..Default::default()
+            }))
+            .await;
+        reporter
+    }
+
+    /// Reports that `done` of `total` steps are complete. Nothing is logged
+    /// when falling back to log messages.
+    pub async fn report(&self, done: usize, total: usize) {
+        if self.token.is_none() {
+            return;
+        }
+
+        self.send(WorkDoneProgress::Report(WorkDoneProgressReport {
+            message: Some(format!("{}/{}", done, total)),
+            percentage: Some(get_percentage(done, total)),
+            ..Default::default()
+        }))
+        .await;
+    }
+
+    pub async fn end(self, message: String) {
+        if self.token.is_none() {
+            self.client.log_message(MessageType::INFO, message).await;
+            return;
+        }
+
+        self.send(WorkDoneProgress::End(WorkDoneProgressEnd {
+            message: Some(message),
+        }))
+        .await;
+    }
+
+    async fn send(&self, value: WorkDoneProgress) {
+        let Some(token) = &self.token else {
+            return;
+        };
+
+        self.client
+            .send_notification::<notification::Progress>(ProgressParams {
+                token: token.clone(),
+                value: ProgressParamsValue::WorkDone(value),
+            })
+            .await;
+    }
+}
+
+/// The percentage of `total` that `done` makes up, rounded down. An empty task
+/// is complete.
+fn get_percentage(done: usize, total: usize) -> u32 {
+    if total == 0 {
+        return 100;
+    }
+
+    (done.min(total) * 100 / total) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_percentage() {
+        assert_eq!(get_percentage(0, 8), 0);
+        assert_eq!(get_percentage(3, 8), 37);
+        assert_eq!(get_percentage(8, 8), 100);
+        assert_eq!(get_percentage(9, 8), 100);
+        assert_eq!(get_percentage(0, 0), 100);
+    }
+}
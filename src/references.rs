@@ -0,0 +1,244 @@
+use std::path::Path;
+
+use dashmap::DashMap;
+use markdown::mdast::Node;
+use tower_lsp::lsp_types::{Location, Position, Url};
+
+use crate::{
+    ast::{find_all_matches, find_deepest_match, get_ancestor_chain},
+    config::ConfigValues,
+    encoding::get_node_range,
+    nodes::{
+        headings::get_heading_slugs, is_configured_partial, partials::find_matching_partial,
+        NodeExt,
+    },
+    workspace::normalize_path,
+};
+
+/// Finds the links pointing at the heading under the cursor, across every
+/// document in `ast_map`, whose text is in `text_map`. Returns `None` when the
+/// cursor isn't on a heading.
+pub fn get_heading_references(
+    ast: &Node,
+    text: &str,
+    uri: &Url,
+    position: &Position,
+    ast_map: &DashMap<String, Node>,
+    text_map: &DashMap<String, String>,
+    include_declaration: bool,
+) -> Option<Vec<Location>> {
+    let ancestor_chain = get_ancestor_chain(ast, text, position);
+    let heading = find_deepest_match(&ancestor_chain, |node| matches!(node, Node::Heading(_)))?;
+    let (slug, _) = get_heading_slugs(ast)
+        .into_iter()
+        .find(|(_, node)| std::ptr::eq(*node, heading))?;
+    let target = uri.to_file_path().ok();
+
+    let mut locations = Vec::new();
+    if include_declaration {
+        locations.push(Location::new(uri.clone(), get_node_range(text, heading)?));
+    }
+
+    for entry in ast_map.iter() {
+        let (Ok(document_uri), Some(document_text)) =
+            (Url::parse(entry.key()), text_map.get(entry.key()))
+        else {
+            continue;
+        };
+
+        for link in find_all_matches(entry.value(), |node| node.is_link()) {
+            let Node::Link(link_node) = link else {
+                continue;
+            };
+            let Some((path, fragment)) = link_node.url.split_once('#') else {
+                continue;
+            };
+            if fragment != slug || !links_to(&document_uri, path, uri, target.as_deref()) {
+                continue;
+            }
+            if let Some(range) = get_node_range(&document_text, link) {
+                locations.push(Location::new(document_uri.clone(), range));
+            }
+        }
+    }
+
+    Some(locations)
+}
+
+/// Finds the `$Partial` elements that include the document at `uri`, across
+/// every document in `ast_map`, whose text is in `text_map`. Returns `None`
+/// when the document isn't in one of the `partials_dirs`.
+pub fn get_partial_file_references(
+    uri: &Url,
+    ast_map: &DashMap<String, Node>,
+    text_map: &DashMap<String, String>,
+    config: &ConfigValues,
+) -> Option<Vec<Location>> {
+    let path = uri.to_file_path().ok()?;
+    if !config.partials_dirs.iter().any(|dir| path.starts_with(dir)) {
+        return None;
+    }
+
+    let mut locations = Vec::new();
+    for entry in ast_map.iter() {
+        let (Ok(document_uri), Some(document_text)) =
+            (Url::parse(entry.key()), text_map.get(entry.key()))
+        else {
+            continue;
+        };
+
+        for partial in find_all_matches(entry.value(), |node| is_configured_partial(node, config)) {
+            let resolved = partial
+                .get_attribute("src")
+                .and_then(|src| find_matching_partial(src, config));
+            if resolved.as_deref() != Some(path.as_path()) {
+                continue;
+            }
+            if let Some(range) = get_node_range(&document_text, partial) {
+                locations.push(Location::new(document_uri.clone(), range));
+            }
+        }
+    }
+
+    Some(locations)
+}
+
+/// Checks whether a link path written in `document_uri` points at the
+/// document `target_uri`. An empty path refers to the linking document itself.
+fn links_to(document_uri: &Url, path: &str, target_uri: &Url, target: Option<&Path>) -> bool {
+    if path.is_empty() {
+        return document_uri == target_uri;
+    }
+
+    let Some(document_dir) = document_uri
+        .to_file_path()
+        .ok()
+        .and_then(|file| file.parent().map(Path::to_path_buf))
+    else {
+        return false;
+    };
+    target.is_some_and(|target| normalize_path(&document_dir.join(path)) == target)
+}
+
+#[cfg(test)]
+mod tests {
+    use log::debug;
+    use markdown::to_mdast;
+    use tower_lsp::lsp_types::Range;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    fn open_document(
+        ast_map: &DashMap<String, Node>,
+        text_map: &DashMap<String, String>,
+        path: &str,
+        text: &str,
+    ) -> Url {
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let uri = Url::from_file_path(path).unwrap();
+        ast_map.insert(uri.to_string(), ast);
+        text_map.insert(uri.to_string(), text.to_string());
+        uri
+    }
+
+    #[test]
+    fn test_heading_references() {
+        let ast_map = DashMap::new();
+        let text_map = DashMap::new();
+        let text = "# Guide\n\n## Setup\n\nSee [setup](#setup).";
+        let guide = open_document(&ast_map, &text_map, "/docs/guide.mdx", text);
+        let index = open_document(
+            &ast_map,
+            &text_map,
+            "/docs/index.mdx",
+            "[Setup](./guide.mdx#setup) and [other](./other.mdx#setup)",
+        );
+
+        let ast = ast_map.get(&guide.to_string()).unwrap();
+        let position = Position {
+            line: 2,
+            character: 4,
+        };
+        let mut references =
+            get_heading_references(&ast, text, &guide, &position, &ast_map, &text_map, false)
+                .unwrap();
+        references.sort_by_key(|location| location.uri.to_string());
+
+        let locations: Vec<_> = references
+            .iter()
+            .map(|location| (location.uri.clone(), location.range.start))
+            .collect();
+        assert_eq!(
+            locations,
+            vec![
+                (
+                    guide.clone(),
+                    Position {
+                        line: 4,
+                        character: 4,
+                    }
+                ),
+                (
+                    index,
+                    Position {
+                        line: 0,
+                        character: 0,
+                    }
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_no_references_outside_heading() {
+        let ast_map = DashMap::new();
+        let text_map = DashMap::new();
+        let text = "# Guide\n\nSome text.";
+        let uri = open_document(&ast_map, &text_map, "/docs/guide.mdx", text);
+
+        let ast = ast_map.get(&uri.to_string()).unwrap();
+        let position = Position {
+            line: 2,
+            character: 2,
+        };
+        assert!(
+            get_heading_references(&ast, text, &uri, &position, &ast_map, &text_map, true)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_partial_file_references() {
+        let partials_dir = tempfile::tempdir().unwrap();
+        let hero_path = partials_dir.path().join("hero.mdx");
+        std::fs::write(&hero_path, "# Hero").unwrap();
+        let config = ConfigValues {
+            partials_dirs: vec![partials_dir.path().to_path_buf()],
+            ..Default::default()
+        };
+
+        let ast_map = DashMap::new();
+        let text_map = DashMap::new();
+        let index = open_document(
+            &ast_map,
+            &text_map,
+            "/docs/index.mdx",
+            "# Home\n\n<$Partial src=\"hero.mdx\" />\n\n<$Partial src=\"other.mdx\" />",
+        );
+        let hero = Url::from_file_path(&hero_path).unwrap();
+
+        let references = get_partial_file_references(&hero, &ast_map, &text_map, &config).unwrap();
+        assert_eq!(
+            references,
+            vec![Location::new(
+                index.clone(),
+                Range::new(Position::new(2, 0), Position::new(2, 27))
+            )]
+        );
+
+        assert!(get_partial_file_references(&index, &ast_map, &text_map, &config).is_none());
+    }
+}
@@ -0,0 +1,527 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use dashmap::DashMap;
+use markdown::mdast::Node;
+use tower_lsp::lsp_types::{
+    DocumentChangeOperation, DocumentChanges, OneOf, OptionalVersionedTextDocumentIdentifier,
+    Position, Range, RenameFile, ResourceOp, TextDocumentEdit, TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::{
+    ast::{find_all_matches, find_deepest_match, get_ancestor_chain},
+    config::ConfigValues,
+    encoding::offset_to_position,
+    nodes::{
+        is_configured_partial,
+        partials::{find_matching_partial, get_search_dirs},
+        NodeExt,
+    },
+    workspace::normalize_path,
+};
+
+/// Why a partial file can't be renamed along with its `src` values.
+#[derive(Debug, PartialEq)]
+pub enum RenameError {
+    /// The partial file isn't in any of the partials directories.
+    OutsidePartialsDirs(PathBuf),
+    /// The new `src` points outside of the directory the partial file is in.
+    LeavesPartialsDir(String),
+    /// The new `src` points at another file that already exists.
+    FileExists(PathBuf),
+    /// A document including the partial has no text in memory and couldn't be
+    /// read from disk, so its `src` values can't be updated.
+    UnreadableDocument(Url),
+}
+
+impl fmt::Display for RenameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenameError::OutsidePartialsDirs(path) => write!(
+                f,
+                "{} isn't in any of the partials directories",
+                path.display()
+            ),
+            RenameError::LeavesPartialsDir(src) => write!(
+                f,
+                "`{}` isn't in the partials directory of the renamed file",
+                src
+            ),
+            RenameError::FileExists(path) => write!(f, "{} already exists", path.display()),
+            RenameError::UnreadableDocument(uri) => {
+                write!(f, "{} couldn't be read to update its partials", uri)
+            }
+        }
+    }
+}
+
+/// Returns the range of the `$Partial` `src` value under the cursor, without
+/// its quotes, along with the value itself.
+pub fn prepare_partial_rename(
+    ast: &Node,
+    text: &str,
+    position: &Position,
+    config: &ConfigValues,
+) -> Option<(Range, String)> {
+    let ancestor_chain = get_ancestor_chain(ast, text, position);
+    let partial = find_deepest_match(&ancestor_chain, |node| is_configured_partial(node, config))?;
+    let src = partial.get_attribute("src")?;
+    let range = get_src_range(partial, text)?;
+    if position < &range.start || position > &range.end {
+        return None;
+    }
+
+    Some((range, src.to_string()))
+}
+
+/// Renames the `src` of the `$Partial` under the cursor to `new_name`, along
+/// with every `src` in `ast_map`, whose text is in `text_map`, that refers to
+/// the same partial file, and moves the file to where `new_name` resolves, in
+/// the same partials directory. When the old `src` doesn't resolve to a file,
+/// the references are the `src` values that match it exactly and no file is
+/// moved. Documents without text in `text_map` are read from disk, and the
+/// rename is refused when one of them can't be.
+pub fn rename_partial(
+    ast: &Node,
+    text: &str,
+    position: &Position,
+    new_name: &str,
+    ast_map: &DashMap<String, Node>,
+    text_map: &DashMap<String, String>,
+    config: &ConfigValues,
+) -> Result<Option<WorkspaceEdit>, RenameError> {
+    let Some((_, old_src)) = prepare_partial_rename(ast, text, position, config) else {
+        return Ok(None);
+    };
+    let old_path = find_matching_partial(&old_src, config).map(|path| normalize_path(&path));
+    let new_path = match &old_path {
+        Some(old_path) => Some(get_new_partial_path(old_path, new_name, config)?),
+        None => None,
+    };
+
+    let mut changes: HashMap<Url, Vec<TextEdit>> = HashMap::new();
+    for entry in ast_map.iter() {
+        let Ok(uri) = Url::parse(entry.key()) else {
+            continue;
+        };
+        let references: Vec<&Node> =
+            find_all_matches(entry.value(), |node| is_configured_partial(node, config))
+                .into_iter()
+                .filter(|partial| {
+                    partial
+                        .get_attribute("src")
+                        .is_some_and(|src| match &old_path {
+                            Some(old_path) => {
+                                find_matching_partial(src, config).as_ref() == Some(old_path)
+                            }
+                            None => src == old_src,
+                        })
+                })
+                .collect();
+        if references.is_empty() {
+            continue;
+        }
+
+        // A document whose text isn't kept isn't open, so it's as on disk.
+        let document_text = match text_map.get(entry.key()) {
+            Some(text) => text.clone(),
+            None => uri
+                .to_file_path()
+                .ok()
+                .and_then(|path| std::fs::read_to_string(path).ok())
+                .ok_or_else(|| RenameError::UnreadableDocument(uri.clone()))?,
+        };
+        for partial in references {
+            if let Some(range) = get_src_range(partial, &document_text) {
+                changes.entry(uri.clone()).or_default().push(TextEdit {
+                    range,
+                    new_text: new_name.to_string(),
+                });
+            }
+        }
+    }
+
+    let (Some(old_path), Some(new_path)) = (old_path, new_path) else {
+        return Ok(Some(WorkspaceEdit {
+            changes: Some(changes),
+            ..Default::default()
+        }));
+    };
+    let mut operations: Vec<_> = changes
+        .into_iter()
+        .map(|(uri, edits)| {
+            DocumentChangeOperation::Edit(TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier { uri, version: None },
+                edits: edits.into_iter().map(OneOf::Left).collect(),
+            })
+        })
+        .collect();
+    // The `src` values may only change spelling, like `hero` to `hero.mdx`.
+    if new_path != old_path {
+        if let (Ok(old_uri), Ok(new_uri)) = (
+            Url::from_file_path(&old_path),
+            Url::from_file_path(&new_path),
+        ) {
+            operations.push(DocumentChangeOperation::Op(ResourceOp::Rename(
+                RenameFile {
+                    old_uri,
+                    new_uri,
+                    options: None,
+                    annotation_id: None,
+                },
+            )));
+        }
+    }
+
+    Ok(Some(WorkspaceEdit {
+        document_changes: Some(DocumentChanges::Operations(operations)),
+        ..Default::default()
+    }))
+}
+
+/// Where the partial file at `old_path` moves to when it's included as
+/// `new_src`: the path `new_src` names in the partials directory that holds
+/// the file, with the file's extension when `new_src` has none.
+fn get_new_partial_path(
+    old_path: &Path,
+    new_src: &str,
+    config: &ConfigValues,
+) -> Result<PathBuf, RenameError> {
+    let old_dir = config
+        .partials_dirs
+        .iter()
+        .chain(config.aliases.values())
+        .filter(|dir| old_path.starts_with(dir))
+        .max_by_key(|dir| dir.components().count())
+        .ok_or_else(|| RenameError::OutsidePartialsDirs(old_path.to_path_buf()))?;
+
+    let (dirs, new_file) = get_search_dirs(new_src, config);
+    let mut new_path = normalize_path(&old_dir.join(new_file));
+    if !dirs.contains(&old_dir.as_path()) || new_path == *old_dir || !new_path.starts_with(old_dir)
+    {
+        return Err(RenameError::LeavesPartialsDir(new_src.to_string()));
+    }
+    if new_path.extension().is_none() {
+        if let Some(extension) = old_path.extension() {
+            new_path.set_extension(extension);
+        }
+    }
+
+    if new_path != old_path && new_path.exists() {
+        return Err(RenameError::FileExists(new_path));
+    }
+    Ok(new_path)
+}
+
+/// Finds the `src` value of a JSX element in the document text, since
+/// attributes carry no position of their own.
+pub fn get_src_range(element: &Node, text: &str) -> Option<Range> {
+    let position = element.position()?;
+    let source = text.get(position.start.offset..position.end.offset)?;
+
+    let mut search_from = 0;
+    let value_start = loop {
+        let index = search_from + source[search_from..].find("src=")?;
+        let preceded_by_space = source[..index]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_whitespace);
+        let quote = source[index + "src=".len()..].chars().next();
+        if preceded_by_space && matches!(quote, Some('"' | '\'')) {
+            break index + "src=".len() + 1;
+        }
+        search_from = index + "src=".len();
+    };
+    let quote = source.as_bytes()[value_start - 1] as char;
+    let value_end = value_start + source[value_start..].find(quote)?;
+
+    Some(Range {
+        start: offset_to_position(text, position.start.offset + value_start),
+        end: offset_to_position(text, position.start.offset + value_end),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use log::debug;
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    fn open_document(
+        ast_map: &DashMap<String, Node>,
+        text_map: &DashMap<String, String>,
+        path: &str,
+        text: &str,
+    ) -> Url {
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let uri = Url::from_file_path(path).unwrap();
+        ast_map.insert(uri.to_string(), ast);
+        text_map.insert(uri.to_string(), text.to_string());
+        uri
+    }
+
+    #[test]
+    fn test_prepare_partial_rename() {
+        let text = "# Héllo\n\n<$Partial title=\"src=x\" src='hero.mdx' />";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        assert_eq!(
+            prepare_partial_rename(&ast, text, &Position::new(2, 32), &ConfigValues::default()),
+            Some((
+                Range::new(Position::new(2, 29), Position::new(2, 37)),
+                "hero.mdx".to_string()
+            ))
+        );
+        assert_eq!(
+            prepare_partial_rename(&ast, text, &Position::new(2, 3), &ConfigValues::default()),
+            None
+        );
+        assert_eq!(
+            prepare_partial_rename(&ast, text, &Position::new(0, 3), &ConfigValues::default()),
+            None
+        );
+    }
+
+    fn rename(
+        path: &str,
+        new_name: &str,
+        ast_map: &DashMap<String, Node>,
+        text_map: &DashMap<String, String>,
+        config: &ConfigValues,
+    ) -> Result<Option<WorkspaceEdit>, RenameError> {
+        let uri = Url::from_file_path(path).unwrap().to_string();
+        let ast = ast_map.get(&uri).unwrap();
+        let text = text_map.get(&uri).unwrap();
+
+        rename_partial(
+            &ast,
+            &text,
+            &Position::new(0, 17),
+            new_name,
+            ast_map,
+            text_map,
+            config,
+        )
+    }
+
+    #[test]
+    fn test_rename_partial() {
+        let partials_dir = tempfile::tempdir().unwrap();
+        std::fs::write(partials_dir.path().join("hero.mdx"), "# Hero").unwrap();
+        let config = ConfigValues {
+            partials_dirs: vec![partials_dir.path().to_path_buf()],
+            ..Default::default()
+        };
+
+        let ast_map = DashMap::new();
+        let text_map = DashMap::new();
+        let index = open_document(
+            &ast_map,
+            &text_map,
+            "/docs/index.mdx",
+            "<$Partial src=\"hero.mdx\" />\n\n<$Partial src=\"other.mdx\" />",
+        );
+        let guide = open_document(
+            &ast_map,
+            &text_map,
+            "/docs/guide.mdx",
+            "# Guide\n\n<$Partial src=\"hero\" />",
+        );
+
+        let edit = rename(
+            "/docs/index.mdx",
+            "banners/banner",
+            &ast_map,
+            &text_map,
+            &config,
+        )
+        .unwrap()
+        .unwrap();
+
+        let Some(DocumentChanges::Operations(mut operations)) = edit.document_changes else {
+            panic!("expected document change operations");
+        };
+        assert_eq!(
+            operations.pop(),
+            Some(DocumentChangeOperation::Op(ResourceOp::Rename(
+                RenameFile {
+                    old_uri: Url::from_file_path(partials_dir.path().join("hero.mdx")).unwrap(),
+                    new_uri: Url::from_file_path(partials_dir.path().join("banners/banner.mdx"))
+                        .unwrap(),
+                    options: None,
+                    annotation_id: None,
+                }
+            )))
+        );
+
+        let changes: HashMap<_, _> = operations
+            .into_iter()
+            .filter_map(|operation| match operation {
+                DocumentChangeOperation::Edit(edit) => Some((edit.text_document.uri, edit.edits)),
+                DocumentChangeOperation::Op(_) => None,
+            })
+            .collect();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(
+            changes[&index],
+            vec![OneOf::Left(TextEdit {
+                range: Range::new(Position::new(0, 15), Position::new(0, 23)),
+                new_text: "banners/banner".to_string(),
+            })]
+        );
+        assert_eq!(
+            changes[&guide],
+            vec![OneOf::Left(TextEdit {
+                range: Range::new(Position::new(2, 15), Position::new(2, 19)),
+                new_text: "banners/banner".to_string(),
+            })]
+        );
+    }
+
+    #[test]
+    fn test_rename_in_indexed_document() {
+        let workspace = tempfile::tempdir().unwrap();
+        let partials_dir = workspace.path().join("partials");
+        std::fs::create_dir(&partials_dir).unwrap();
+        std::fs::write(partials_dir.join("hero.mdx"), "# Hero").unwrap();
+        let config = ConfigValues {
+            partials_dirs: vec![partials_dir],
+            ..Default::default()
+        };
+
+        let ast_map = DashMap::new();
+        let text_map = DashMap::new();
+        open_document(
+            &ast_map,
+            &text_map,
+            "/docs/index.mdx",
+            "<$Partial src=\"hero.mdx\" />",
+        );
+        // Indexed but not open: only the AST is in memory.
+        let guide_path = workspace.path().join("guide.mdx");
+        let guide_text = "# Guide\n\n<$Partial src=\"hero\" />";
+        std::fs::write(&guide_path, guide_text).unwrap();
+        let guide = Url::from_file_path(&guide_path).unwrap();
+        ast_map.insert(
+            guide.to_string(),
+            to_mdast(guide_text, &get_parser_options()).unwrap(),
+        );
+
+        let edit = rename("/docs/index.mdx", "banner", &ast_map, &text_map, &config)
+            .unwrap()
+            .unwrap();
+        let Some(DocumentChanges::Operations(operations)) = edit.document_changes else {
+            panic!("expected document change operations");
+        };
+        assert!(
+            operations.contains(&DocumentChangeOperation::Edit(TextDocumentEdit {
+                text_document: OptionalVersionedTextDocumentIdentifier {
+                    uri: guide.clone(),
+                    version: None,
+                },
+                edits: vec![OneOf::Left(TextEdit {
+                    range: Range::new(Position::new(2, 15), Position::new(2, 19)),
+                    new_text: "banner".to_string(),
+                })],
+            }))
+        );
+
+        // Without the file, the document's `src` can't be updated.
+        std::fs::remove_file(&guide_path).unwrap();
+        assert_eq!(
+            rename("/docs/index.mdx", "banner", &ast_map, &text_map, &config),
+            Err(RenameError::UnreadableDocument(guide))
+        );
+    }
+
+    #[test]
+    fn test_rename_unresolved_partial() {
+        let ast_map = DashMap::new();
+        let text_map = DashMap::new();
+        let index = open_document(
+            &ast_map,
+            &text_map,
+            "/docs/index.mdx",
+            "<$Partial src=\"missing.mdx\" />",
+        );
+
+        let edit = rename(
+            "/docs/index.mdx",
+            "../outside.mdx",
+            &ast_map,
+            &text_map,
+            &ConfigValues::default(),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(edit.document_changes, None);
+        assert_eq!(edit.changes.unwrap()[&index].len(), 1);
+    }
+
+    #[test]
+    fn test_invalid_partial_file_rename() {
+        let partials_dir = tempfile::tempdir().unwrap();
+        std::fs::write(partials_dir.path().join("hero.mdx"), "# Hero").unwrap();
+        std::fs::write(partials_dir.path().join("footer.mdx"), "Footer").unwrap();
+        let config = ConfigValues {
+            partials_dirs: vec![partials_dir.path().to_path_buf()],
+            ..Default::default()
+        };
+
+        let ast_map = DashMap::new();
+        let text_map = DashMap::new();
+        open_document(
+            &ast_map,
+            &text_map,
+            "/docs/index.mdx",
+            "<$Partial src=\"hero.mdx\" />",
+        );
+
+        assert_eq!(
+            rename(
+                "/docs/index.mdx",
+                "../hero.mdx",
+                &ast_map,
+                &text_map,
+                &config
+            ),
+            Err(RenameError::LeavesPartialsDir("../hero.mdx".to_string()))
+        );
+        assert_eq!(
+            rename("/docs/index.mdx", "footer", &ast_map, &text_map, &config),
+            Err(RenameError::FileExists(
+                partials_dir.path().join("footer.mdx")
+            ))
+        );
+
+        // A `src` that resolves outside of the partials directories
+        let config = ConfigValues {
+            partials_dirs: vec![partials_dir.path().join("nested")],
+            ..Default::default()
+        };
+        std::fs::create_dir(partials_dir.path().join("nested")).unwrap();
+        text_map.insert(
+            Url::from_file_path("/docs/index.mdx").unwrap().to_string(),
+            "<$Partial src=\"../hero.mdx\" />".to_string(),
+        );
+        ast_map.insert(
+            Url::from_file_path("/docs/index.mdx").unwrap().to_string(),
+            to_mdast("<$Partial src=\"../hero.mdx\" />", &get_parser_options()).unwrap(),
+        );
+        assert_eq!(
+            rename("/docs/index.mdx", "hero.mdx", &ast_map, &text_map, &config),
+            Err(RenameError::OutsidePartialsDirs(
+                partials_dir.path().join("hero.mdx")
+            ))
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use markdown::mdast::Node;
+use tower_lsp::lsp_types::{Position, Range, SelectionRange};
+
+use crate::{
+    ast::{find_heading_chain, get_ancestor_chain},
+    encoding::get_node_range,
+    nodes::headings::get_section,
+};
+
+/// Returns one selection range per position, each built from its own
+/// ancestor chain.
+pub fn get_selection_ranges(ast: &Node, text: &str, positions: &[Position]) -> Vec<SelectionRange> {
+    positions
+        .iter()
+        .map(|position| get_selection_range(ast, text, position))
+        .collect()
+}
+
+/// Expands from the innermost node through its ancestors. Between the
+/// top-level block and the whole document, the selection grows through the
+/// sections of the enclosing headings, innermost first.
+fn get_selection_range(ast: &Node, text: &str, position: &Position) -> SelectionRange {
+    let ancestor_chain = get_ancestor_chain(ast, text, position);
+
+    let mut ranges: Vec<Range> = ancestor_chain
+        .iter()
+        .skip(1)
+        .rev()
+        .filter_map(|node| get_node_range(text, node))
+        .collect();
+    ranges.extend(
+        find_heading_chain(ast, text, position)
+            .into_iter()
+            .rev()
+            .filter_map(|heading| get_section_range(ast, text, heading)),
+    );
+    ranges.extend(get_node_range(text, ast));
+
+    // Every range must contain the previous one, and repeating a range would
+    // make an expansion step do nothing.
+    let mut expansion: Vec<Range> = Vec::new();
+    for range in ranges {
+        match expansion.last() {
+            Some(last) if *last == range || !contains(&range, last) => {}
+            _ => expansion.push(range),
+        }
+    }
+
+    let fallback = Range {
+        start: *position,
+        end: *position,
+    };
+    expansion
+        .into_iter()
+        .rev()
+        .fold(None, |parent, range| {
+            Some(SelectionRange {
+                range,
+                parent: parent.map(Box::new),
+            })
+        })
+        .unwrap_or(SelectionRange {
+            range: fallback,
+            parent: None,
+        })
+}
+
+fn get_section_range(ast: &Node, text: &str, heading: &Node) -> Option<Range> {
+    let section = get_section(ast, heading);
+    let start = get_node_range(text, section.first()?)?.start;
+    let end = get_node_range(text, section.last()?)?.end;
+
+    Some(Range { start, end })
+}
+
+fn contains(outer: &Range, inner: &Range) -> bool {
+    let key = |position: &Position| (position.line, position.character);
+    key(&outer.start) <= key(&inner.start) && key(&inner.end) <= key(&outer.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use log::debug;
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    fn get_lines(selection: &SelectionRange) -> Vec<(u32, u32)> {
+        let mut lines = Vec::new();
+        let mut current = Some(selection);
+        while let Some(selection) = current {
+            lines.push((selection.range.start.line, selection.range.end.line));
+            current = selection.parent.as_deref();
+        }
+        lines
+    }
+
+    #[test]
+    fn test_selection_expands_through_sections() {
+        let text = r#"
+# Guide
+
+## Setup
+
+Install *it*.
+
+More text.
+
+## Usage
+"#
+        .trim();
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let selections = get_selection_ranges(
+            &ast,
+            text,
+            &[Position {
+                line: 4,
+                character: 10,
+            }],
+        );
+
+        // Text, emphasis, paragraph, `## Setup` section, `# Guide` section
+        // (same as the document, so merged into it).
+        assert_eq!(
+            get_lines(&selections[0]),
+            vec![(4, 4), (4, 4), (4, 4), (2, 6), (0, 8)]
+        );
+    }
+
+    #[test]
+    fn test_selection_without_headings() {
+        let text = "Some text.\n\nMore.";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+
+        let selections = get_selection_ranges(
+            &ast,
+            text,
+            &[Position {
+                line: 2,
+                character: 1,
+            }],
+        );
+
+        // The text spans the whole paragraph, so they are a single step.
+        assert_eq!(get_lines(&selections[0]), vec![(2, 2), (0, 2)]);
+    }
+
+    #[test]
+    fn test_selection_counts_utf16_columns() {
+        let text = "Café 😀 *it* here.";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        // Inside `it`, which starts at UTF-16 column 9 and byte column 12.
+        let selections = get_selection_ranges(&ast, text, &[Position::new(0, 10)]);
+
+        let mut ranges = Vec::new();
+        let mut current = Some(&selections[0]);
+        while let Some(selection) = current {
+            ranges.push((
+                selection.range.start.character,
+                selection.range.end.character,
+            ));
+            current = selection.parent.as_deref();
+        }
+        assert_eq!(ranges, vec![(9, 11), (8, 12), (0, 18)]);
+    }
+}
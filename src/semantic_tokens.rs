@@ -0,0 +1,520 @@
+use markdown::mdast::Node;
+use tower_lsp::lsp_types::{
+    SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensLegend,
+};
+
+use crate::{
+    ast::find_all_matches,
+    encoding::{offset_to_position, utf16_len},
+    nodes::{esm::get_imported_names, NodeExt},
+};
+
+pub const TOKEN_TYPES: [SemanticTokenType; 7] = [
+    SemanticTokenType::PROPERTY,
+    SemanticTokenType::STRING,
+    SemanticTokenType::NUMBER,
+    SemanticTokenType::KEYWORD,
+    SemanticTokenType::CLASS,
+    SemanticTokenType::FUNCTION,
+    SemanticTokenType::VARIABLE,
+];
+
+/// Bits of the modifiers set, in the order of `get_legend`.
+pub const DEFINITION: u32 = 1;
+pub const REFERENCE: u32 = 1 << 1;
+
+pub fn get_legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: TOKEN_TYPES.to_vec(),
+        token_modifiers: vec![
+            SemanticTokenModifier::DEFINITION,
+            SemanticTokenModifier::new("reference"),
+        ],
+    }
+}
+
+/// A token with an absolute position. Columns and lengths are UTF-16 code
+/// units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub line: u32,
+    pub start: u32,
+    pub length: u32,
+    pub token_type: SemanticTokenType,
+    /// Bitset of `DEFINITION` and `REFERENCE`.
+    pub modifiers: u32,
+}
+
+/// Scans YAML frontmatter line by line for `key: value` pairs and list items.
+/// It doesn't handle multi-line scalars or flow collections, which are left
+/// untokenized.
+pub struct FrontmatterTokenizer<'a> {
+    yaml: &'a str,
+    /// Line of the document on which the YAML starts, after the opening `---`.
+    first_line: u32,
+}
+
+impl<'a> FrontmatterTokenizer<'a> {
+    pub fn new(yaml: &'a str, first_line: u32) -> Self {
+        Self { yaml, first_line }
+    }
+
+    pub fn tokens(&self) -> Vec<Token> {
+        let mut tokens = Vec::new();
+
+        for (index, line) in self.yaml.lines().enumerate() {
+            let line_number = self.first_line + index as u32;
+            let mut rest = line.trim_start();
+            if rest.is_empty() || rest.starts_with('#') {
+                continue;
+            }
+            while let Some(item) = rest.strip_prefix("- ") {
+                rest = item.trim_start();
+            }
+            // Every part is a subslice of `line`, so its offset locates it.
+            let column =
+                |part: &str| utf16_len(&line[..part.as_ptr() as usize - line.as_ptr() as usize]);
+
+            let value = match find_key_end(rest) {
+                Some(key_end) => {
+                    tokens.push(Token {
+                        line: line_number,
+                        start: column(rest),
+                        length: utf16_len(&rest[..key_end]),
+                        token_type: SemanticTokenType::PROPERTY,
+                        modifiers: 0,
+                    });
+                    &rest[key_end + 1..]
+                }
+                None => rest,
+            };
+
+            let value = strip_comment(value).trim();
+            if let Some(token_type) = classify_value(value) {
+                tokens.push(Token {
+                    line: line_number,
+                    start: column(value),
+                    length: utf16_len(value),
+                    token_type,
+                    modifiers: 0,
+                });
+            }
+        }
+
+        tokens
+    }
+}
+
+/// Returns the byte index of the `:` ending the key, if the line has one.
+fn find_key_end(line: &str) -> Option<usize> {
+    if let Some(quote) = line.chars().next().filter(|ch| matches!(ch, '"' | '\'')) {
+        let close = line[1..].find(quote)? + 1;
+        return line[close + 1..].starts_with(':').then_some(close + 1);
+    }
+
+    line.char_indices()
+        .find(|(index, ch)| {
+            *ch == ':'
+                && line[index + 1..]
+                    .chars()
+                    .next()
+                    .is_none_or(char::is_whitespace)
+        })
+        .map(|(index, _)| index)
+}
+
+/// Removes a trailing ` # comment` outside of quotes.
+fn strip_comment(value: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (index, ch) in value.char_indices() {
+        match quote {
+            Some(open) if ch == open => quote = None,
+            Some(_) => {}
+            None if matches!(ch, '"' | '\'') => quote = Some(ch),
+            None if ch == '#' && previous.is_whitespace() => return &value[..index],
+            None => {}
+        }
+        previous = ch;
+    }
+    value
+}
+
+fn classify_value(value: &str) -> Option<SemanticTokenType> {
+    if value.is_empty() || value.starts_with(['[', '{', '|', '>', '&', '*']) {
+        return None;
+    }
+
+    Some(match value {
+        "true" | "false" | "True" | "False" | "TRUE" | "FALSE" => SemanticTokenType::KEYWORD,
+        _ if value.parse::<f64>().is_ok() => SemanticTokenType::NUMBER,
+        _ => SemanticTokenType::STRING,
+    })
+}
+
+/// Finds the MDX syntax of a document: JSX element and attribute names,
+/// single-line expressions, and the names bound by imports. Comment
+/// expressions are left to the editor's grammar.
+pub struct MdxTokenizer<'a> {
+    ast: &'a Node,
+    text: &'a str,
+}
+
+impl<'a> MdxTokenizer<'a> {
+    pub fn new(ast: &'a Node, text: &'a str) -> Self {
+        Self { ast, text }
+    }
+
+    pub fn tokens(&self) -> Vec<Token> {
+        let mut tokens = Vec::new();
+
+        for node in find_all_matches(self.ast, |node| {
+            node.is_any_jsx_element()
+                || matches!(
+                    node,
+                    Node::MdxFlowExpression(_) | Node::MdxTextExpression(_) | Node::MdxjsEsm(_)
+                )
+        }) {
+            match node {
+                Node::MdxjsEsm(_) => tokens.extend(self.import_tokens(node)),
+                Node::MdxFlowExpression(_) | Node::MdxTextExpression(_) => {
+                    tokens.extend(self.expression_token(node))
+                }
+                _ => tokens.extend(self.element_tokens(node)),
+            }
+        }
+
+        tokens
+    }
+
+    /// The element name, in the opening and closing tags, and its attribute
+    /// names, which are looked up in the source since they carry no position.
+    fn element_tokens(&self, element: &Node) -> Vec<Token> {
+        let (Some(position), Some(name)) = (element.position(), element.get_name()) else {
+            return Vec::new();
+        };
+        let Some(source) = self.text.get(position.start.offset..position.end.offset) else {
+            return Vec::new();
+        };
+        let start = position.start.offset;
+
+        let mut tokens = Vec::new();
+        if source[1..].starts_with(name) {
+            tokens.extend(self.token(start + 1, name, SemanticTokenType::CLASS, REFERENCE));
+        }
+
+        let mut cursor = 1 + name.len();
+        for attribute in element.get_all_attributes() {
+            let Some(index) = find_attribute(&source[cursor..], attribute) else {
+                break;
+            };
+            let attribute_start = cursor + index;
+            tokens.extend(self.token(
+                start + attribute_start,
+                attribute,
+                SemanticTokenType::PROPERTY,
+                0,
+            ));
+            cursor = skip_value(source, attribute_start + attribute.len());
+        }
+
+        let closing_tag = format!("</{}>", name);
+        if source.ends_with(&closing_tag) {
+            let offset = start + source.len() - closing_tag.len() + 2;
+            tokens.extend(self.token(offset, name, SemanticTokenType::CLASS, REFERENCE));
+        }
+
+        tokens
+    }
+
+    /// The content of an expression between its braces, when it fits on one
+    /// line.
+    fn expression_token(&self, expression: &Node) -> Option<Token> {
+        if expression.is_comment_expression() {
+            return None;
+        }
+        let position = expression.position()?;
+        let content = self
+            .text
+            .get(position.start.offset + 1..position.end.offset.checked_sub(1)?)?;
+        if content.contains('\n') {
+            return None;
+        }
+        let trimmed = content.trim_start();
+        let offset = position.start.offset + 1 + content.len() - trimmed.len();
+
+        self.token(offset, trimmed.trim_end(), SemanticTokenType::VARIABLE, 0)
+    }
+
+    /// The first occurrence of each imported name in the ESM block, which is
+    /// where the import binds it.
+    fn import_tokens(&self, esm: &Node) -> Vec<Token> {
+        let Some(position) = esm.position() else {
+            return Vec::new();
+        };
+        let Some(source) = self.text.get(position.start.offset..position.end.offset) else {
+            return Vec::new();
+        };
+
+        get_imported_names(esm)
+            .iter()
+            .filter_map(|name| {
+                let index = find_word(source, name)?;
+                self.token(
+                    position.start.offset + index,
+                    name,
+                    SemanticTokenType::FUNCTION,
+                    DEFINITION,
+                )
+            })
+            .collect()
+    }
+
+    fn token(
+        &self,
+        offset: usize,
+        value: &str,
+        token_type: SemanticTokenType,
+        modifiers: u32,
+    ) -> Option<Token> {
+        if value.is_empty() {
+            return None;
+        }
+        let start = offset_to_position(self.text, offset);
+
+        Some(Token {
+            line: start.line,
+            start: start.character,
+            length: utf16_len(value),
+            token_type,
+            modifiers,
+        })
+    }
+}
+
+/// Returns the index of the attribute `name` in a tag's source: a whole word
+/// after whitespace, followed by `=`, whitespace or the end of the tag.
+fn find_attribute(source: &str, name: &str) -> Option<usize> {
+    source
+        .match_indices(name)
+        .map(|(index, _)| index)
+        .find(|index| {
+            let before = source[..*index].chars().next_back();
+            let after = source[index + name.len()..].chars().next();
+            before.is_some_and(char::is_whitespace)
+                && after.is_none_or(|ch| ch == '=' || ch == '/' || ch == '>' || ch.is_whitespace())
+        })
+}
+
+/// Returns the index after an attribute's `="value"` or `={expression}`, if
+/// it has one, starting at the end of its name.
+fn skip_value(source: &str, index: usize) -> usize {
+    let Some(value) = source[index..].strip_prefix('=') else {
+        return index;
+    };
+    let value_start = index + 1;
+
+    match value.chars().next() {
+        Some(quote @ ('"' | '\'')) => value[1..]
+            .find(quote)
+            .map_or(source.len(), |end| value_start + end + 2),
+        Some('{') => {
+            let mut depth = 0;
+            for (offset, ch) in value.char_indices() {
+                match ch {
+                    '{' => depth += 1,
+                    '}' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return value_start + offset + 1;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            source.len()
+        }
+        _ => value_start,
+    }
+}
+
+/// Returns the index of the first occurrence of `word` not inside a longer
+/// identifier.
+fn find_word(source: &str, word: &str) -> Option<usize> {
+    let is_identifier = |ch: char| ch.is_alphanumeric() || matches!(ch, '_' | '$');
+
+    source
+        .match_indices(word)
+        .map(|(index, _)| index)
+        .find(|index| {
+            !source[..*index]
+                .chars()
+                .next_back()
+                .is_some_and(is_identifier)
+                && !source[index + word.len()..]
+                    .chars()
+                    .next()
+                    .is_some_and(is_identifier)
+        })
+}
+
+/// Returns the semantic tokens of the document, relative to each other as LSP
+/// expects.
+pub fn get_semantic_tokens(ast: &Node, text: &str) -> Vec<SemanticToken> {
+    let mut tokens: Vec<Token> = ast
+        .children()
+        .into_iter()
+        .flatten()
+        .filter_map(|node| match node {
+            Node::Yaml(yaml) => {
+                let first_line = yaml.position.as_ref()?.start.line as u32;
+                Some(FrontmatterTokenizer::new(&yaml.value, first_line).tokens())
+            }
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    tokens.extend(MdxTokenizer::new(ast, text).tokens());
+    tokens.sort_by_key(|token| (token.line, token.start));
+
+    encode_tokens(&tokens)
+}
+
+fn encode_tokens(tokens: &[Token]) -> Vec<SemanticToken> {
+    let mut previous = (0, 0);
+    tokens
+        .iter()
+        .map(|token| {
+            let delta_line = token.line - previous.0;
+            let delta_start = if delta_line == 0 {
+                token.start - previous.1
+            } else {
+                token.start
+            };
+            previous = (token.line, token.start);
+
+            SemanticToken {
+                delta_line,
+                delta_start,
+                length: token.length,
+                token_type: TOKEN_TYPES
+                    .iter()
+                    .position(|token_type| *token_type == token.token_type)
+                    .unwrap_or_default() as u32,
+                token_modifiers_bitset: token.modifiers,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use log::debug;
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    fn token(line: u32, start: u32, length: u32, token_type: SemanticTokenType) -> Token {
+        Token {
+            line,
+            start,
+            length,
+            token_type,
+            modifiers: 0,
+        }
+    }
+
+    #[test]
+    fn test_frontmatter_tokenizer() {
+        let yaml = "title: \"Hero: intro\" # shown in the nav\ndraft: true\norder: 2\nauthors:\n  - name: Ana\n\n# comment\ntags: [a, b]";
+
+        assert_eq!(
+            FrontmatterTokenizer::new(yaml, 1).tokens(),
+            vec![
+                token(1, 0, 5, SemanticTokenType::PROPERTY),
+                token(1, 7, 13, SemanticTokenType::STRING),
+                token(2, 0, 5, SemanticTokenType::PROPERTY),
+                token(2, 7, 4, SemanticTokenType::KEYWORD),
+                token(3, 0, 5, SemanticTokenType::PROPERTY),
+                token(3, 7, 1, SemanticTokenType::NUMBER),
+                token(4, 0, 7, SemanticTokenType::PROPERTY),
+                token(5, 4, 4, SemanticTokenType::PROPERTY),
+                token(5, 10, 3, SemanticTokenType::STRING),
+                token(8, 0, 4, SemanticTokenType::PROPERTY),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_semantic_tokens() {
+        let text = "---\ntitle: Hero\ndraft: false\n---\n\n# Hero <Badge />";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let data: Vec<_> = get_semantic_tokens(&ast, text)
+            .into_iter()
+            .map(|token| {
+                (
+                    token.delta_line,
+                    token.delta_start,
+                    token.length,
+                    token.token_type,
+                    token.token_modifiers_bitset,
+                )
+            })
+            .collect();
+        assert_eq!(
+            data,
+            vec![
+                (1, 0, 5, 0, 0),
+                (0, 7, 4, 1, 0),
+                (1, 0, 5, 0, 0),
+                (0, 7, 5, 3, 0),
+                (3, 8, 5, 4, REFERENCE),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mdx_tokenizer() {
+        let text = r#"import { Card as Box } from "./card.js"
+
+<Box title="a b" size={{ w: 1 }} open>
+  Total: {count * 2} {/* note */}
+</Box>
+
+Café <Badge label='x' />"#;
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let mut tokens = MdxTokenizer::new(&ast, text).tokens();
+        tokens.sort_by_key(|token| (token.line, token.start));
+        assert_eq!(
+            tokens,
+            vec![
+                Token {
+                    modifiers: DEFINITION,
+                    ..token(0, 17, 3, SemanticTokenType::FUNCTION)
+                },
+                Token {
+                    modifiers: REFERENCE,
+                    ..token(2, 1, 3, SemanticTokenType::CLASS)
+                },
+                token(2, 5, 5, SemanticTokenType::PROPERTY),
+                token(2, 17, 4, SemanticTokenType::PROPERTY),
+                token(2, 33, 4, SemanticTokenType::PROPERTY),
+                token(3, 10, 9, SemanticTokenType::VARIABLE),
+                Token {
+                    modifiers: REFERENCE,
+                    ..token(4, 2, 3, SemanticTokenType::CLASS)
+                },
+                Token {
+                    modifiers: REFERENCE,
+                    ..token(6, 6, 5, SemanticTokenType::CLASS)
+                },
+                token(6, 12, 5, SemanticTokenType::PROPERTY),
+            ]
+        );
+    }
+}
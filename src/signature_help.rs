@@ -0,0 +1,134 @@
+use tower_lsp::lsp_types::{
+    ParameterInformation, ParameterLabel, Position, SignatureHelp, SignatureInformation,
+};
+
+use crate::{
+    completion::{get_line_prefix, parse_tag_prefix, TagCursor},
+    config::ConfigValues,
+};
+
+/// Shows the props of `config.prop_schemas` for the element whose opening tag
+/// the cursor is in, as the parameters of a signature like
+/// `<$Partial src id />`. The prop being typed, or whose value is, is the
+/// active parameter.
+pub fn get_signature_help(
+    text: &str,
+    position: &Position,
+    config: &ConfigValues,
+) -> Option<SignatureHelp> {
+    let prefix = get_line_prefix(text, position);
+    let tag = parse_tag_prefix(&prefix)?;
+    let schema = config.prop_schemas.get(tag.name)?;
+
+    let active_field = match tag.cursor {
+        TagCursor::AttributeName(typed) if !typed.is_empty() => schema
+            .fields
+            .iter()
+            .position(|field| field.starts_with(typed)),
+        TagCursor::AttributeValue => tag
+            .attributes
+            .last()
+            .and_then(|name| schema.fields.iter().position(|field| field == name)),
+        _ => None,
+    };
+
+    let mut label = format!("<{}", tag.name);
+    let mut parameters = Vec::new();
+    for field in &schema.fields {
+        label.push(' ');
+        let start = label.encode_utf16().count() as u32;
+        label.push_str(field);
+        parameters.push(ParameterInformation {
+            label: ParameterLabel::LabelOffsets([start, label.encode_utf16().count() as u32]),
+            documentation: None,
+        });
+    }
+    label.push_str(" />");
+
+    Some(SignatureHelp {
+        signatures: vec![SignatureInformation {
+            label,
+            documentation: None,
+            parameters: Some(parameters),
+            active_parameter: active_field.map(|index| index as u32),
+        }],
+        active_signature: Some(0),
+        active_parameter: active_field.map(|index| index as u32),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::PropSchema, nodes::PARTIAL};
+
+    fn get_active_field(text: &str) -> Option<Option<u32>> {
+        let config = ConfigValues {
+            prop_schemas: [(
+                PARTIAL.to_string(),
+                PropSchema {
+                    fields: vec!["src".to_string(), "id".to_string(), "class".to_string()],
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let position = Position::new(0, text.encode_utf16().count() as u32);
+
+        get_signature_help(text, &position, &config).map(|help| help.active_parameter)
+    }
+
+    #[test]
+    fn test_signature_label() {
+        let config = ConfigValues {
+            prop_schemas: [(
+                "Café".to_string(),
+                PropSchema {
+                    fields: vec!["title".to_string(), "id".to_string()],
+                },
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+
+        let help = get_signature_help("<Café ", &Position::new(0, 6), &config).unwrap();
+        let signature = &help.signatures[0];
+        assert_eq!(signature.label, "<Café title id />");
+        assert_eq!(
+            signature.parameters,
+            Some(vec![
+                ParameterInformation {
+                    label: ParameterLabel::LabelOffsets([6, 11]),
+                    documentation: None,
+                },
+                ParameterInformation {
+                    label: ParameterLabel::LabelOffsets([12, 14]),
+                    documentation: None,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_active_parameter() {
+        assert_eq!(get_active_field("<$Partial "), Some(None));
+        assert_eq!(get_active_field("<$Partial cl"), Some(Some(2)));
+        assert_eq!(get_active_field("<$Partial src="), Some(Some(0)));
+        assert_eq!(
+            get_active_field("<$Partial src=\"hero.mdx\" id=\"ma"),
+            Some(Some(1))
+        );
+        assert_eq!(get_active_field("<$Partial src=\"hero.mdx\""), Some(None));
+        assert_eq!(get_active_field("<$Partial title={x > 1} "), Some(None));
+    }
+
+    #[test]
+    fn test_no_signature_help() {
+        assert_eq!(get_active_field("<$Partial"), None);
+        assert_eq!(get_active_field("<$Partial src=\"hero.mdx\" /> "), None);
+        assert_eq!(get_active_field("<Card "), None);
+        assert_eq!(get_active_field("Some text "), None);
+    }
+}
@@ -0,0 +1,249 @@
+use std::path::Path;
+
+use dashmap::DashSet;
+use markdown::mdast::Node;
+use tokio::fs;
+use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+
+use crate::{
+    ast::find_all_matches,
+    encoding::{get_node_range, offset_to_position},
+    nodes::headings::get_section,
+};
+
+pub const SPELLING_SOURCE: &str = "supermdx-spelling";
+
+const MAX_SUGGESTIONS: usize = 3;
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Reads a word list with one word per line. Words are compared lowercased.
+pub async fn load_wordlist(path: &Path) -> std::io::Result<DashSet<String>> {
+    let text = fs::read_to_string(path).await?;
+
+    Ok(text
+        .lines()
+        .map(|line| line.trim().to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect())
+}
+
+/// Checks the words of the section starting at the heading on `line` against
+/// `wordlist`, reporting each unknown word with the closest known words.
+pub fn check_section_spelling(
+    ast: &Node,
+    text: &str,
+    line: u32,
+    wordlist: &DashSet<String>,
+) -> Vec<Diagnostic> {
+    let Some(heading) = ast.children().and_then(|children| {
+        children.iter().find(|node| {
+            matches!(node, Node::Heading(_))
+                && get_node_range(text, node).is_some_and(|range| range.start.line == line)
+        })
+    }) else {
+        return Vec::new();
+    };
+
+    get_section(ast, heading)
+        .into_iter()
+        .flat_map(|node| find_all_matches(node, |node| matches!(node, Node::Text(_))))
+        .flat_map(|node| {
+            let Some(pos) = node.position() else {
+                return Vec::new();
+            };
+            let Some(source) = text.get(pos.start.offset..pos.end.offset) else {
+                return Vec::new();
+            };
+            let start = offset_to_position(text, pos.start.offset);
+
+            get_words(source, start)
+                .into_iter()
+                .filter(|(word, _)| !wordlist.contains(&word.to_lowercase()))
+                .map(|(word, range)| get_spelling_diagnostic(word, range, wordlist))
+                .collect()
+        })
+        .collect()
+}
+
+fn get_spelling_diagnostic(word: &str, range: Range, wordlist: &DashSet<String>) -> Diagnostic {
+    let suggestions = get_suggestions(&word.to_lowercase(), wordlist);
+    let message = if suggestions.is_empty() {
+        format!("Unknown word '{}'", word)
+    } else {
+        format!(
+            "Unknown word '{}'. Did you mean: {}?",
+            word,
+            suggestions.join(", ")
+        )
+    };
+
+    Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::INFORMATION),
+        source: Some(SPELLING_SOURCE.to_string()),
+        message,
+        ..Default::default()
+    }
+}
+
+/// Splits `source` into words (letters and inner apostrophes), returning each
+/// word with its range. `start` is the position of the first character.
+fn get_words(source: &str, start: Position) -> Vec<(&str, Range)> {
+    let mut words = Vec::new();
+    let mut position = start;
+    let mut word_start: Option<(usize, Position)> = None;
+
+    for (index, ch) in source.char_indices() {
+        let in_word = ch.is_alphabetic() || (ch == '\'' && word_start.is_some());
+        match (in_word, word_start) {
+            (true, None) => word_start = Some((index, position)),
+            (false, Some((begin, begin_position))) => {
+                words.push(get_word(source, begin, index, begin_position, position));
+                word_start = None;
+            }
+            _ => {}
+        }
+
+        if ch == '\n' {
+            position = Position {
+                line: position.line + 1,
+                character: 0,
+            };
+        } else {
+            position.character += ch.len_utf16() as u32;
+        }
+    }
+    if let Some((begin, begin_position)) = word_start {
+        words.push(get_word(
+            source,
+            begin,
+            source.len(),
+            begin_position,
+            position,
+        ));
+    }
+
+    words
+}
+
+/// Builds a word from `source[begin..end]`, dropping trailing apostrophes.
+fn get_word(
+    source: &str,
+    begin: usize,
+    end: usize,
+    start: Position,
+    end_position: Position,
+) -> (&str, Range) {
+    let word = &source[begin..end];
+    let trimmed = word.trim_end_matches('\'');
+    let end_position = Position {
+        line: end_position.line,
+        character: end_position.character - (word.len() - trimmed.len()) as u32,
+    };
+
+    (
+        trimmed,
+        Range {
+            start,
+            end: end_position,
+        },
+    )
+}
+
+fn get_suggestions(word: &str, wordlist: &DashSet<String>) -> Vec<String> {
+    let mut candidates: Vec<(usize, String)> = wordlist
+        .iter()
+        .filter_map(|known| {
+            let distance = edit_distance(word, &known);
+            (distance <= MAX_SUGGESTION_DISTANCE).then(|| (distance, known.clone()))
+        })
+        .collect();
+    candidates.sort();
+
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, known)| known)
+        .collect()
+}
+
+/// Levenshtein distance between two words.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_ch) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_ch) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_ch != *b_ch);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use log::debug;
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    fn wordlist(words: &[&str]) -> DashSet<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("teh", "the"), 2);
+        assert_eq!(edit_distance("instal", "install"), 1);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+
+    #[test]
+    fn test_check_section_spelling() {
+        let text =
+            "# Guide\n\nUnchecked wurds.\n\n## Setup\n\nInstal the *pakage*.\n\n## Usage\n\nMoar.";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let words = wordlist(&["setup", "install", "the", "package"]);
+        let diagnostics = check_section_spelling(&ast, text, 4, &words);
+
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.message.as_str(), diagnostic.range.start))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "Unknown word 'Instal'. Did you mean: install?",
+                    Position {
+                        line: 6,
+                        character: 0,
+                    }
+                ),
+                (
+                    "Unknown word 'pakage'. Did you mean: package?",
+                    Position {
+                        line: 6,
+                        character: 12,
+                    }
+                ),
+            ]
+        );
+        assert_eq!(diagnostics[1].range.end.character, 18);
+    }
+
+    #[test]
+    fn test_check_spelling_outside_heading() {
+        let text = "# Guide\n\nSome text.";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+
+        assert!(check_section_spelling(&ast, text, 2, &wordlist(&[])).is_empty());
+    }
+}
@@ -0,0 +1,575 @@
+use std::path::PathBuf;
+
+use dashmap::DashMap;
+use markdown::mdast::Node;
+use tower_lsp::lsp_types::{
+    DocumentSymbol, Location, Position, Range, SymbolInformation, SymbolKind, Url,
+};
+
+use crate::{
+    ast::find_node_heading_chain,
+    config::ConfigValues,
+    encoding::get_node_range,
+    nodes::{is_configured_partial, NodeExt},
+};
+
+pub fn get_document_symbols(ast: &Node, text: &str, config: &ConfigValues) -> Vec<DocumentSymbol> {
+    let mut symbols: Vec<_> = get_frontmatter_symbol(ast, text).into_iter().collect();
+    symbols.extend(get_outline_symbols(ast, text, config));
+    symbols
+}
+
+/// Builds the outline of a document: headings nest under the closest
+/// preceding heading of a lower depth, and the JSX elements of a section
+/// nest under its heading.
+fn get_outline_symbols(ast: &Node, text: &str, config: &ConfigValues) -> Vec<DocumentSymbol> {
+    let mut roots = Vec::new();
+    // Open headings, outermost first, with their depth.
+    let mut stack: Vec<(u8, DocumentSymbol)> = Vec::new();
+
+    fn close(stack: &mut Vec<(u8, DocumentSymbol)>, roots: &mut Vec<DocumentSymbol>) {
+        if let Some((_, symbol)) = stack.pop() {
+            push_symbol(stack, roots, symbol);
+        }
+    }
+
+    fn push_symbol(
+        stack: &mut [(u8, DocumentSymbol)],
+        roots: &mut Vec<DocumentSymbol>,
+        symbol: DocumentSymbol,
+    ) {
+        match stack.last_mut() {
+            Some((_, parent)) => parent.children.get_or_insert_with(Vec::new).push(symbol),
+            None => roots.push(symbol),
+        }
+    }
+
+    for child in ast.children().into_iter().flatten() {
+        let Node::Heading(heading) = child else {
+            for symbol in get_jsx_symbols_of(child, text, config) {
+                push_symbol(&mut stack, &mut roots, symbol);
+            }
+            continue;
+        };
+        let Some(range) = get_node_range(text, child) else {
+            continue;
+        };
+
+        while stack
+            .last()
+            .is_some_and(|(depth, _)| *depth >= heading.depth)
+        {
+            close(&mut stack, &mut roots);
+        }
+
+        #[allow(deprecated)]
+        stack.push((
+            heading.depth,
+            DocumentSymbol {
+                name: child.to_string(),
+                detail: None,
+                kind: SymbolKind::STRING,
+                tags: None,
+                deprecated: None,
+                range,
+                selection_range: range,
+                children: None,
+            },
+        ));
+    }
+
+    while !stack.is_empty() {
+        close(&mut stack, &mut roots);
+    }
+
+    roots
+}
+
+/// Lists the headings of every document in `ast_map`, whose text is in
+/// `text_map`, then the indexed partial files, whose name contains `query`,
+/// ignoring case.
+pub fn get_workspace_symbols(
+    ast_map: &DashMap<String, Node>,
+    text_map: &DashMap<String, String>,
+    partial_symbols: &DashMap<String, SymbolInformation>,
+    query: &str,
+) -> Vec<SymbolInformation> {
+    let query = query.to_lowercase();
+
+    let mut partials: Vec<SymbolInformation> = partial_symbols
+        .iter()
+        .map(|entry| entry.value().clone())
+        .collect();
+    partials.sort_by(|a, b| a.name.cmp(&b.name));
+
+    ast_map
+        .iter()
+        .filter_map(|entry| {
+            let uri = Url::parse(entry.key()).ok()?;
+            let text = text_map.get(entry.key())?;
+            Some(get_heading_symbols(entry.value(), &text, &uri))
+        })
+        .flatten()
+        .chain(partials)
+        .filter(|symbol| symbol.name.to_lowercase().contains(&query))
+        .collect()
+}
+
+/// Describes each partial file, as listed by `list_all_partials_in_dirs`,
+/// keyed by its path. The symbol is named after the `src` that includes it.
+pub fn get_partial_symbols(partials: &[(PathBuf, String)]) -> Vec<(String, SymbolInformation)> {
+    partials
+        .iter()
+        .filter_map(|(path, src)| {
+            let uri = Url::from_file_path(path).ok()?;
+
+            #[allow(deprecated)]
+            let symbol = SymbolInformation {
+                name: src.clone(),
+                kind: SymbolKind::FILE,
+                tags: None,
+                deprecated: None,
+                location: Location::new(uri, Range::default()),
+                container_name: Some("partials".to_string()),
+            };
+            Some((path.to_string_lossy().to_string(), symbol))
+        })
+        .collect()
+}
+
+/// Describes the top-level headings of a document. The container of each
+/// heading is the chain of its parent headings (`Introduction > Motivation`),
+/// or the file name for headings without a parent.
+fn get_heading_symbols(ast: &Node, text: &str, uri: &Url) -> Vec<SymbolInformation> {
+    let file_name = uri
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or_default()
+        .to_string();
+
+    ast.children()
+        .into_iter()
+        .flatten()
+        .filter(|node| matches!(node, Node::Heading(_)))
+        .filter_map(|heading| {
+            let range = get_node_range(text, heading)?;
+            let chain = find_node_heading_chain(ast, heading);
+            let parents: Vec<String> = chain
+                .iter()
+                .take_while(|node| !std::ptr::eq(**node, heading))
+                .map(|node| node.to_string())
+                .collect();
+            let container_name = if parents.is_empty() {
+                file_name.clone()
+            } else {
+                parents.join(" > ")
+            };
+
+            #[allow(deprecated)]
+            Some(SymbolInformation {
+                name: heading.to_string(),
+                kind: SymbolKind::STRING,
+                tags: None,
+                deprecated: None,
+                location: Location::new(uri.clone(), range),
+                container_name: Some(container_name),
+            })
+        })
+        .collect()
+}
+
+/// Describes the frontmatter block, including its `---` (or `+++`)
+/// delimiters, with one child per top-level key.
+fn get_frontmatter_symbol(ast: &Node, text: &str) -> Option<DocumentSymbol> {
+    let frontmatter = ast
+        .children()?
+        .iter()
+        .find(|node| matches!(node, Node::Yaml(_) | Node::Toml(_)))?;
+    let range = get_node_range(text, frontmatter)?;
+
+    // The value starts on the line after the opening delimiter.
+    let first_line = range.start.line + 1;
+    let value = frontmatter.to_string();
+    let keys = match frontmatter {
+        Node::Toml(_) => get_toml_keys(&value),
+        _ => get_yaml_keys(&value),
+    }
+    .into_iter()
+    .map(|(index, line, key)| {
+        let line_range = Range {
+            start: Position {
+                line: first_line + index as u32,
+                character: 0,
+            },
+            end: Position {
+                line: first_line + index as u32,
+                character: line.encode_utf16().count() as u32,
+            },
+        };
+
+        #[allow(deprecated)]
+        DocumentSymbol {
+            name: key.to_string(),
+            detail: None,
+            kind: SymbolKind::FIELD,
+            tags: None,
+            deprecated: None,
+            range: line_range,
+            selection_range: line_range,
+            children: None,
+        }
+    })
+    .collect();
+
+    #[allow(deprecated)]
+    Some(DocumentSymbol {
+        name: "Frontmatter".to_string(),
+        detail: None,
+        kind: SymbolKind::OBJECT,
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range: range,
+        children: Some(keys),
+    })
+}
+
+/// Returns the line index, line and name of each top-level YAML key, i.e.
+/// the unindented `key: value` lines.
+fn get_yaml_keys(value: &str) -> Vec<(usize, &str, &str)> {
+    value
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            !line.starts_with(|ch: char| ch.is_whitespace() || matches!(ch, '#' | '-'))
+        })
+        .filter_map(|(index, line)| {
+            let (key, _) = line.split_once(':')?;
+            Some((index, line, key.trim().trim_matches(['"', '\''])))
+        })
+        .collect()
+}
+
+/// Returns the line index, line and name of each top-level TOML key: the
+/// `key = value` lines before the first table, then the table headers.
+fn get_toml_keys(value: &str) -> Vec<(usize, &str, &str)> {
+    let mut keys = Vec::new();
+    let mut in_table = false;
+
+    for (index, line) in value.lines().enumerate() {
+        let trimmed = line.trim();
+        if let Some(header) = trimmed.strip_prefix('[') {
+            in_table = true;
+            let name = header
+                .trim_start_matches('[')
+                .split(']')
+                .next()
+                .unwrap_or_default();
+            keys.push((index, line, name.trim()));
+        } else if !in_table && !trimmed.starts_with('#') {
+            if let Some((key, _)) = trimmed.split_once('=') {
+                keys.push((index, line, key.trim().trim_matches(['"', '\''])));
+            }
+        }
+    }
+
+    keys
+}
+
+/// Collects symbols for the JSX elements below `node`. JSX elements nested
+/// inside other JSX elements become children of their closest JSX ancestor,
+/// even when there are non-JSX nodes (paragraphs, lists...) in between.
+fn get_jsx_symbols(node: &Node, text: &str, config: &ConfigValues) -> Vec<DocumentSymbol> {
+    node.children()
+        .into_iter()
+        .flatten()
+        .flat_map(|child| get_jsx_symbols_of(child, text, config))
+        .collect()
+}
+
+/// Like `get_jsx_symbols`, but `node` itself is included when it is a JSX
+/// element. Partials are listed as modules.
+fn get_jsx_symbols_of(node: &Node, text: &str, config: &ConfigValues) -> Vec<DocumentSymbol> {
+    if !node.is_any_jsx_element() {
+        return get_jsx_symbols(node, text, config);
+    }
+
+    let Some(range) = get_node_range(text, node) else {
+        return Vec::new();
+    };
+    let children = get_jsx_symbols(node, text, config);
+
+    #[allow(deprecated)]
+    let symbol = DocumentSymbol {
+        name: node.get_name().unwrap_or("<>").to_string(),
+        detail: None,
+        kind: if is_configured_partial(node, config) {
+            SymbolKind::MODULE
+        } else {
+            SymbolKind::CLASS
+        },
+        tags: None,
+        deprecated: None,
+        range,
+        selection_range: range,
+        children: if children.is_empty() {
+            None
+        } else {
+            Some(children)
+        },
+    };
+
+    vec![symbol]
+}
+
+#[cfg(test)]
+mod tests {
+    use log::debug;
+    use markdown::to_mdast;
+
+    use super::*;
+    use crate::parser::get_parser_options;
+
+    #[test]
+    fn test_nested_jsx_symbols() {
+        let text = r#"
+<Section>
+  <Header>
+    Some <Badge /> text.
+  </Header>
+
+  <$Partial src="a.mdx" />
+
+  <$Partial src="b.mdx" />
+</Section>
+"#
+        .trim();
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let symbols = get_document_symbols(&ast, text, &ConfigValues::default());
+        debug!("symbols: {:#?}", symbols);
+
+        assert_eq!(symbols.len(), 1);
+        let section = &symbols[0];
+        assert_eq!(section.name, "Section");
+
+        let section_children = section.children.as_ref().unwrap();
+        let names: Vec<_> = section_children.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["Header", "$Partial", "$Partial"]);
+
+        let header_children = section_children[0].children.as_ref().unwrap();
+        assert_eq!(header_children.len(), 1);
+        assert_eq!(header_children[0].name, "Badge");
+        assert!(header_children[0].children.is_none());
+    }
+
+    #[test]
+    fn test_no_symbols() {
+        let text = "This is a *test*.";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+
+        assert!(get_document_symbols(&ast, text, &ConfigValues::default()).is_empty());
+    }
+
+    fn get_outline(symbols: &[DocumentSymbol]) -> Vec<(String, SymbolKind, usize)> {
+        let mut outline = Vec::new();
+        let mut stack: Vec<(&DocumentSymbol, usize)> =
+            symbols.iter().rev().map(|symbol| (symbol, 0)).collect();
+        while let Some((symbol, level)) = stack.pop() {
+            outline.push((symbol.name.clone(), symbol.kind, level));
+            for child in symbol.children.iter().flatten().rev() {
+                stack.push((child, level + 1));
+            }
+        }
+        outline
+    }
+
+    #[test]
+    fn test_heading_outline() {
+        let text = r#"
+<Banner />
+
+# Guide
+
+## Install
+
+<$Partial src="install.mdx" />
+
+### From source
+
+## Usage
+
+# Reference
+
+### Options
+"#
+        .trim();
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let outline = get_outline(&get_document_symbols(&ast, text, &ConfigValues::default()));
+        let outline: Vec<_> = outline
+            .iter()
+            .map(|(name, kind, level)| (name.as_str(), *kind, *level))
+            .collect();
+        assert_eq!(
+            outline,
+            vec![
+                ("Banner", SymbolKind::CLASS, 0),
+                ("Guide", SymbolKind::STRING, 0),
+                ("Install", SymbolKind::STRING, 1),
+                ("$Partial", SymbolKind::MODULE, 2),
+                ("From source", SymbolKind::STRING, 2),
+                ("Usage", SymbolKind::STRING, 1),
+                ("Reference", SymbolKind::STRING, 0),
+                ("Options", SymbolKind::STRING, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_frontmatter_symbol() {
+        let text = r#"
+---
+title: Getting started
+tags:
+  - intro
+description: "A: B"
+---
+
+<Callout />
+"#
+        .trim();
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let symbols = get_document_symbols(&ast, text, &ConfigValues::default());
+        let names: Vec<_> = symbols.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["Frontmatter", "Callout"]);
+
+        let frontmatter = &symbols[0];
+        assert_eq!(frontmatter.kind, SymbolKind::OBJECT);
+        assert_eq!(frontmatter.range.start.line, 0);
+        assert_eq!(frontmatter.range.end.line, 5);
+
+        let keys = frontmatter.children.as_ref().unwrap();
+        let fields: Vec<_> = keys
+            .iter()
+            .map(|s| (s.name.as_str(), s.range.start.line))
+            .collect();
+        assert_eq!(fields, vec![("title", 1), ("tags", 2), ("description", 4)]);
+        assert!(keys.iter().all(|key| key.kind == SymbolKind::FIELD));
+    }
+
+    #[test]
+    fn test_toml_frontmatter_symbol() {
+        let text = r#"
++++
+title = "Getting started"
+
+[author]
+name = "Jane"
++++
+"#
+        .trim();
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let symbols = get_document_symbols(&ast, text, &ConfigValues::default());
+        let keys: Vec<_> = symbols[0]
+            .children
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(keys, vec!["title", "author"]);
+    }
+
+    fn get_containers(text: &str, query: &str) -> Vec<(String, Option<String>)> {
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+
+        let ast_map = DashMap::new();
+        ast_map.insert("file:///docs/guide.mdx".to_string(), ast);
+        let text_map = DashMap::new();
+        text_map.insert("file:///docs/guide.mdx".to_string(), text.to_string());
+
+        get_workspace_symbols(&ast_map, &text_map, &DashMap::new(), query)
+            .into_iter()
+            .map(|symbol| (symbol.name, symbol.container_name))
+            .collect()
+    }
+
+    #[test]
+    fn test_workspace_symbols_flat() {
+        let symbols = get_containers("# Install\n\n# Usage", "");
+
+        assert_eq!(
+            symbols,
+            vec![
+                ("Install".to_string(), Some("guide.mdx".to_string())),
+                ("Usage".to_string(), Some("guide.mdx".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_workspace_symbols_nested() {
+        let text = "# Introduction\n\n## Motivation\n\n### Prior art\n\n## Goals";
+
+        assert_eq!(
+            get_containers(text, ""),
+            vec![
+                ("Introduction".to_string(), Some("guide.mdx".to_string())),
+                ("Motivation".to_string(), Some("Introduction".to_string())),
+                (
+                    "Prior art".to_string(),
+                    Some("Introduction > Motivation".to_string())
+                ),
+                ("Goals".to_string(), Some("Introduction".to_string())),
+            ]
+        );
+        assert_eq!(
+            get_containers(text, "PRIOR"),
+            vec![(
+                "Prior art".to_string(),
+                Some("Introduction > Motivation".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn test_workspace_symbols_partials() {
+        let text = "# Buttons";
+        let ast = to_mdast(text, &get_parser_options()).unwrap();
+        debug!("{:#?}", ast);
+        let ast_map = DashMap::new();
+        ast_map.insert("file:///docs/guide.mdx".to_string(), ast);
+        let text_map = DashMap::new();
+        text_map.insert("file:///docs/guide.mdx".to_string(), text.to_string());
+
+        let partials = [
+            (
+                PathBuf::from("/partials/buttons/primary.mdx"),
+                "buttons/primary.mdx".to_string(),
+            ),
+            (PathBuf::from("/partials/hero.mdx"), "hero.mdx".to_string()),
+        ];
+        let partial_symbols: DashMap<_, _> = get_partial_symbols(&partials).into_iter().collect();
+
+        let symbols: Vec<_> =
+            get_workspace_symbols(&ast_map, &text_map, &partial_symbols, "BUTTON")
+                .into_iter()
+                .map(|symbol| (symbol.name, symbol.kind))
+                .collect();
+        assert_eq!(
+            symbols,
+            vec![
+                ("Buttons".to_string(), SymbolKind::STRING),
+                ("buttons/primary.mdx".to_string(), SymbolKind::FILE),
+            ]
+        );
+    }
+}
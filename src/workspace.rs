@@ -0,0 +1,180 @@
+use std::path::{Component, Path, PathBuf};
+
+use markdown::{mdast::Node, to_mdast};
+use tokio::fs;
+
+use crate::{
+    config::ConfigValues, nodes::partials::is_partial_file, parser::get_parser_options_for,
+};
+
+/// Recursively lists the files under `roots` accepted by `test`, skipping any
+/// directory listed in `exclude_dirs`. Directories that cannot be read are
+/// skipped.
+pub async fn find_files<F>(roots: &[PathBuf], exclude_dirs: &[PathBuf], test: F) -> Vec<PathBuf>
+where
+    F: Fn(&Path) -> bool,
+{
+    let mut files = Vec::new();
+    let mut pending: Vec<PathBuf> = roots.to_vec();
+
+    while let Some(dir) = pending.pop() {
+        if exclude_dirs.contains(&dir) {
+            continue;
+        }
+        let Ok(mut entries) = fs::read_dir(&dir).await else {
+            continue;
+        };
+
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            match entry.file_type().await {
+                Ok(file_type) if file_type.is_dir() => pending.push(path),
+                Ok(file_type) if file_type.is_file() && test(&path) => files.push(path),
+                _ => {}
+            }
+        }
+    }
+
+    files.sort();
+    files.dedup();
+    files
+}
+
+pub fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.contains(&ext))
+}
+
+/// Lists the `.mdx` and `.md` files under `workspace_root` and every
+/// `partials_dir`, honouring `exclude_dirs`. This is the partial index used by
+/// features that need to know about files that aren't open.
+pub async fn index_workspace_files(config: &ConfigValues) -> Vec<PathBuf> {
+    let mut roots = vec![config.workspace_root.clone()];
+    roots.extend(config.partials_dirs.iter().cloned());
+
+    find_files(&roots, &config.exclude_dirs, is_partial_file).await
+}
+
+/// Reads and parses a document that isn't open, with the parser options for
+/// its extension, returning its AST and text. Returns `None` when it can't be
+/// read or parsed.
+pub async fn parse_document_file(path: &Path, config: &ConfigValues) -> Option<(Node, String)> {
+    let text = fs::read_to_string(path).await.ok()?;
+    let ast = to_mdast(&text, &get_parser_options_for(path, config)).ok()?;
+    Some((ast, text))
+}
+
+/// Returns the path of `to` relative to the directory `from_dir`, stepping up
+/// with `..` where needed. Both paths are expected to be absolute.
+pub fn relative_path(from_dir: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from_dir.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut path = PathBuf::new();
+    for _ in common..from.len() {
+        path.push("..");
+    }
+    for component in &to[common..] {
+        path.push(component);
+    }
+    path
+}
+
+/// Resolves `.` and `..` components without touching the file system, so it
+/// also works for paths that don't exist.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_find_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("docs")).unwrap();
+        fs::create_dir_all(dir.path().join("node_modules/pkg")).unwrap();
+        fs::write(dir.path().join("index.mdx"), "").unwrap();
+        fs::write(dir.path().join("docs/guide.mdx"), "").unwrap();
+        fs::write(dir.path().join("docs/image.png"), "").unwrap();
+        fs::write(dir.path().join("node_modules/pkg/readme.mdx"), "").unwrap();
+
+        let files = find_files(
+            &[dir.path().to_path_buf()],
+            &[dir.path().join("node_modules")],
+            |path| has_extension(path, &["mdx"]),
+        )
+        .await;
+
+        assert_eq!(
+            files,
+            vec![
+                dir.path().join("docs/guide.mdx"),
+                dir.path().join("index.mdx")
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parse_document_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("index.mdx"), "# Index\n\n<Card />").unwrap();
+        fs::write(dir.path().join("broken.mdx"), "Some {text").unwrap();
+        let config = ConfigValues::default();
+
+        let (ast, text) = parse_document_file(&dir.path().join("index.mdx"), &config)
+            .await
+            .unwrap();
+        assert_eq!(ast.children().unwrap().len(), 2);
+        assert_eq!(text, "# Index\n\n<Card />");
+        assert!(parse_document_file(&dir.path().join("broken.mdx"), &config)
+            .await
+            .is_none());
+        assert!(
+            parse_document_file(&dir.path().join("missing.mdx"), &config)
+                .await
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path(
+                Path::new("/docs/guides"),
+                Path::new("/docs/guides/intro.mdx")
+            ),
+            PathBuf::from("intro.mdx")
+        );
+        assert_eq!(
+            relative_path(
+                Path::new("/docs/guides"),
+                Path::new("/docs/partials/hero.mdx")
+            ),
+            PathBuf::from("../partials/hero.mdx")
+        );
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(
+            normalize_path(Path::new("/docs/guides/./../partials/hero.mdx")),
+            PathBuf::from("/docs/partials/hero.mdx")
+        );
+    }
+}